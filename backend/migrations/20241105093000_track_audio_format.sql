@@ -0,0 +1,5 @@
+-- Detected container / codec of the assembled upload, used to answer with the right Content-Type
+ALTER TABLE tracks
+    ADD COLUMN container VARCHAR(20),
+    ADD COLUMN codec VARCHAR(20),
+    ADD COLUMN mime_type VARCHAR(100);
@@ -0,0 +1,4 @@
+-- Who may stream a track: everyone, only people with the link, or just the owner
+ALTER TABLE tracks
+    ADD COLUMN visibility VARCHAR(20) NOT NULL DEFAULT 'public'
+        CHECK (visibility IN ('public', 'unlisted', 'private'));
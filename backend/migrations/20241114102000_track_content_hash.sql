@@ -0,0 +1,4 @@
+-- Assembled audio is stored under the SHA-256 of its content, several tracks may share one file
+ALTER TABLE tracks ADD COLUMN content_hash CHAR(64);
+
+CREATE INDEX idx_tracks_content_hash ON tracks (content_hash);
@@ -0,0 +1,2 @@
+-- SHA-256 the client declared for the whole file, checked once the chunks are assembled
+ALTER TABLE audio_files ADD COLUMN file_hash CHAR(64);
@@ -0,0 +1,16 @@
+-- One row per received chunk, so retried and out-of-order chunks are counted once
+CREATE TABLE upload_chunks (
+    track_id UUID NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    chunk_number INTEGER NOT NULL,
+    chunk_hash CHAR(64) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (track_id, chunk_number)
+);
+
+-- Parallel chunks of one upload share a single audio_files row
+DELETE FROM audio_files a
+USING audio_files b
+WHERE a.track_id = b.track_id
+  AND (a.created_at, a.id) < (b.created_at, b.id);
+
+CREATE UNIQUE INDEX idx_audio_files_track_id ON audio_files (track_id);
@@ -0,0 +1,5 @@
+-- tus uploads append chunks of any size, the upload offset is the sum of them
+ALTER TABLE upload_chunks ADD COLUMN chunk_size BIGINT NOT NULL DEFAULT 0;
+
+-- Declared Upload-Length of a tus upload, NULL for the multipart protocol
+ALTER TABLE audio_files ADD COLUMN upload_length BIGINT;
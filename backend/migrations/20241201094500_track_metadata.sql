@@ -0,0 +1,10 @@
+-- Descriptive metadata, pre-filled from the file's tags when the upload is assembled
+ALTER TABLE tracks
+    ADD COLUMN album VARCHAR(255),
+    ADD COLUMN album_artist VARCHAR(255),
+    ADD COLUMN track_number INTEGER,
+    ADD COLUMN disc_number INTEGER,
+    ADD COLUMN release_year INTEGER,
+    ADD COLUMN genre VARCHAR(255),
+    ADD COLUMN isrc CHAR(12),
+    ADD COLUMN lyrics TEXT;
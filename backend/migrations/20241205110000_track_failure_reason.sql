@@ -0,0 +1,2 @@
+-- Tracks whose audio couldn't be processed are kept as `failed` with the reason
+ALTER TABLE tracks ADD COLUMN failure_reason TEXT;
@@ -0,0 +1,32 @@
+-- Background work (transcoding, analysis...) queued for the job workers
+CREATE TABLE jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind VARCHAR(50) NOT NULL,
+    track_id UUID REFERENCES tracks(id) ON DELETE CASCADE,
+    payload TEXT,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    run_after TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_jobs_pending ON jobs (run_after) WHERE status = 'pending';
+
+-- Streamable versions of a track next to the original upload
+CREATE TABLE track_renditions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    track_id UUID NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    quality VARCHAR(20) NOT NULL,
+    codec VARCHAR(50) NOT NULL,
+    mime_type VARCHAR(100) NOT NULL,
+    bitrate INTEGER,
+    storage_key TEXT NOT NULL,
+    file_size BIGINT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (track_id, quality)
+);
+
+-- Rendition picked when a stream request doesn't ask for one
+ALTER TABLE users ADD COLUMN stream_quality VARCHAR(20) NOT NULL DEFAULT 'auto';
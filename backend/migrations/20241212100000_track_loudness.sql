@@ -0,0 +1,7 @@
+-- EBU R128 measurements, filled in by the loudness analysis job
+ALTER TABLE tracks
+    ADD COLUMN loudness DOUBLE PRECISION,
+    ADD COLUMN loudness_range DOUBLE PRECISION,
+    ADD COLUMN true_peak DOUBLE PRECISION,
+    ADD COLUMN album_loudness DOUBLE PRECISION,
+    ADD COLUMN album_true_peak DOUBLE PRECISION;
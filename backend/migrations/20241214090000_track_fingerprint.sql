@@ -0,0 +1,5 @@
+-- Chromaprint-style fingerprint of the start of the track, filled in by the
+-- fingerprint job, and the earlier track it turned out to be a copy of
+ALTER TABLE tracks
+    ADD COLUMN fingerprint INTEGER[],
+    ADD COLUMN duplicate_of UUID REFERENCES tracks(id) ON DELETE SET NULL;
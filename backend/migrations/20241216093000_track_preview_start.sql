@@ -0,0 +1,3 @@
+-- Seconds into the track where its preview starts, picked by the owner.
+-- Without one the preview job looks for the loudest part.
+ALTER TABLE tracks ADD COLUMN preview_start INTEGER;
@@ -0,0 +1,13 @@
+-- Bytes of the stored original, what the track counts against its owner's quota
+ALTER TABLE tracks ADD COLUMN file_size BIGINT;
+
+-- Lossless tracks already know theirs, older lossy ones count as empty
+UPDATE tracks t
+SET file_size = r.file_size
+FROM track_renditions r
+WHERE r.track_id = t.id AND r.quality = 'lossless';
+
+-- Per-account quotas, NULL for the configured defaults
+ALTER TABLE users
+    ADD COLUMN storage_quota BIGINT,
+    ADD COLUMN track_quota INTEGER;
@@ -0,0 +1,34 @@
+-- Albums put together from uploaded archives. `import_status` is `pending`
+-- until every file of the archive has been tried.
+CREATE TABLE albums (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    title VARCHAR(255) NOT NULL,
+    artist VARCHAR(255),
+    release_year INTEGER,
+    thumbnail_name VARCHAR(255),
+    import_status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_albums_user_id ON albums (user_id);
+
+CREATE TRIGGER update_albums_updated_at
+BEFORE UPDATE ON albums
+FOR EACH ROW EXECUTE PROCEDURE update_updated_at_column();
+
+ALTER TABLE tracks ADD COLUMN album_id UUID REFERENCES albums(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_tracks_album_id ON tracks (album_id);
+
+-- What happened to every audio file of an archive: the track it became,
+-- and why it failed if it did
+CREATE TABLE album_files (
+    album_id UUID NOT NULL REFERENCES albums(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    track_id UUID REFERENCES tracks(id) ON DELETE SET NULL,
+    error TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (album_id, path)
+);
@@ -0,0 +1,34 @@
+-- `admin` accounts can import libraries from directories on the server
+ALTER TABLE users ADD COLUMN role VARCHAR(20) NOT NULL DEFAULT 'user';
+
+-- A directory on the server mirrored into one user's tracks. Every scan
+-- brings the tracks up to date with what's on disk. `scan_status` is `idle`
+-- between scans, `pending` while one is queued.
+CREATE TABLE libraries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    root_path TEXT NOT NULL,
+    scan_status VARCHAR(20) NOT NULL DEFAULT 'idle',
+    scanned_at TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (user_id, root_path)
+);
+
+CREATE TRIGGER update_libraries_updated_at
+BEFORE UPDATE ON libraries
+FOR EACH ROW EXECUTE PROCEDURE update_updated_at_column();
+
+-- Audio files of a library as they were at the last scan, paths relative to
+-- the root. Files whose size and modification time still match are skipped.
+CREATE TABLE library_files (
+    library_id UUID NOT NULL REFERENCES libraries(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    file_size BIGINT NOT NULL,
+    modified_at TIMESTAMP NOT NULL,
+    track_id UUID REFERENCES tracks(id) ON DELETE SET NULL,
+    error TEXT,
+    PRIMARY KEY (library_id, path)
+);
+
+CREATE INDEX idx_library_files_track_id ON library_files (track_id);
@@ -0,0 +1,30 @@
+-- Every audio a track has had, so a replaced master can be rolled back.
+-- `tracks.audio_version` is the one playing now.
+CREATE TABLE track_versions (
+    track_id UUID NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    content_hash CHAR(64) NOT NULL,
+    container VARCHAR(20),
+    codec VARCHAR(20),
+    mime_type VARCHAR(100),
+    duration INTERVAL,
+    file_size BIGINT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (track_id, version)
+);
+
+ALTER TABLE tracks
+    ADD COLUMN audio_version INTEGER,
+    -- Set on uploads that carry new audio for an existing track
+    ADD COLUMN replaces UUID REFERENCES tracks(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_tracks_replaces ON tracks (replaces);
+
+-- Tracks that already have audio start at version 1
+INSERT INTO track_versions (track_id, version, content_hash, container, codec, mime_type, duration, file_size)
+SELECT id, 1, content_hash, container, codec, mime_type, duration, file_size
+FROM tracks
+WHERE upload_status = 'complete' AND content_hash IS NOT NULL;
+
+UPDATE tracks SET audio_version = 1
+WHERE upload_status = 'complete' AND content_hash IS NOT NULL;
@@ -0,0 +1,2 @@
+-- Free text the owner writes about a track, never read from tags
+ALTER TABLE tracks ADD COLUMN description TEXT;
@@ -0,0 +1,102 @@
+use std::{fs::File, io::ErrorKind, ops::ControlFlow, path::Path};
+
+use symphonia::core::{
+    audio::{SampleBuffer, SignalSpec},
+    codecs::DecoderOptions,
+    errors::Error,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+use super::probe::sniff_container;
+
+// Jobs decode on blocking threads and hand the error back, so it has to be Send
+pub type DecodeError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmFormat {
+    pub sample_rate: u32,
+    pub channels: usize,
+}
+
+// Decodes the default track of an audio file and hands the samples to `sink`
+// as they come out, interleaved f32 at the file's own rate. Only one packet
+// is held in memory, whatever the length of the track. The sink breaks once
+// it has seen enough, the rest of the file isn't decoded.
+pub fn decode_audio<F>(file_path: &Path, mut sink: F) -> Result<(), DecodeError>
+where
+    F: FnMut(PcmFormat, &[f32]) -> Result<ControlFlow<()>, DecodeError>,
+{
+    let mut hint = Hint::new();
+    if let Some(container) = sniff_container(file_path)? {
+        hint.with_extension(container.extension());
+    }
+
+    let file = File::open(file_path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions {
+            enable_gapless: true,
+            ..Default::default()
+        },
+        &MetadataOptions::default(),
+    )?;
+
+    let track = probed.format.default_track().ok_or("No track found")?;
+    let track_id = track.id;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut buffer: Option<(SignalSpec, SampleBuffer<f32>)> = None;
+
+    loop {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt packet is skipped, like players do
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let spec = *decoded.spec();
+        let needed = decoded.capacity() * spec.channels.count();
+
+        let samples = match &mut buffer {
+            Some((buffer_spec, samples))
+                if *buffer_spec == spec && samples.capacity() >= needed =>
+            {
+                samples
+            }
+            _ => {
+                let samples = SampleBuffer::new(decoded.capacity() as u64, spec);
+                &mut buffer.insert((spec, samples)).1
+            }
+        };
+        samples.copy_interleaved_ref(decoded);
+
+        let format = PcmFormat {
+            sample_rate: spec.rate,
+            channels: spec.channels.count(),
+        };
+        if sink(format, samples.samples())?.is_break() {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,401 @@
+use std::{collections::HashSet, ops::ControlFlow, path::Path, sync::Arc};
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use super::decode::{decode_audio, DecodeError, PcmFormat};
+
+// Chromaprint's parameters: 11025 Hz mono, 4096 sample frames every third of
+// a frame, chroma of 28 Hz - 3.5 kHz, the first two minutes
+const SAMPLE_RATE: u32 = 11025;
+const FRAME_SIZE: usize = 4096;
+const FRAME_STEP: usize = FRAME_SIZE / 3;
+const MIN_FREQUENCY: f64 = 28.0;
+const MAX_FREQUENCY: f64 = 3520.0;
+const MAX_SECONDS: usize = 120;
+
+const BANDS: usize = 12;
+// Smooths every chroma band over 5 frames
+const CHROMA_FILTER: [f64; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+// Widest classifier, in frames
+const FILTER_WIDTH: usize = 16;
+
+// Alignments tried when comparing, about 10 seconds either way
+const MAX_OFFSET: isize = 80;
+// Overlaps shorter than about 5 seconds don't say much
+const MIN_OVERLAP: usize = 40;
+
+// A filter over a `height` bands by `width` frames area of the chroma image,
+// quantized to 2 bits. Chromaprint's default classifiers, trained on real music.
+struct Classifier {
+    kind: u8,
+    band: usize,
+    height: usize,
+    width: usize,
+    thresholds: [f64; 3],
+}
+
+const fn classifier(
+    kind: u8,
+    band: usize,
+    height: usize,
+    width: usize,
+    thresholds: [f64; 3],
+) -> Classifier {
+    Classifier {
+        kind,
+        band,
+        height,
+        width,
+        thresholds,
+    }
+}
+
+const CLASSIFIERS: [Classifier; 16] = [
+    classifier(0, 4, 3, 15, [1.98215, 2.35817, 2.63523]),
+    classifier(4, 4, 6, 15, [-1.03809, -0.651211, -0.282167]),
+    classifier(1, 0, 4, 16, [-0.298702, 0.119262, 0.558497]),
+    classifier(3, 8, 2, 12, [-0.105439, 0.0153946, 0.135898]),
+    classifier(3, 4, 4, 8, [-0.142891, 0.0258736, 0.200632]),
+    classifier(4, 0, 3, 5, [-0.826319, -0.590612, -0.368214]),
+    classifier(1, 2, 2, 9, [-0.557409, -0.233035, 0.0534525]),
+    classifier(2, 7, 3, 4, [-0.0646826, 0.00620476, 0.0784847]),
+    classifier(2, 6, 2, 16, [-0.192387, -0.029699, 0.215855]),
+    classifier(2, 1, 3, 2, [-0.0397818, -0.00568076, 0.0292026]),
+    classifier(5, 10, 1, 15, [-0.53823, -0.369934, -0.190235]),
+    classifier(3, 6, 2, 10, [-0.124877, 0.0296483, 0.139239]),
+    classifier(2, 1, 1, 14, [-0.101475, 0.0225617, 0.231971]),
+    classifier(3, 5, 6, 4, [-0.0799915, -0.00729616, 0.063262]),
+    classifier(1, 9, 2, 12, [-0.272556, 0.019424, 0.302559]),
+    classifier(3, 4, 2, 14, [-0.164292, -0.0321188, 0.0846339]),
+];
+
+// Chromaprint-style fingerprint: one 32 bit word for every ~124 ms of the
+// start of the track. Survives re-encoding, not editing.
+pub fn fingerprint_audio(input: &Path) -> Result<Vec<u32>, DecodeError> {
+    let mut chroma: Option<ChromaExtractor> = None;
+
+    decode_audio(input, |format, samples| {
+        let chroma = match &mut chroma {
+            Some(chroma) if chroma.format == format => chroma,
+            Some(_) => return Err("Sample rate or channels change mid-stream".into()),
+            None => chroma.insert(ChromaExtractor::new(format)),
+        };
+
+        Ok(chroma.push(samples))
+    })?;
+
+    let image = chroma.ok_or("No audio decoded")?.image;
+    Ok(fingerprint_image(&image))
+}
+
+// Steady tones and silence repeat the same few words, and any two of them
+// look alike. Music hardly ever repeats a word.
+pub fn is_distinctive(fingerprint: &[u32]) -> bool {
+    let distinct: HashSet<u32> = fingerprint.iter().copied().collect();
+    fingerprint.len() >= MIN_OVERLAP && distinct.len() * 4 >= fingerprint.len()
+}
+
+// Share of identical bits at the best alignment, about 0.5 for unrelated audio
+pub fn similarity(a: &[u32], b: &[u32]) -> f64 {
+    let mut best = 0.0;
+
+    for offset in -MAX_OFFSET..=MAX_OFFSET {
+        let (a, b) = if offset < 0 {
+            (a, b.get(offset.unsigned_abs()..).unwrap_or_default())
+        } else {
+            (a.get(offset as usize..).unwrap_or_default(), b)
+        };
+
+        let overlap = a.len().min(b.len());
+        if overlap < MIN_OVERLAP {
+            continue;
+        }
+
+        let errors: u32 = a.iter().zip(b).map(|(a, b)| (a ^ b).count_ones()).sum();
+        let score = 1.0 - errors as f64 / (32 * overlap) as f64;
+        if score > best {
+            best = score;
+        }
+    }
+
+    best
+}
+
+struct ChromaExtractor {
+    format: PcmFormat,
+    // Mono downsampling by averaging, input samples per output sample
+    step: f64,
+    position: f64,
+    next: f64,
+    sum: f64,
+    count: u32,
+    last: f64,
+    buffer: Vec<f64>,
+    fft: Arc<dyn Fft<f64>>,
+    window: Vec<f64>,
+    // FFT bin to chroma band, for the bins in the frequency range
+    bands: Vec<(usize, usize)>,
+    recent: Vec<[f64; BANDS]>,
+    image: Vec<[f64; BANDS]>,
+}
+
+impl ChromaExtractor {
+    fn new(format: PcmFormat) -> Self {
+        let step = format.sample_rate as f64 / SAMPLE_RATE as f64;
+
+        let index =
+            |frequency: f64| (FRAME_SIZE as f64 * frequency / SAMPLE_RATE as f64).round() as usize;
+        let bands = (index(MIN_FREQUENCY).max(1)..index(MAX_FREQUENCY).min(FRAME_SIZE / 2))
+            .map(|bin| {
+                let frequency = bin as f64 * SAMPLE_RATE as f64 / FRAME_SIZE as f64;
+                // Octaves above A0, the fraction is the note
+                let octave = (frequency / 27.5).log2();
+                let band = (BANDS as f64 * (octave - octave.floor())) as usize;
+                (bin, band.min(BANDS - 1))
+            })
+            .collect();
+
+        let window = (0..FRAME_SIZE)
+            .map(|n| {
+                0.54 - 0.46
+                    * (2.0 * std::f64::consts::PI * n as f64 / (FRAME_SIZE - 1) as f64).cos()
+            })
+            .collect();
+
+        ChromaExtractor {
+            format,
+            step,
+            position: 0.0,
+            next: step,
+            sum: 0.0,
+            count: 0,
+            last: 0.0,
+            buffer: Vec::with_capacity(FRAME_SIZE * 2),
+            fft: FftPlanner::new().plan_fft_forward(FRAME_SIZE),
+            window,
+            bands,
+            recent: Vec::with_capacity(CHROMA_FILTER.len()),
+            image: Vec::new(),
+        }
+    }
+
+    // Breaks once the first two minutes are in
+    fn push(&mut self, samples: &[f32]) -> ControlFlow<()> {
+        let channels = self.format.channels;
+        let max_frames = MAX_SECONDS * SAMPLE_RATE as usize / FRAME_STEP;
+
+        for frame in samples.chunks_exact(channels) {
+            if self.image.len() >= max_frames {
+                return ControlFlow::Break(());
+            }
+
+            // 16 bit scale, like Chromaprint sees it
+            let sample = frame.iter().sum::<f32>() as f64 / channels as f64 * 32768.0;
+            self.sum += sample;
+            self.count += 1;
+            self.position += 1.0;
+
+            // Upsampling repeats the last value
+            while self.position >= self.next {
+                if self.count > 0 {
+                    self.last = self.sum / self.count as f64;
+                    (self.sum, self.count) = (0.0, 0);
+                }
+                self.buffer.push(self.last);
+                self.next += self.step;
+            }
+
+            if self.buffer.len() >= FRAME_SIZE {
+                self.end_frame();
+                self.buffer.drain(..FRAME_STEP);
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    fn end_frame(&mut self) {
+        let mut spectrum: Vec<Complex<f64>> = self.buffer[..FRAME_SIZE]
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, window)| Complex::new(sample * window, 0.0))
+            .collect();
+        self.fft.process(&mut spectrum);
+
+        let mut chroma = [0.0; BANDS];
+        for (bin, band) in &self.bands {
+            chroma[*band] += spectrum[*bin].norm_sqr();
+        }
+
+        if self.recent.len() == CHROMA_FILTER.len() {
+            self.recent.remove(0);
+        }
+        self.recent.push(chroma);
+        if self.recent.len() < CHROMA_FILTER.len() {
+            return;
+        }
+
+        let mut filtered = [0.0; BANDS];
+        for (chroma, coefficient) in self.recent.iter().zip(CHROMA_FILTER) {
+            for (value, energy) in filtered.iter_mut().zip(chroma) {
+                *value += coefficient * energy;
+            }
+        }
+
+        let norm = filtered
+            .iter()
+            .map(|value| value * value)
+            .sum::<f64>()
+            .sqrt();
+        if norm < 0.01 {
+            filtered = [0.0; BANDS];
+        } else {
+            filtered.iter_mut().for_each(|value| *value /= norm);
+        }
+        self.image.push(filtered);
+    }
+}
+
+fn fingerprint_image(image: &[[f64; BANDS]]) -> Vec<u32> {
+    if image.len() < FILTER_WIDTH {
+        return Vec::new();
+    }
+
+    // Sums of the image above and left of every point
+    let mut integral = vec![[0.0; BANDS + 1]; image.len() + 1];
+    for (frame, chroma) in image.iter().enumerate() {
+        for band in 0..BANDS {
+            integral[frame + 1][band + 1] =
+                chroma[band] + integral[frame][band + 1] + integral[frame + 1][band]
+                    - integral[frame][band];
+        }
+    }
+    let area = |x1: usize, y1: usize, x2: usize, y2: usize| {
+        integral[x2][y2] - integral[x1][y2] - integral[x2][y1] + integral[x1][y1]
+    };
+
+    (0..=image.len() - FILTER_WIDTH)
+        .map(|x| {
+            CLASSIFIERS.iter().fold(0u32, |bits, classifier| {
+                let value = classify(classifier, x, &area);
+                let [t0, t1, t2] = classifier.thresholds;
+                let quantized = match value {
+                    v if v < t0 => 0,
+                    v if v < t1 => 1,
+                    v if v < t2 => 2,
+                    _ => 3,
+                };
+                // Gray code, neighbouring levels differ by one bit
+                (bits << 2) | [0, 1, 3, 2][quantized]
+            })
+        })
+        .collect()
+}
+
+fn classify(
+    classifier: &Classifier,
+    x: usize,
+    area: &impl Fn(usize, usize, usize, usize) -> f64,
+) -> f64 {
+    let (y, w, h) = (classifier.band, classifier.width, classifier.height);
+    let (w_2, h_2, w_3, h_3) = (w / 2, h / 2, w / 3, h / 3);
+
+    let (a, b) = match classifier.kind {
+        0 => (area(x, y, x + w, y + h), 0.0),
+        // Halves of the bands
+        1 => (area(x, y + h_2, x + w, y + h), area(x, y, x + w, y + h_2)),
+        // Halves in time
+        2 => (area(x + w_2, y, x + w, y + h), area(x, y, x + w_2, y + h)),
+        // Quadrants
+        3 => (
+            area(x, y + h_2, x + w_2, y + h) + area(x + w_2, y, x + w, y + h_2),
+            area(x, y, x + w_2, y + h_2) + area(x + w_2, y + h_2, x + w, y + h),
+        ),
+        // Middle third of the bands
+        4 => (
+            area(x, y + h_3, x + w, y + 2 * h_3),
+            area(x, y, x + w, y + h_3) + area(x, y + 2 * h_3, x + w, y + h),
+        ),
+        // Middle third in time
+        _ => (
+            area(x + w_3, y, x + 2 * w_3, y + h),
+            area(x, y, x + w_3, y + h) + area(x + 2 * w_3, y, x + w, y + h),
+        ),
+    };
+
+    (1.0 + a).ln() - (1.0 + b).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stand-in for a fingerprint of music, no two words alike
+    fn words(seed: u32, len: usize) -> Vec<u32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_fingerprints_match() {
+        let fingerprint = words(1, 200);
+        assert_eq!(similarity(&fingerprint, &fingerprint), 1.0);
+    }
+
+    #[test]
+    fn aligns_shifted_fingerprints() {
+        // The same recording with a few seconds more lead-in
+        let fingerprint = words(1, 200);
+        let mut shifted = words(2, 30);
+        shifted.extend(&fingerprint);
+
+        assert_eq!(similarity(&fingerprint, &shifted), 1.0);
+        assert_eq!(similarity(&shifted, &fingerprint), 1.0);
+
+        // Further apart than the offsets tried
+        let mut shifted = words(2, MAX_OFFSET as usize + 1);
+        shifted.extend(&fingerprint);
+        assert!(similarity(&fingerprint, &shifted) < 0.6);
+    }
+
+    #[test]
+    fn reencoding_noise_still_matches() {
+        let fingerprint = words(1, 200);
+        let noisy: Vec<u32> = fingerprint
+            .iter()
+            .zip(words(3, 200))
+            .map(|(word, noise)| word ^ (noise & noise >> 8 & 0x0101_0101))
+            .collect();
+
+        assert!(similarity(&fingerprint, &noisy) > 0.95);
+    }
+
+    #[test]
+    fn unrelated_fingerprints_score_about_half() {
+        let score = similarity(&words(1, 200), &words(2, 200));
+        assert!(score > 0.45 && score < 0.6, "{}", score);
+    }
+
+    #[test]
+    fn short_overlaps_dont_count() {
+        let fingerprint = words(1, MIN_OVERLAP - 1);
+        assert_eq!(similarity(&fingerprint, &fingerprint), 0.0);
+    }
+
+    #[test]
+    fn steady_tones_are_not_distinctive() {
+        assert!(is_distinctive(&words(1, 200)));
+        assert!(!is_distinctive(&[0x1234_5678; 200]));
+        assert!(!is_distinctive(&words(1, MIN_OVERLAP - 1)));
+
+        let repeating: Vec<u32> = words(1, 4).into_iter().cycle().take(200).collect();
+        assert!(!is_distinctive(&repeating));
+    }
+}
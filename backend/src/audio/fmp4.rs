@@ -0,0 +1,278 @@
+// Minimal fragmented MP4 (ISO/IEC 14496-12) writer for single track audio,
+// just enough for HLS: an init segment with the codec setup and media
+// segments holding one `moof` + `mdat` each.
+
+// The only track in every file
+const TRACK_ID: u32 = 1;
+
+pub struct AudioTrack {
+    // Ticks per second of sample durations and decode times
+    pub timescale: u32,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub entry: SampleEntry,
+}
+
+pub enum SampleEntry {
+    // Body of the `dOps` box
+    Opus(Vec<u8>),
+    // MPEG-4 audio, AAC or MP3 depending on the object type
+    Mp4a {
+        object_type: u8,
+        decoder_config: Option<Vec<u8>>,
+    },
+    // FLAC STREAMINFO block
+    Flac(Vec<u8>),
+}
+
+pub struct Sample {
+    pub duration: u32,
+    pub data: Vec<u8>,
+}
+
+fn write_box(out: &mut Vec<u8>, kind: &[u8; 4], body: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(kind);
+    body(out);
+
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn write_full_box(
+    out: &mut Vec<u8>,
+    kind: &[u8; 4],
+    version: u8,
+    flags: u32,
+    body: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(out, kind, |out| {
+        out.extend_from_slice(&((version as u32) << 24 | flags).to_be_bytes());
+        body(out);
+    });
+}
+
+// MPEG-4 descriptors carry their size as 7 bits per byte
+fn write_descriptor(out: &mut Vec<u8>, tag: u8, body: &[u8]) {
+    out.push(tag);
+
+    let mut size = body.len();
+    let mut bytes = vec![(size & 0x7f) as u8];
+    size >>= 7;
+    while size > 0 {
+        bytes.push((size & 0x7f) as u8 | 0x80);
+        size >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+    out.extend_from_slice(body);
+}
+
+const UNITY_MATRIX: [u32; 9] = [0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x4000_0000];
+
+fn write_matrix(out: &mut Vec<u8>) {
+    for value in UNITY_MATRIX {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+// `ftyp` + `moov` describing the track, with no samples of its own
+pub fn init_segment(track: &AudioTrack) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    write_box(&mut out, b"ftyp", |out| {
+        out.extend_from_slice(b"iso6");
+        out.extend_from_slice(&0u32.to_be_bytes());
+        for brand in [b"iso6", b"cmfc", b"mp41"] {
+            out.extend_from_slice(brand);
+        }
+    });
+
+    write_box(&mut out, b"moov", |out| {
+        write_full_box(out, b"mvhd", 0, 0, |out| {
+            out.extend_from_slice(&[0; 8]); // creation and modification time
+            out.extend_from_slice(&track.timescale.to_be_bytes());
+            out.extend_from_slice(&0u32.to_be_bytes()); // duration, fragments carry it
+            out.extend_from_slice(&0x10000u32.to_be_bytes()); // rate 1.0
+            out.extend_from_slice(&0x100u16.to_be_bytes()); // volume 1.0
+            out.extend_from_slice(&[0; 10]);
+            write_matrix(out);
+            out.extend_from_slice(&[0; 24]);
+            out.extend_from_slice(&(TRACK_ID + 1).to_be_bytes());
+        });
+
+        write_box(out, b"trak", |out| {
+            // Enabled and in the movie
+            write_full_box(out, b"tkhd", 0, 3, |out| {
+                out.extend_from_slice(&[0; 8]);
+                out.extend_from_slice(&TRACK_ID.to_be_bytes());
+                out.extend_from_slice(&[0; 4]);
+                out.extend_from_slice(&0u32.to_be_bytes()); // duration
+                out.extend_from_slice(&[0; 8]);
+                out.extend_from_slice(&[0; 4]); // layer and alternate group
+                out.extend_from_slice(&0x100u16.to_be_bytes());
+                out.extend_from_slice(&[0; 2]);
+                write_matrix(out);
+                out.extend_from_slice(&[0; 8]); // width and height
+            });
+
+            write_box(out, b"mdia", |out| {
+                write_full_box(out, b"mdhd", 0, 0, |out| {
+                    out.extend_from_slice(&[0; 8]);
+                    out.extend_from_slice(&track.timescale.to_be_bytes());
+                    out.extend_from_slice(&0u32.to_be_bytes());
+                    out.extend_from_slice(&0x55c4u16.to_be_bytes()); // "und"
+                    out.extend_from_slice(&[0; 2]);
+                });
+
+                write_full_box(out, b"hdlr", 0, 0, |out| {
+                    out.extend_from_slice(&[0; 4]);
+                    out.extend_from_slice(b"soun");
+                    out.extend_from_slice(&[0; 12]);
+                    out.extend_from_slice(b"SoundHandler\0");
+                });
+
+                write_box(out, b"minf", |out| {
+                    write_full_box(out, b"smhd", 0, 0, |out| {
+                        out.extend_from_slice(&[0; 4]);
+                    });
+
+                    write_box(out, b"dinf", |out| {
+                        write_full_box(out, b"dref", 0, 0, |out| {
+                            out.extend_from_slice(&1u32.to_be_bytes());
+                            // Media is in the same file
+                            write_full_box(out, b"url ", 0, 1, |_| {});
+                        });
+                    });
+
+                    write_box(out, b"stbl", |out| {
+                        write_full_box(out, b"stsd", 0, 0, |out| {
+                            out.extend_from_slice(&1u32.to_be_bytes());
+                            write_sample_entry(out, track);
+                        });
+                        // Empty sample tables, the samples are all in fragments
+                        write_full_box(out, b"stts", 0, 0, |out| {
+                            out.extend_from_slice(&[0; 4]);
+                        });
+                        write_full_box(out, b"stsc", 0, 0, |out| {
+                            out.extend_from_slice(&[0; 4]);
+                        });
+                        write_full_box(out, b"stsz", 0, 0, |out| {
+                            out.extend_from_slice(&[0; 8]);
+                        });
+                        write_full_box(out, b"stco", 0, 0, |out| {
+                            out.extend_from_slice(&[0; 4]);
+                        });
+                    });
+                });
+            });
+        });
+
+        write_box(out, b"mvex", |out| {
+            write_full_box(out, b"trex", 0, 0, |out| {
+                out.extend_from_slice(&TRACK_ID.to_be_bytes());
+                out.extend_from_slice(&1u32.to_be_bytes()); // sample description index
+                out.extend_from_slice(&[0; 12]); // default duration, size and flags
+            });
+        });
+    });
+
+    out
+}
+
+fn write_sample_entry(out: &mut Vec<u8>, track: &AudioTrack) {
+    let kind = match track.entry {
+        SampleEntry::Opus(_) => b"Opus",
+        SampleEntry::Mp4a { .. } => b"mp4a",
+        SampleEntry::Flac(_) => b"fLaC",
+    };
+
+    write_box(out, kind, |out| {
+        out.extend_from_slice(&[0; 6]);
+        out.extend_from_slice(&1u16.to_be_bytes()); // data reference index
+        out.extend_from_slice(&[0; 8]);
+        out.extend_from_slice(&track.channels.to_be_bytes());
+        out.extend_from_slice(&16u16.to_be_bytes()); // sample size
+        out.extend_from_slice(&[0; 4]);
+        // 16.16 fixed point, rates that don't fit are left to the codec box
+        let sample_rate = if track.sample_rate <= u16::MAX as u32 {
+            track.sample_rate << 16
+        } else {
+            0
+        };
+        out.extend_from_slice(&sample_rate.to_be_bytes());
+
+        match &track.entry {
+            SampleEntry::Opus(head) => write_box(out, b"dOps", |out| {
+                out.extend_from_slice(head);
+            }),
+            SampleEntry::Mp4a {
+                object_type,
+                decoder_config,
+            } => write_full_box(out, b"esds", 0, 0, |out| {
+                let mut config = vec![*object_type, 0x15]; // audio stream
+                config.extend_from_slice(&[0; 11]); // buffer size and bitrates
+                if let Some(decoder_config) = decoder_config {
+                    write_descriptor(&mut config, 0x05, decoder_config);
+                }
+
+                let mut es = vec![0, 0, 0]; // ES id and flags
+                write_descriptor(&mut es, 0x04, &config);
+                write_descriptor(&mut es, 0x06, &[0x02]);
+
+                write_descriptor(out, 0x03, &es);
+            }),
+            SampleEntry::Flac(stream_info) => write_full_box(out, b"dfLa", 0, 0, |out| {
+                // STREAMINFO as the last (and only) metadata block
+                out.push(0x80);
+                out.extend_from_slice(&(stream_info.len() as u32).to_be_bytes()[1..]);
+                out.extend_from_slice(stream_info);
+            }),
+        }
+    });
+}
+
+// One `moof` + `mdat` pair. `sequence` starts at 1 and `decode_time` is the
+// timestamp of the first sample in timescale ticks.
+pub fn media_segment(sequence: u32, decode_time: u64, samples: &[Sample]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut data_offset_at = 0;
+
+    write_box(&mut out, b"moof", |out| {
+        write_full_box(out, b"mfhd", 0, 0, |out| {
+            out.extend_from_slice(&sequence.to_be_bytes());
+        });
+
+        write_box(out, b"traf", |out| {
+            // Offsets are relative to the start of the moof
+            write_full_box(out, b"tfhd", 0, 0x02_0000, |out| {
+                out.extend_from_slice(&TRACK_ID.to_be_bytes());
+            });
+            write_full_box(out, b"tfdt", 1, 0, |out| {
+                out.extend_from_slice(&decode_time.to_be_bytes());
+            });
+            // Data offset, then a duration and size per sample
+            write_full_box(out, b"trun", 0, 0x0301, |out| {
+                out.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+                data_offset_at = out.len();
+                out.extend_from_slice(&[0; 4]);
+                for sample in samples {
+                    out.extend_from_slice(&sample.duration.to_be_bytes());
+                    out.extend_from_slice(&(sample.data.len() as u32).to_be_bytes());
+                }
+            });
+        });
+    });
+
+    // The samples start right after the mdat header
+    let data_offset = (out.len() + 8) as u32;
+    out[data_offset_at..data_offset_at + 4].copy_from_slice(&data_offset.to_be_bytes());
+
+    write_box(&mut out, b"mdat", |out| {
+        for sample in samples {
+            out.extend_from_slice(&sample.data);
+        }
+    });
+
+    out
+}
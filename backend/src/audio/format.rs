@@ -0,0 +1,211 @@
+use symphonia::core::codecs::{
+    CodecType, CODEC_TYPE_AAC, CODEC_TYPE_ALAC, CODEC_TYPE_FLAC, CODEC_TYPE_MP3, CODEC_TYPE_OPUS,
+    CODEC_TYPE_VORBIS,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Mp3,
+    Flac,
+    Ogg,
+    Wav,
+    Adts,
+    Mp4,
+}
+
+impl Container {
+    // Identifies the container from the first bytes of a file.
+    pub fn sniff(header: &[u8]) -> Option<Container> {
+        // An ID3v2 tag can sit in front of MP3, AAC or even FLAC streams,
+        // look behind it when we have enough bytes to do so.
+        if header.starts_with(b"ID3") && header.len() >= 10 {
+            let tag_size = header[6..10]
+                .iter()
+                .fold(0usize, |size, byte| (size << 7) | (*byte as usize & 0x7f));
+
+            return match header.get(10 + tag_size..) {
+                Some(rest) if !rest.is_empty() => Container::sniff(rest).or(Some(Container::Mp3)),
+                _ => Some(Container::Mp3),
+            };
+        }
+
+        if header.starts_with(b"fLaC") {
+            return Some(Container::Flac);
+        }
+
+        if header.starts_with(b"OggS") {
+            return Some(Container::Ogg);
+        }
+
+        if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WAVE" {
+            return Some(Container::Wav);
+        }
+
+        if header.len() >= 8 && &header[4..8] == b"ftyp" {
+            return Some(Container::Mp4);
+        }
+
+        if header.len() >= 2 && header[0] == 0xff && header[1] & 0xe0 == 0xe0 {
+            // Layer bits of `00` mean an ADTS (AAC) header, anything else is MPEG audio
+            return if header[1] & 0xf6 == 0xf0 {
+                Some(Container::Adts)
+            } else {
+                Some(Container::Mp3)
+            };
+        }
+
+        None
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Container::Mp3 => "mp3",
+            Container::Flac => "flac",
+            Container::Ogg => "ogg",
+            Container::Wav => "wav",
+            Container::Adts => "aac",
+            Container::Mp4 => "mp4",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "m4a",
+            container => container.as_str(),
+        }
+    }
+}
+
+pub fn codec_name(codec: CodecType) -> &'static str {
+    match codec {
+        CODEC_TYPE_MP3 => "mp3",
+        CODEC_TYPE_AAC => "aac",
+        CODEC_TYPE_FLAC => "flac",
+        CODEC_TYPE_VORBIS => "vorbis",
+        CODEC_TYPE_OPUS => "opus",
+        CODEC_TYPE_ALAC => "alac",
+        codec => match symphonia::default::get_codecs().get_codec(codec) {
+            Some(descriptor) if descriptor.short_name.starts_with("pcm") => "pcm",
+            Some(descriptor) => descriptor.short_name,
+            None => "unknown",
+        },
+    }
+}
+
+// MIME type browsers expect for a given container / codec pair.
+pub fn mime_type(container: Container, codec: Option<&str>) -> String {
+    match (container, codec) {
+        (Container::Mp3, _) => "audio/mpeg".to_string(),
+        (Container::Flac, _) => "audio/flac".to_string(),
+        (Container::Wav, _) => "audio/wav".to_string(),
+        (Container::Adts, _) => "audio/aac".to_string(),
+        (Container::Mp4, _) => "audio/mp4".to_string(),
+        (Container::Ogg, Some(codec @ ("vorbis" | "opus" | "flac"))) => {
+            format!("audio/ogg; codecs={}", codec)
+        }
+        (Container::Ogg, _) => "audio/ogg".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ID3v2 header whose syncsafe size covers `size` bytes of frames
+    fn id3_header(size: usize) -> Vec<u8> {
+        let mut header = b"ID3\x04\x00\x00".to_vec();
+        header.extend(
+            (0..4)
+                .rev()
+                .map(|shift| ((size >> (7 * shift)) & 0x7f) as u8),
+        );
+        header
+    }
+
+    #[test]
+    fn sniffs_magic_numbers() {
+        assert_eq!(
+            Container::sniff(b"fLaC\x00\x00\x00\x22"),
+            Some(Container::Flac)
+        );
+        assert_eq!(Container::sniff(b"OggS\x00\x02"), Some(Container::Ogg));
+        assert_eq!(
+            Container::sniff(b"RIFF\x24\x08\x00\x00WAVEfmt "),
+            Some(Container::Wav)
+        );
+        assert_eq!(
+            Container::sniff(b"\x00\x00\x00\x20ftypM4A "),
+            Some(Container::Mp4)
+        );
+        assert_eq!(Container::sniff(b"RIFF\x24\x08\x00\x00AVI "), None);
+        assert_eq!(Container::sniff(b"not audio"), None);
+        assert_eq!(Container::sniff(b""), None);
+    }
+
+    #[test]
+    fn tells_adts_from_mpeg_audio() {
+        // MPEG-1 layer III and MPEG-2 layer III frame syncs
+        assert_eq!(
+            Container::sniff(&[0xff, 0xfb, 0x90, 0x64]),
+            Some(Container::Mp3)
+        );
+        assert_eq!(
+            Container::sniff(&[0xff, 0xf3, 0x90, 0x64]),
+            Some(Container::Mp3)
+        );
+        // ADTS with and without CRC, MPEG-4 and MPEG-2
+        assert_eq!(
+            Container::sniff(&[0xff, 0xf1, 0x50, 0x80]),
+            Some(Container::Adts)
+        );
+        assert_eq!(
+            Container::sniff(&[0xff, 0xf0, 0x50, 0x80]),
+            Some(Container::Adts)
+        );
+        assert_eq!(
+            Container::sniff(&[0xff, 0xf9, 0x50, 0x80]),
+            Some(Container::Adts)
+        );
+    }
+
+    #[test]
+    fn looks_behind_id3_tags() {
+        let mut flac = id3_header(200);
+        flac.extend([0; 200]);
+        flac.extend(b"fLaC\x00\x00\x00\x22");
+        assert_eq!(Container::sniff(&flac), Some(Container::Flac));
+
+        let mut adts = id3_header(3);
+        adts.extend([0; 3]);
+        adts.extend([0xff, 0xf1, 0x50, 0x80]);
+        assert_eq!(Container::sniff(&adts), Some(Container::Adts));
+
+        let mut mp3 = id3_header(0);
+        mp3.extend([0xff, 0xfb, 0x90, 0x64]);
+        assert_eq!(Container::sniff(&mp3), Some(Container::Mp3));
+    }
+
+    #[test]
+    fn id3_tags_default_to_mp3() {
+        // The tag runs past the sniffed bytes
+        let mut long_tag = id3_header(100_000);
+        long_tag.extend([0; 64]);
+        assert_eq!(Container::sniff(&long_tag), Some(Container::Mp3));
+
+        // Nothing recognisable behind the tag
+        let mut unknown = id3_header(4);
+        unknown.extend(b"\x00\x00\x00\x00junk");
+        assert_eq!(Container::sniff(&unknown), Some(Container::Mp3));
+    }
+
+    #[test]
+    fn mime_types_follow_the_ogg_codec() {
+        assert_eq!(
+            mime_type(Container::Ogg, Some("opus")),
+            "audio/ogg; codecs=opus"
+        );
+        assert_eq!(mime_type(Container::Ogg, Some("speex")), "audio/ogg");
+        assert_eq!(mime_type(Container::Adts, Some("aac")), "audio/aac");
+        assert_eq!(Container::Mp4.extension(), "m4a");
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::ErrorKind,
+    path::Path,
+};
+
+use symphonia::core::{
+    codecs::{CodecParameters, CODEC_TYPE_AAC, CODEC_TYPE_FLAC, CODEC_TYPE_MP3, CODEC_TYPE_OPUS},
+    errors::Error,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+use super::{
+    fmp4::{init_segment, media_segment, AudioTrack, Sample, SampleEntry},
+    probe::sniff_container,
+};
+
+// Packaging runs on blocking threads in the job workers, like decoding
+pub type PackageError = Box<dyn std::error::Error + Send + Sync>;
+
+// Codecs that can go into fMP4 segments as they are, without re-encoding
+pub const PACKAGEABLE_CODECS: [&str; 4] = ["mp3", "aac", "flac", "opus"];
+
+pub const MASTER_PLAYLIST: &str = "master.m3u8";
+pub const MEDIA_PLAYLIST: &str = "index.m3u8";
+pub const INIT_SEGMENT: &str = "init.mp4";
+
+// Segments are cut on the first packet boundary past this many seconds
+const SEGMENT_DURATION: f64 = 6.0;
+
+const AAC_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+pub fn segment_name(index: usize) -> String {
+    format!("seg_{}.m4s", index)
+}
+
+#[derive(Debug, Clone)]
+pub struct HlsVariant {
+    // RFC 6381 codec string for the master playlist
+    pub codecs: String,
+    // Peak and average segment bitrate, in bits/s
+    pub bandwidth: u64,
+    pub average_bandwidth: u64,
+}
+
+// Remuxes an audio file into fMP4 segments plus a media playlist in
+// `output_dir`. The packets are copied as they are, nothing is re-encoded.
+pub fn package_variant(input: &Path, output_dir: &Path) -> Result<HlsVariant, PackageError> {
+    let mut hint = Hint::new();
+    if let Some(container) = sniff_container(input)? {
+        hint.with_extension(container.extension());
+    }
+
+    let file = File::open(input)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    // Gapless trimming is left to the players, every packet is kept
+    let mut probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    let track = probed.format.default_track().ok_or("No track found")?;
+    let track_id = track.id;
+    let (audio_track, codecs) = audio_track(&track.codec_params)?;
+    let timescale = audio_track.timescale as f64;
+
+    fs::create_dir_all(output_dir)?;
+    fs::write(output_dir.join(INIT_SEGMENT), init_segment(&audio_track))?;
+
+    // (duration in seconds, size in bytes) of every segment written so far
+    let mut segments: Vec<(f64, u64)> = Vec::new();
+    let mut samples: Vec<Sample> = Vec::new();
+    let mut segment_ticks: u64 = 0;
+    let mut decode_time: u64 = 0;
+
+    let mut write_segment = |samples: &mut Vec<Sample>, ticks: u64| -> Result<(), PackageError> {
+        let index = segments.len();
+        let segment = media_segment(index as u32 + 1, decode_time, samples);
+        fs::write(output_dir.join(segment_name(index)), &segment)?;
+
+        segments.push((ticks as f64 / timescale, segment.len() as u64));
+        decode_time += ticks;
+        samples.clear();
+
+        Ok(())
+    };
+
+    loop {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let duration = packet.dur() as u32;
+        samples.push(Sample {
+            duration,
+            data: packet.data.into_vec(),
+        });
+        segment_ticks += duration as u64;
+
+        if segment_ticks as f64 >= SEGMENT_DURATION * timescale {
+            write_segment(&mut samples, segment_ticks)?;
+            segment_ticks = 0;
+        }
+    }
+
+    if !samples.is_empty() {
+        write_segment(&mut samples, segment_ticks)?;
+    }
+    if segments.is_empty() {
+        return Err("No audio packets found".into());
+    }
+
+    fs::write(output_dir.join(MEDIA_PLAYLIST), media_playlist(&segments))?;
+
+    let bandwidth = segments
+        .iter()
+        .filter(|(duration, _)| *duration > 0.0)
+        .map(|(duration, size)| (*size as f64 * 8.0 / duration).ceil() as u64)
+        .max()
+        .unwrap_or_default();
+    let total_duration: f64 = segments.iter().map(|(duration, _)| duration).sum();
+    let total_size: u64 = segments.iter().map(|(_, size)| size).sum();
+    let average_bandwidth = if total_duration > 0.0 {
+        (total_size as f64 * 8.0 / total_duration).ceil() as u64
+    } else {
+        0
+    };
+
+    Ok(HlsVariant {
+        codecs,
+        bandwidth,
+        average_bandwidth,
+    })
+}
+
+// Sample entry and codec string for the stream, or an error for codecs that
+// have no fMP4 mapping here
+fn audio_track(params: &CodecParameters) -> Result<(AudioTrack, String), PackageError> {
+    let sample_rate = params.sample_rate.ok_or("Unknown sample rate")?;
+    let channels = params
+        .channels
+        .map(|channels| channels.count() as u16)
+        .ok_or("Unknown channel layout")?;
+
+    // Packet durations count in the time base, raw streams count in samples
+    let timescale = match params.time_base {
+        Some(time_base) if time_base.numer == 1 => time_base.denom,
+        _ => sample_rate,
+    };
+
+    let (entry, codecs) = match params.codec {
+        CODEC_TYPE_OPUS => {
+            let head = params.extra_data.as_deref().ok_or("Missing Opus header")?;
+            (SampleEntry::Opus(opus_specific_box(head)?), "opus".to_string())
+        }
+        CODEC_TYPE_AAC => {
+            // ADTS streams have no AudioSpecificConfig, one is made up from the frame header
+            let config = match params.extra_data.as_deref() {
+                Some(config) if !config.is_empty() => config.to_vec(),
+                _ => adts_audio_config(sample_rate, channels)?,
+            };
+            let codecs = format!("mp4a.40.{}", config[0] >> 3);
+
+            (
+                SampleEntry::Mp4a {
+                    object_type: 0x40,
+                    decoder_config: Some(config),
+                },
+                codecs,
+            )
+        }
+        CODEC_TYPE_MP3 => (
+            SampleEntry::Mp4a {
+                // MPEG-1 and MPEG-2 (the low sample rates) have object types of their own
+                object_type: if sample_rate >= 32000 { 0x6b } else { 0x69 },
+                decoder_config: None,
+            },
+            "mp4a.40.34".to_string(),
+        ),
+        CODEC_TYPE_FLAC => {
+            let stream_info = params.extra_data.as_deref().ok_or("Missing FLAC stream info")?;
+            (SampleEntry::Flac(stream_info.to_vec()), "fLaC".to_string())
+        }
+        _ => return Err("Codec can't be packaged for HLS".into()),
+    };
+
+    Ok((
+        AudioTrack {
+            timescale,
+            sample_rate,
+            channels,
+            entry,
+        },
+        codecs,
+    ))
+}
+
+// `dOps` holds the OpusHead fields big-endian, without the magic and version
+fn opus_specific_box(head: &[u8]) -> Result<Vec<u8>, PackageError> {
+    if head.len() < 19 || !head.starts_with(b"OpusHead") {
+        return Err("Invalid Opus header".into());
+    }
+
+    let mut dops = vec![0, head[9]];
+    dops.extend_from_slice(&u16::from_le_bytes([head[10], head[11]]).to_be_bytes());
+    dops.extend_from_slice(&u32::from_le_bytes([head[12], head[13], head[14], head[15]]).to_be_bytes());
+    dops.extend_from_slice(&i16::from_le_bytes([head[16], head[17]]).to_be_bytes());
+    // Mapping family, then its channel mapping table as is
+    dops.extend_from_slice(&head[18..]);
+
+    Ok(dops)
+}
+
+// AAC-LC AudioSpecificConfig, the profile of ADTS streams isn't kept by the demuxer
+fn adts_audio_config(sample_rate: u32, channels: u16) -> Result<Vec<u8>, PackageError> {
+    let rate_index = AAC_SAMPLE_RATES
+        .iter()
+        .position(|rate| *rate == sample_rate)
+        .ok_or("Unsupported AAC sample rate")?;
+
+    let config = (2u16 << 11) | ((rate_index as u16) << 7) | (channels << 3);
+    Ok(config.to_be_bytes().to_vec())
+}
+
+fn media_playlist(segments: &[(f64, u64)]) -> String {
+    // Every segment, rounded, has to fit in the target duration
+    let target_duration = segments
+        .iter()
+        .map(|(duration, _)| duration.round() as u64)
+        .max()
+        .unwrap_or_default()
+        .max(1);
+
+    let mut playlist = String::new();
+    let _ = writeln!(playlist, "#EXTM3U");
+    let _ = writeln!(playlist, "#EXT-X-VERSION:7");
+    let _ = writeln!(playlist, "#EXT-X-TARGETDURATION:{}", target_duration);
+    let _ = writeln!(playlist, "#EXT-X-MEDIA-SEQUENCE:0");
+    let _ = writeln!(playlist, "#EXT-X-PLAYLIST-TYPE:VOD");
+    let _ = writeln!(playlist, "#EXT-X-INDEPENDENT-SEGMENTS");
+    let _ = writeln!(playlist, "#EXT-X-MAP:URI=\"{}\"", INIT_SEGMENT);
+    for (index, (duration, _)) in segments.iter().enumerate() {
+        let _ = writeln!(playlist, "#EXTINF:{:.3},", duration);
+        let _ = writeln!(playlist, "{}", segment_name(index));
+    }
+    let _ = writeln!(playlist, "#EXT-X-ENDLIST");
+
+    playlist
+}
+
+// Master playlist listing each variant's media playlist at `{name}/index.m3u8`
+pub fn master_playlist(variants: &[(String, HlsVariant)]) -> String {
+    let mut playlist = String::new();
+    let _ = writeln!(playlist, "#EXTM3U");
+    let _ = writeln!(playlist, "#EXT-X-VERSION:7");
+    let _ = writeln!(playlist, "#EXT-X-INDEPENDENT-SEGMENTS");
+    for (name, variant) in variants {
+        let _ = writeln!(
+            playlist,
+            "#EXT-X-STREAM-INF:BANDWIDTH={},AVERAGE-BANDWIDTH={},CODECS=\"{}\"",
+            variant.bandwidth, variant.average_bandwidth, variant.codecs
+        );
+        let _ = writeln!(playlist, "{}/{}", name, MEDIA_PLAYLIST);
+    }
+
+    playlist
+}
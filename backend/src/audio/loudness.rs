@@ -0,0 +1,383 @@
+use std::{f64::consts::PI, ops::ControlFlow, path::Path};
+
+use super::decode::{decode_audio, DecodeError, PcmFormat};
+
+// ReplayGain 2.0 plays everything back at -18 LUFS
+pub const REFERENCE_LOUDNESS: f64 = -18.0;
+
+const ABSOLUTE_GATE: f64 = -70.0;
+// Below the ungated loudness, for the integrated loudness and the range
+const RELATIVE_GATE: f64 = -10.0;
+const RANGE_RELATIVE_GATE: f64 = -20.0;
+
+// Blocks are built from 100 ms steps: 400 ms momentary, 3 s short-term
+const MOMENTARY_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+
+// Interpolation filter taps per phase of the true peak oversampler
+const TAPS_PER_PHASE: usize = 12;
+
+// EBU R128 measurements of a whole track. Silence has no loudness, so every
+// value is missing for a track that never gets above the absolute gate.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Loudness {
+    // Integrated loudness, LUFS
+    pub integrated: Option<f64>,
+    // Loudness range, LU
+    pub range: Option<f64>,
+    // dBTP
+    pub true_peak: Option<f64>,
+}
+
+// Gain in dB bringing a track to the reference loudness
+pub fn replay_gain(loudness: f64) -> f64 {
+    REFERENCE_LOUDNESS - loudness
+}
+
+pub fn measure_loudness(input: &Path) -> Result<Loudness, DecodeError> {
+    let mut meter: Option<LoudnessMeter> = None;
+
+    decode_audio(input, |format, samples| {
+        let meter = match &mut meter {
+            Some(meter) if meter.format == format => meter,
+            Some(_) => return Err("Sample rate or channels change mid-stream".into()),
+            None => meter.insert(LoudnessMeter::new(format)),
+        };
+
+        meter.push(samples);
+        Ok(ControlFlow::Continue(()))
+    })?;
+
+    Ok(meter.ok_or("No audio decoded")?.finish())
+}
+
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad { b, a, state: [0.0; 2] }
+    }
+
+    // Transposed direct form II
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+// BS.1770 K-weighting (high shelf, then high pass) for any sample rate, with
+// the analog prototypes of libebur128 so 48 kHz matches the published coefficients
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+// Surround channels count a bit more and the LFE not at all, in the usual
+// WAV / FLAC channel order
+fn channel_weight(channel: usize, channels: usize) -> f64 {
+    match (channels, channel) {
+        (5, 3 | 4) | (6, 4 | 5) => 1.41,
+        (6, 3) => 0.0,
+        _ => 1.0,
+    }
+}
+
+// Polyphase FIR that upsamples a channel to find the peaks between samples
+struct TruePeak {
+    phases: Vec<[f64; TAPS_PER_PHASE]>,
+    history: Vec<[f64; TAPS_PER_PHASE]>,
+    position: usize,
+    peak: f64,
+}
+
+impl TruePeak {
+    fn new(sample_rate: u32, channels: usize) -> Self {
+        // BS.1770 asks for 4x at 48 kHz, high rates need less
+        let factor = match sample_rate {
+            rate if rate < 96_000 => 4,
+            rate if rate < 192_000 => 2,
+            _ => 1,
+        };
+
+        // Windowed sinc low pass at the original Nyquist frequency
+        let taps = factor * TAPS_PER_PHASE;
+        let center = (taps - 1) as f64 / 2.0;
+        let mut phases = vec![[0.0; TAPS_PER_PHASE]; factor];
+        for n in 0..taps {
+            let t = (n as f64 - center) / factor as f64;
+            let sinc = if t == 0.0 { 1.0 } else { (PI * t).sin() / (PI * t) };
+            let x = 2.0 * PI * n as f64 / (taps - 1) as f64;
+            let blackman = 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos();
+            phases[n % factor][n / factor] = sinc * blackman;
+        }
+
+        TruePeak {
+            phases,
+            history: vec![[0.0; TAPS_PER_PHASE]; channels],
+            position: 0,
+            peak: 0.0,
+        }
+    }
+
+    fn push(&mut self, frame: &[f32]) {
+        self.position = (self.position + 1) % TAPS_PER_PHASE;
+
+        for (history, sample) in self.history.iter_mut().zip(frame) {
+            history[self.position] = *sample as f64;
+            self.peak = self.peak.max(sample.abs() as f64);
+
+            for phase in &self.phases {
+                let mut value = 0.0;
+                for (tap, coefficient) in phase.iter().enumerate() {
+                    value += coefficient
+                        * history[(self.position + TAPS_PER_PHASE - tap) % TAPS_PER_PHASE];
+                }
+                self.peak = self.peak.max(value.abs());
+            }
+        }
+    }
+}
+
+struct LoudnessMeter {
+    format: PcmFormat,
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    true_peak: TruePeak,
+    step_frames: usize,
+    // Weighted energy of the 100 ms step being filled and its frame count
+    step_energy: f64,
+    step_filled: usize,
+    // Energy of the latest steps, enough for a short-term block
+    steps: Vec<f64>,
+    // Mean square of every momentary and short-term block
+    momentary: Vec<f64>,
+    short_term: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    fn new(format: PcmFormat) -> Self {
+        LoudnessMeter {
+            format,
+            filters: vec![k_weighting(format.sample_rate); format.channels],
+            weights: (0..format.channels)
+                .map(|channel| channel_weight(channel, format.channels))
+                .collect(),
+            true_peak: TruePeak::new(format.sample_rate, format.channels),
+            step_frames: (format.sample_rate as usize / 10).max(1),
+            step_energy: 0.0,
+            step_filled: 0,
+            steps: Vec::with_capacity(SHORT_TERM_STEPS),
+            momentary: Vec::new(),
+            short_term: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.format.channels) {
+            self.true_peak.push(frame);
+
+            for ((sample, filters), weight) in
+                frame.iter().zip(&mut self.filters).zip(&self.weights)
+            {
+                let filtered = filters
+                    .iter_mut()
+                    .fold(*sample as f64, |value, filter| filter.process(value));
+                self.step_energy += weight * filtered * filtered;
+            }
+
+            self.step_filled += 1;
+            if self.step_filled == self.step_frames {
+                self.end_step();
+            }
+        }
+    }
+
+    // A full 100 ms step closes a momentary and a short-term block, once
+    // enough audio has gone by for them
+    fn end_step(&mut self) {
+        if self.steps.len() == SHORT_TERM_STEPS {
+            self.steps.remove(0);
+        }
+        self.steps.push(self.step_energy);
+        self.step_energy = 0.0;
+        self.step_filled = 0;
+
+        let block = |steps: &[f64]| steps.iter().sum::<f64>() / (steps.len() * self.step_frames) as f64;
+
+        if self.steps.len() >= MOMENTARY_STEPS {
+            let momentary = block(&self.steps[self.steps.len() - MOMENTARY_STEPS..]);
+            self.momentary.push(momentary);
+        }
+        if self.steps.len() == SHORT_TERM_STEPS {
+            let short_term = block(&self.steps);
+            self.short_term.push(short_term);
+        }
+    }
+
+    fn finish(self) -> Loudness {
+        let true_peak = (self.true_peak.peak > 0.0).then(|| 20.0 * self.true_peak.peak.log10());
+
+        Loudness {
+            integrated: integrated_loudness(&self.momentary),
+            range: loudness_range(&self.short_term),
+            true_peak,
+        }
+    }
+}
+
+fn to_lufs(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+// Mean square of the blocks louder than `gate`
+fn gated_mean(blocks: &[f64], gate: f64) -> Option<f64> {
+    let (sum, count) = blocks
+        .iter()
+        .filter(|block| to_lufs(**block) > gate)
+        .fold((0.0, 0), |(sum, count), block| (sum + block, count + 1));
+
+    (count > 0).then(|| sum / count as f64)
+}
+
+fn integrated_loudness(momentary: &[f64]) -> Option<f64> {
+    let ungated = gated_mean(momentary, ABSOLUTE_GATE)?;
+    let relative_gate = to_lufs(ungated) + RELATIVE_GATE;
+
+    gated_mean(momentary, relative_gate.max(ABSOLUTE_GATE)).map(to_lufs)
+}
+
+// EBU Tech 3342: spread between the 10th and 95th percentile of the gated
+// short-term loudness
+fn loudness_range(short_term: &[f64]) -> Option<f64> {
+    let ungated = gated_mean(short_term, ABSOLUTE_GATE)?;
+    let relative_gate = (to_lufs(ungated) + RANGE_RELATIVE_GATE).max(ABSOLUTE_GATE);
+
+    let mut loudness: Vec<f64> = short_term
+        .iter()
+        .map(|block| to_lufs(*block))
+        .filter(|loudness| *loudness > relative_gate)
+        .collect();
+    if loudness.is_empty() {
+        return None;
+    }
+    loudness.sort_by(f64::total_cmp);
+
+    let percentile = |p: f64| loudness[((loudness.len() - 1) as f64 * p).round() as usize];
+    Some(percentile(0.95) - percentile(0.10))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    // Stereo 1 kHz sine, `amplitude` in dBFS, on both channels
+    fn sine(meter: &mut LoudnessMeter, amplitude: f64, seconds: f64) {
+        let gain = 10f64.powf(amplitude / 20.0);
+        let frames = (SAMPLE_RATE as f64 * seconds) as usize;
+        let samples: Vec<f32> = (0..frames)
+            .flat_map(|n| {
+                let t = n as f64 / SAMPLE_RATE as f64;
+                let sample = (gain * (2.0 * PI * 1000.0 * t).sin()) as f32;
+                [sample, sample]
+            })
+            .collect();
+        meter.push(&samples);
+    }
+
+    fn stereo_meter() -> LoudnessMeter {
+        LoudnessMeter::new(PcmFormat {
+            sample_rate: SAMPLE_RATE,
+            channels: 2,
+        })
+    }
+
+    #[test]
+    fn measures_a_reference_tone() {
+        // EBU Tech 3341: a -23 dBFS stereo sine reads -23 LUFS
+        let mut meter = stereo_meter();
+        sine(&mut meter, -23.0, 5.0);
+        let loudness = meter.finish();
+
+        let integrated = loudness.integrated.unwrap();
+        assert!((integrated + 23.0).abs() < 0.1, "{}", integrated);
+        assert!(loudness.range.unwrap() < 0.1);
+        let true_peak = loudness.true_peak.unwrap();
+        assert!((true_peak + 23.0).abs() < 0.2, "{}", true_peak);
+    }
+
+    #[test]
+    fn quiet_passages_are_gated() {
+        // The quiet half is below the relative gate, it doesn't pull the
+        // integrated loudness down
+        let mut meter = stereo_meter();
+        sine(&mut meter, -23.0, 10.0);
+        sine(&mut meter, -60.0, 10.0);
+        let integrated = meter.finish().integrated.unwrap();
+
+        assert!((integrated + 23.0).abs() < 0.1, "{}", integrated);
+    }
+
+    #[test]
+    fn measures_the_loudness_range() {
+        // Like EBU Tech 3342's first case, -30 then -20 LUFS have a range of 10 LU
+        let mut meter = stereo_meter();
+        sine(&mut meter, -30.0, 10.0);
+        sine(&mut meter, -20.0, 10.0);
+        let range = meter.finish().range.unwrap();
+
+        assert!((range - 10.0).abs() < 1.0, "{}", range);
+    }
+
+    #[test]
+    fn silence_has_no_loudness() {
+        let mut meter = stereo_meter();
+        meter.push(&vec![0.0; SAMPLE_RATE as usize * 2 * 5]);
+        let loudness = meter.finish();
+
+        assert_eq!(loudness.integrated, None);
+        assert_eq!(loudness.range, None);
+        assert_eq!(loudness.true_peak, None);
+
+        let mut meter = stereo_meter();
+        sine(&mut meter, -80.0, 5.0);
+        assert_eq!(meter.finish().integrated, None);
+    }
+
+    #[test]
+    fn replay_gain_targets_the_reference() {
+        assert_eq!(replay_gain(-23.0), 5.0);
+        assert_eq!(replay_gain(-8.0), -10.0);
+    }
+}
@@ -0,0 +1,16 @@
+pub mod decode;
+pub mod fingerprint;
+pub mod fmp4;
+pub mod format;
+pub mod hls;
+pub mod loudness;
+pub mod probe;
+pub mod tags;
+pub mod waveform;
+
+// The only encoder needs libopus
+#[cfg(feature = "opus")]
+pub mod opus;
+// Previews are encoded, so finding where they start needs libopus too
+#[cfg(feature = "opus")]
+pub mod preview;
@@ -0,0 +1,340 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    ops::ControlFlow,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use audiopus::{coder::Encoder, Application, Bitrate, Channels, SampleRate};
+use ogg::{PacketWriteEndInfo, PacketWriter};
+use rubato::{FastFixedIn, PolynomialDegree, Resampler};
+
+use super::decode::{decode_audio, DecodeError, PcmFormat};
+
+// Opus always runs at 48 kHz, 20 ms frames are what every player expects
+const OPUS_RATE: u32 = 48_000;
+const FRAME_SIZE: usize = 960;
+const RESAMPLER_CHUNK: usize = 1024;
+// Largest packet libopus recommends allocating for
+const MAX_PACKET: usize = 4000;
+// Fades at both ends of a clip so the cuts don't click
+const CLIP_FADE_SECONDS: f64 = 0.5;
+
+// Part of a track, in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clip {
+    pub start: f64,
+    pub duration: f64,
+}
+
+// Encodes an audio file to Ogg Opus (RFC 7845) at the given bitrate in bits/s,
+// with `gain` dB applied to the samples, only `clip` of it when given.
+// Surround sources keep their front left and right channels.
+pub fn encode_opus(
+    input: &Path,
+    output: &Path,
+    bitrate: i32,
+    gain: f64,
+    clip: Option<Clip>,
+) -> Result<(), DecodeError> {
+    let mut writer: Option<OpusWriter> = None;
+    let scale = 10f64.powf(gain / 20.0) as f32;
+    let mut scaled = Vec::new();
+
+    let mut sink = |format: PcmFormat, samples: &[f32]| {
+        let writer = match &mut writer {
+            Some(writer) if writer.input_format == format => writer,
+            Some(_) => return Err("Sample rate or channels change mid-stream".into()),
+            None => writer.insert(OpusWriter::new(output, format, bitrate)?),
+        };
+
+        if gain == 0.0 {
+            return writer.push(samples);
+        }
+        scaled.clear();
+        scaled.extend(samples.iter().map(|sample| sample * scale));
+        writer.push(&scaled)
+    };
+    match clip {
+        Some(clip) => decode_clip(input, clip, sink)?,
+        None => decode_audio(input, |format, samples| {
+            sink(format, samples).map(|()| ControlFlow::Continue(()))
+        })?,
+    }
+
+    writer.ok_or("No audio decoded")?.finish()
+}
+
+// Like `decode_audio`, but only the samples of `clip`, faded in and out
+fn decode_clip<F>(file_path: &Path, clip: Clip, mut sink: F) -> Result<(), DecodeError>
+where
+    F: FnMut(PcmFormat, &[f32]) -> Result<(), DecodeError>,
+{
+    let mut position: u64 = 0;
+    let mut faded = Vec::new();
+
+    decode_audio(file_path, |format, samples| {
+        let rate = format.sample_rate as f64;
+        let start = (clip.start * rate) as u64;
+        let end = ((clip.start + clip.duration) * rate) as u64;
+        let fade = (CLIP_FADE_SECONDS * rate).min((end - start) as f64 / 2.0).max(1.0);
+
+        let packet_start = position;
+        position += (samples.len() / format.channels) as u64;
+        let first = packet_start.clamp(start, end);
+        let last = position.clamp(start, end);
+        if packet_start >= end {
+            return Ok(ControlFlow::Break(()));
+        }
+        if first == last {
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        faded.clear();
+        for frame in first..last {
+            let offset = (frame - packet_start) as usize * format.channels;
+            let gain = ((frame - start) as f64 / fade)
+                .min((end - frame) as f64 / fade)
+                .min(1.0) as f32;
+            faded.extend(
+                samples[offset..offset + format.channels]
+                    .iter()
+                    .map(|sample| sample * gain),
+            );
+        }
+
+        sink(format, &faded)?;
+        Ok(ControlFlow::Continue(()))
+    })
+}
+
+struct OpusWriter {
+    input_format: PcmFormat,
+    channels: usize,
+    encoder: Encoder,
+    packets: PacketWriter<BufWriter<File>>,
+    serial: u32,
+    resampler: Option<FastFixedIn<f32>>,
+    // Planar samples waiting for a full resampler chunk
+    resampler_input: Vec<Vec<f32>>,
+    // Resampler output still to be dropped, it starts with `output_delay` frames of silence
+    delay: usize,
+    // Interleaved 48 kHz samples waiting for a full frame
+    pcm: Vec<f32>,
+    input_frames: u64,
+    output_frames: u64,
+    pre_skip: u64,
+    granule: u64,
+    // Held back so the last packet can be flagged as the end of the stream
+    pending: Option<(Vec<u8>, u64)>,
+    packet: Vec<u8>,
+}
+
+impl OpusWriter {
+    fn new(output: &Path, input_format: PcmFormat, bitrate: i32) -> Result<Self, DecodeError> {
+        let channels = input_format.channels.min(2);
+        let mut encoder = Encoder::new(
+            SampleRate::Hz48000,
+            if channels == 1 {
+                Channels::Mono
+            } else {
+                Channels::Stereo
+            },
+            Application::Audio,
+        )?;
+        encoder.set_bitrate(Bitrate::BitsPerSecond(bitrate))?;
+
+        let resampler = if input_format.sample_rate == OPUS_RATE {
+            None
+        } else {
+            Some(FastFixedIn::<f32>::new(
+                OPUS_RATE as f64 / input_format.sample_rate as f64,
+                1.0,
+                PolynomialDegree::Septic,
+                RESAMPLER_CHUNK,
+                channels,
+            )?)
+        };
+        let delay = resampler
+            .as_ref()
+            .map_or(0, |resampler| resampler.output_delay());
+
+        let serial = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |now| now.subsec_nanos());
+
+        let mut writer = OpusWriter {
+            input_format,
+            channels,
+            pre_skip: encoder.lookahead()? as u64,
+            encoder,
+            packets: PacketWriter::new(BufWriter::new(File::create(output)?)),
+            serial,
+            resampler,
+            resampler_input: vec![Vec::new(); channels],
+            delay,
+            pcm: Vec::new(),
+            input_frames: 0,
+            output_frames: 0,
+            granule: 0,
+            pending: None,
+            packet: vec![0; MAX_PACKET],
+        };
+        writer.write_headers()?;
+
+        Ok(writer)
+    }
+
+    fn write_headers(&mut self) -> Result<(), DecodeError> {
+        let mut head = Vec::with_capacity(19);
+        head.extend_from_slice(b"OpusHead");
+        head.push(1);
+        head.push(self.channels as u8);
+        head.extend_from_slice(&(self.pre_skip as u16).to_le_bytes());
+        head.extend_from_slice(&self.input_format.sample_rate.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes());
+        head.push(0);
+
+        let vendor = concat!("music-platform ", env!("CARGO_PKG_VERSION"));
+        let mut tags = Vec::new();
+        tags.extend_from_slice(b"OpusTags");
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor.as_bytes());
+        tags.extend_from_slice(&0u32.to_le_bytes());
+
+        // Both headers sit on pages of their own
+        self.packets.write_packet(
+            head.into_boxed_slice(),
+            self.serial,
+            PacketWriteEndInfo::EndPage,
+            0,
+        )?;
+        self.packets.write_packet(
+            tags.into_boxed_slice(),
+            self.serial,
+            PacketWriteEndInfo::EndPage,
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    fn push(&mut self, samples: &[f32]) -> Result<(), DecodeError> {
+        let input_channels = self.input_format.channels;
+        self.input_frames += (samples.len() / input_channels) as u64;
+
+        match &mut self.resampler {
+            None => {
+                for frame in samples.chunks_exact(input_channels) {
+                    self.pcm.extend_from_slice(&frame[..self.channels]);
+                }
+                self.output_frames = self.input_frames;
+            }
+            Some(_) => {
+                for frame in samples.chunks_exact(input_channels) {
+                    for (channel, input) in self.resampler_input.iter_mut().enumerate() {
+                        input.push(frame[channel]);
+                    }
+                }
+                self.resample(false)?;
+            }
+        }
+
+        self.encode_frames(false)
+    }
+
+    // Runs every full chunk through the resampler. At the end of the stream the
+    // rest is padded with silence until the delayed tail has come out too.
+    fn resample(&mut self, flush: bool) -> Result<(), DecodeError> {
+        let Some(resampler) = &mut self.resampler else {
+            return Ok(());
+        };
+        let expected = self.input_frames * OPUS_RATE as u64 / self.input_format.sample_rate as u64;
+
+        loop {
+            let needed = resampler.input_frames_next();
+            if self.resampler_input[0].len() < needed {
+                if !flush || self.output_frames >= expected {
+                    break;
+                }
+                for input in &mut self.resampler_input {
+                    input.resize(needed, 0.0);
+                }
+            }
+
+            let chunk: Vec<Vec<f32>> = self
+                .resampler_input
+                .iter_mut()
+                .map(|input| input.drain(..needed).collect())
+                .collect();
+            let resampled = resampler.process(&chunk, None)?;
+
+            let frames = resampled[0].len();
+            let skip = self.delay.min(frames);
+            self.delay -= skip;
+
+            for frame in skip..frames {
+                if flush && self.output_frames >= expected {
+                    break;
+                }
+                for channel in &resampled {
+                    self.pcm.push(channel[frame]);
+                }
+                self.output_frames += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_frames(&mut self, flush: bool) -> Result<(), DecodeError> {
+        let frame_len = FRAME_SIZE * self.channels;
+
+        // The encoder lags `pre_skip` samples behind, so the stream is padded with
+        // that much silence up to a whole frame. The final granule position trims it.
+        if flush {
+            let remaining = (self.output_frames + self.pre_skip - self.granule) as usize;
+            let padded = remaining.next_multiple_of(FRAME_SIZE) * self.channels;
+            self.pcm.resize(padded.max(self.pcm.len()), 0.0);
+        }
+
+        let mut offset = 0;
+        while self.pcm.len() - offset >= frame_len {
+            let frame = &self.pcm[offset..offset + frame_len];
+            let size = self.encoder.encode_float(frame, &mut self.packet)?;
+            offset += frame_len;
+
+            // Granule positions count every decoded sample, the pre-skip included
+            self.granule += FRAME_SIZE as u64;
+            let packet = self.packet[..size].to_vec();
+            if let Some((previous, granule)) = self.pending.replace((packet, self.granule)) {
+                self.packets.write_packet(
+                    previous.into_boxed_slice(),
+                    self.serial,
+                    PacketWriteEndInfo::NormalPacket,
+                    granule,
+                )?;
+            }
+        }
+        self.pcm.drain(..offset);
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), DecodeError> {
+        self.resample(true)?;
+        self.encode_frames(true)?;
+
+        let (last, _) = self.pending.take().ok_or("No audio decoded")?;
+        self.packets.write_packet(
+            last.into_boxed_slice(),
+            self.serial,
+            PacketWriteEndInfo::EndStream,
+            self.pre_skip + self.output_frames,
+        )?;
+        self.packets.inner_mut().flush()?;
+
+        Ok(())
+    }
+}
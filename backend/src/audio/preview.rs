@@ -0,0 +1,73 @@
+use std::{ops::ControlFlow, path::Path};
+
+use super::decode::{decode_audio, DecodeError, PcmFormat};
+
+// Where a preview of `duration` seconds sounds most like the track: the
+// loudest stretch, which is usually the chorus rather than the intro.
+// Seconds from the start, 0 for tracks no longer than the preview.
+pub fn find_preview_start(input: &Path, duration: u32) -> Result<u32, DecodeError> {
+    let mut meter: Option<EnergyMeter> = None;
+
+    decode_audio(input, |format, samples| {
+        let meter = match &mut meter {
+            Some(meter) if meter.format == format => meter,
+            Some(_) => return Err("Sample rate or channels change mid-stream".into()),
+            None => meter.insert(EnergyMeter::new(format)),
+        };
+
+        meter.push(samples);
+        Ok(ControlFlow::Continue(()))
+    })?;
+
+    let seconds = meter.ok_or("No audio decoded")?.seconds;
+    let window = duration as usize;
+    if window == 0 || seconds.len() <= window {
+        return Ok(0);
+    }
+
+    // Sliding sum over the window, the earliest of equally loud ones wins
+    let mut energy: f64 = seconds[..window].iter().sum();
+    let (mut best, mut best_energy) = (0, energy);
+    for start in 1..=seconds.len() - window {
+        energy += seconds[start + window - 1] - seconds[start - 1];
+        if energy > best_energy {
+            (best, best_energy) = (start, energy);
+        }
+    }
+
+    Ok(best as u32)
+}
+
+// Mean square of every full second, channels mixed down
+struct EnergyMeter {
+    format: PcmFormat,
+    energy: f64,
+    filled: u32,
+    seconds: Vec<f64>,
+}
+
+impl EnergyMeter {
+    fn new(format: PcmFormat) -> Self {
+        EnergyMeter {
+            format,
+            energy: 0.0,
+            filled: 0,
+            seconds: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        let channels = self.format.channels;
+
+        for frame in samples.chunks_exact(channels) {
+            let sample = frame.iter().sum::<f32>() as f64 / channels as f64;
+            self.energy += sample * sample;
+            self.filled += 1;
+
+            if self.filled == self.format.sample_rate {
+                self.seconds.push(self.energy / self.filled as f64);
+                (self.energy, self.filled) = (0.0, 0);
+            }
+        }
+    }
+}
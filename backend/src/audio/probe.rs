@@ -0,0 +1,132 @@
+use std::{
+    fs::File,
+    io::{ErrorKind, Read},
+    path::Path,
+};
+
+use chrono::Duration;
+use symphonia::core::{
+    errors::Error,
+    formats::{FormatOptions, FormatReader},
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+    units::TimeBase,
+};
+
+use super::{
+    format::{codec_name, mime_type, Container},
+    tags::{read_cover, read_metadata, CoverArt},
+};
+use crate::models::TrackMetadata;
+
+// Enough bytes to look past a typical ID3v2 tag when sniffing the container
+const SNIFF_LEN: u64 = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct AudioInfo {
+    pub duration: Duration,
+    pub container: Container,
+    pub codec: String,
+    pub mime_type: String,
+    pub metadata: TrackMetadata,
+    pub cover: Option<CoverArt>,
+}
+
+pub fn sniff_container(file_path: &Path) -> std::io::Result<Option<Container>> {
+    let mut header = Vec::new();
+    File::open(file_path)?
+        .take(SNIFF_LEN)
+        .read_to_end(&mut header)?;
+
+    Ok(Container::sniff(&header))
+}
+
+pub fn probe_audio(file_path: &Path) -> Result<AudioInfo, Box<dyn std::error::Error>> {
+    let container = sniff_container(file_path)?.ok_or("Unsupported audio container")?;
+
+    // Open the audio file
+    let file = File::open(file_path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    // Create a hint to help the format registry guess the format
+    let mut hint = Hint::new();
+    hint.with_extension(container.extension());
+
+    // Probe the media file
+    let mut probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    // Get the default track
+    let track = probed.format.default_track().ok_or("No track found")?;
+    let track_id = track.id;
+    let codec_params = track.codec_params.clone();
+
+    // Raw streams without a time base count in samples
+    let time_base = codec_params
+        .time_base
+        .or_else(|| codec_params.sample_rate.map(|rate| TimeBase::new(1, rate)))
+        .ok_or("No time base")?;
+
+    let codec = codec_name(codec_params.codec);
+
+    // Tags are read before demuxing, which may move the metadata log on
+    let metadata = read_metadata(&mut probed);
+    let cover = read_cover(&mut probed);
+
+    // Xing/Info headers, STREAMINFO and friends give the length up front. VBR MP3
+    // without one and some Ogg streams don't, those are demuxed to the end.
+    let n_frames = match codec_params.n_frames.filter(|n_frames| *n_frames > 0) {
+        Some(n_frames) => n_frames,
+        None => count_frames(probed.format.as_mut(), track_id, codec_params.start_ts)?,
+    };
+
+    Ok(AudioInfo {
+        duration: frames_to_duration(n_frames, time_base),
+        container,
+        codec: codec.to_string(),
+        mime_type: mime_type(container, Some(codec)),
+        metadata,
+        cover,
+    })
+}
+
+// Walks every packet of the track, only the container is parsed, nothing is decoded
+fn count_frames(
+    format: &mut dyn FormatReader,
+    track_id: u32,
+    start_ts: u64,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut end_ts = start_ts;
+
+    loop {
+        match format.next_packet() {
+            Ok(packet) if packet.track_id() == track_id => {
+                end_ts = end_ts.max(packet.ts() + packet.dur());
+            }
+            Ok(_) => {}
+            Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            // A damaged tail still leaves a usable length
+            Err(_) if end_ts > start_ts => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if end_ts <= start_ts {
+        return Err("No audio packets found".into());
+    }
+
+    Ok(end_ts - start_ts)
+}
+
+fn frames_to_duration(n_frames: u64, time_base: TimeBase) -> Duration {
+    let microseconds =
+        n_frames as u128 * time_base.numer as u128 * 1_000_000 / time_base.denom as u128;
+
+    Duration::microseconds(microseconds.min(i64::MAX as u128) as i64)
+}
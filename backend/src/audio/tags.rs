@@ -0,0 +1,160 @@
+use symphonia::core::{
+    meta::{MetadataRevision, StandardTagKey, StandardVisualKey, Visual},
+    probe::ProbeResult,
+};
+
+use crate::models::TrackMetadata;
+
+// Matches the VARCHAR(255) text columns on `tracks`
+const MAX_TEXT_LEN: usize = 255;
+
+// Reads ID3, Vorbis comment and MP4 atom tags. Tags belonging to the container
+// win over ones found in front of it (an ID3v2 block before a FLAC stream).
+pub fn read_metadata(probed: &mut ProbeResult) -> TrackMetadata {
+    let mut metadata = TrackMetadata::default();
+
+    if let Some(revision) = probed.format.metadata().current() {
+        apply_revision(&mut metadata, revision);
+    }
+
+    if let Some(mut log) = probed.metadata.get() {
+        if let Some(revision) = log.skip_to_latest() {
+            apply_revision(&mut metadata, revision);
+        }
+    }
+
+    metadata
+}
+
+#[derive(Debug, Clone)]
+pub struct CoverArt {
+    pub extension: &'static str,
+    pub data: Box<[u8]>,
+}
+
+// Embedded artwork (ID3 APIC, FLAC PICTURE, MP4 covr). The front cover is
+// preferred, otherwise the first picture that is in a format browsers show.
+pub fn read_cover(probed: &mut ProbeResult) -> Option<CoverArt> {
+    let mut visuals: Vec<Visual> = Vec::new();
+
+    if let Some(revision) = probed.format.metadata().current() {
+        visuals.extend(revision.visuals().iter().cloned());
+    }
+
+    if let Some(mut log) = probed.metadata.get() {
+        if let Some(revision) = log.skip_to_latest() {
+            visuals.extend(revision.visuals().iter().cloned());
+        }
+    }
+
+    let front_cover = visuals
+        .iter()
+        .position(|visual| visual.usage == Some(StandardVisualKey::FrontCover));
+    if let Some(index) = front_cover {
+        visuals.swap(0, index);
+    }
+
+    visuals.into_iter().find_map(|visual| {
+        let extension = image_extension(&visual.media_type, &visual.data)?;
+        Some(CoverArt {
+            extension,
+            data: visual.data,
+        })
+    })
+}
+
+// The declared media type is often missing or wrong, fall back to the magic bytes
+fn image_extension(media_type: &str, data: &[u8]) -> Option<&'static str> {
+    match media_type.to_ascii_lowercase().as_str() {
+        "image/jpeg" | "image/jpg" => return Some("jpg"),
+        "image/png" => return Some("png"),
+        "image/gif" => return Some("gif"),
+        "image/webp" => return Some("webp"),
+        _ => {}
+    }
+
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpg")
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if data.starts_with(b"GIF8") {
+        Some("gif")
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("webp")
+    } else {
+        None
+    }
+}
+
+fn apply_revision(metadata: &mut TrackMetadata, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        let Some(key) = tag.std_key else {
+            continue;
+        };
+
+        let value = tag.value.to_string();
+        let value = value.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+        if value.is_empty() {
+            continue;
+        }
+
+        match key {
+            StandardTagKey::TrackTitle => set(&mut metadata.title, Some(text(value))),
+            StandardTagKey::Artist => set(&mut metadata.artist, Some(text(value))),
+            StandardTagKey::Album => set(&mut metadata.album, Some(text(value))),
+            StandardTagKey::AlbumArtist => set(&mut metadata.album_artist, Some(text(value))),
+            StandardTagKey::Genre => set(&mut metadata.genre, Some(text(value))),
+            StandardTagKey::TrackNumber => set(&mut metadata.track_number, leading_number(value)),
+            StandardTagKey::DiscNumber => set(&mut metadata.disc_number, leading_number(value)),
+            StandardTagKey::Date | StandardTagKey::ReleaseDate | StandardTagKey::OriginalDate => {
+                set(&mut metadata.year, year(value))
+            }
+            StandardTagKey::IdentIsrc => set(&mut metadata.isrc, normalize_isrc(value)),
+            StandardTagKey::Lyrics => set(&mut metadata.lyrics, Some(value.to_string())),
+            _ => {}
+        }
+    }
+}
+
+// The first source that has a value keeps it
+fn set<T>(field: &mut Option<T>, value: Option<T>) {
+    if field.is_none() {
+        *field = value;
+    }
+}
+
+fn text(value: &str) -> String {
+    value.chars().take(MAX_TEXT_LEN).collect()
+}
+
+// "3/12" -> 3
+fn leading_number(value: &str) -> Option<i32> {
+    let digits: String = value
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+
+    digits.parse().ok().filter(|number| *number > 0)
+}
+
+// "2019", "2019-05-01" or "2019-05-01T00:00:00Z" -> 2019
+fn year(value: &str) -> Option<i32> {
+    let year = value.get(0..4)?;
+    if !year.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    year.parse().ok().filter(|year| (1000..=9999).contains(year))
+}
+
+// "US-S1Z-99-00001" -> "USS1Z9900001"
+pub fn normalize_isrc(value: &str) -> Option<String> {
+    let isrc: String = value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    (isrc.len() == 12).then_some(isrc)
+}
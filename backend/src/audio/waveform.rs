@@ -0,0 +1,166 @@
+use std::{ops::ControlFlow, path::Path};
+
+use serde_json::json;
+
+use super::decode::{decode_audio, DecodeError, PcmFormat};
+
+// Enough detail for a scrubber, about 300 KB of binary data for a two hour track
+const PIXELS_PER_SECOND: u32 = 20;
+
+// Peak data in the audiowaveform formats, so existing players such as peaks.js
+// can read it: https://github.com/bbc/audiowaveform/blob/master/doc/DataFormat.md
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaveformFormat {
+    Json,
+    Binary,
+}
+
+impl WaveformFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(WaveformFormat::Json),
+            "dat" => Some(WaveformFormat::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            WaveformFormat::Json => "json",
+            WaveformFormat::Binary => "dat",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            WaveformFormat::Json => "application/json",
+            WaveformFormat::Binary => "application/octet-stream",
+        }
+    }
+}
+
+// Min / max pairs of the channels mixed down to one, at 8 bits
+#[derive(Debug, Clone)]
+pub struct Waveform {
+    pub sample_rate: u32,
+    pub samples_per_pixel: u32,
+    pub data: Vec<i8>,
+}
+
+impl Waveform {
+    pub fn encode(&self, format: WaveformFormat) -> Vec<u8> {
+        match format {
+            WaveformFormat::Json => self.to_json(),
+            WaveformFormat::Binary => self.to_binary(),
+        }
+    }
+
+    fn length(&self) -> u32 {
+        (self.data.len() / 2) as u32
+    }
+
+    fn to_json(&self) -> Vec<u8> {
+        json!({
+            "version": 2,
+            "channels": 1,
+            "sample_rate": self.sample_rate,
+            "samples_per_pixel": self.samples_per_pixel,
+            "bits": 8,
+            "length": self.length(),
+            "data": self.data,
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    // Version 2 header, all little-endian, then the 8-bit pairs
+    fn to_binary(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(24 + self.data.len());
+        out.extend_from_slice(&2i32.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes()); // 8-bit data
+        out.extend_from_slice(&(self.sample_rate as i32).to_le_bytes());
+        out.extend_from_slice(&(self.samples_per_pixel as i32).to_le_bytes());
+        out.extend_from_slice(&self.length().to_le_bytes());
+        out.extend_from_slice(&1i32.to_le_bytes()); // channels
+        out.extend(self.data.iter().map(|value| *value as u8));
+        out
+    }
+}
+
+pub fn generate_waveform(input: &Path) -> Result<Waveform, DecodeError> {
+    let mut builder: Option<WaveformBuilder> = None;
+
+    decode_audio(input, |format, samples| {
+        let builder = match &mut builder {
+            Some(builder) if builder.format == format => builder,
+            Some(_) => return Err("Sample rate or channels change mid-stream".into()),
+            None => builder.insert(WaveformBuilder::new(format)),
+        };
+
+        builder.push(samples);
+        Ok(ControlFlow::Continue(()))
+    })?;
+
+    Ok(builder.ok_or("No audio decoded")?.finish())
+}
+
+struct WaveformBuilder {
+    format: PcmFormat,
+    waveform: Waveform,
+    // Pixel being filled: samples so far, min and max
+    count: u32,
+    min: f32,
+    max: f32,
+}
+
+impl WaveformBuilder {
+    fn new(format: PcmFormat) -> Self {
+        WaveformBuilder {
+            format,
+            waveform: Waveform {
+                sample_rate: format.sample_rate,
+                samples_per_pixel: (format.sample_rate / PIXELS_PER_SECOND).max(1),
+                data: Vec::new(),
+            },
+            count: 0,
+            min: 0.0,
+            max: 0.0,
+        }
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        let channels = self.format.channels;
+
+        for frame in samples.chunks_exact(channels) {
+            let sample = frame.iter().sum::<f32>() / channels as f32;
+            if self.count == 0 {
+                (self.min, self.max) = (sample, sample);
+            } else {
+                self.min = self.min.min(sample);
+                self.max = self.max.max(sample);
+            }
+
+            self.count += 1;
+            if self.count == self.waveform.samples_per_pixel {
+                self.end_pixel();
+            }
+        }
+    }
+
+    fn end_pixel(&mut self) {
+        let to_i8 = |sample: f32| (sample * 128.0).round().clamp(-128.0, 127.0) as i8;
+
+        self.waveform.data.push(to_i8(self.min));
+        self.waveform.data.push(to_i8(self.max));
+        self.count = 0;
+    }
+
+    fn finish(mut self) -> Waveform {
+        // The last pixel may be short
+        if self.count > 0 {
+            self.end_pixel();
+        }
+
+        self.waveform
+    }
+}
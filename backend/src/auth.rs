@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use axum::{
     extract::Request, 
-    http::header, 
+    http::{header, StatusCode}, 
     middleware::Next, 
     response::IntoResponse, 
     Extension
@@ -14,7 +14,7 @@ use serde::{Deserialize, Serialize};
 use crate::{
     database::users::UserExt, 
     error::{ErrorMessage, HttpError}, 
-    models::User, 
+    models::{User, ADMIN_ROLE}, 
     utils::token, 
     AppState
 };
@@ -84,3 +84,16 @@ pub async fn auth(
 
     Ok(next.run(req).await)
 }
+
+// Goes inside `auth`, for routes only admins may use
+pub async fn admin(
+    Extension(user): Extension<JWTAuthMiddleware>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HttpError> {
+    if user.user.role != ADMIN_ROLE {
+        return Err(HttpError::new("Admins only", StatusCode::FORBIDDEN));
+    }
+
+    Ok(next.run(req).await)
+}
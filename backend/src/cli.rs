@@ -0,0 +1,209 @@
+use std::path::Path;
+
+use crate::{
+    audio::{
+        hls::{self, HlsVariant},
+        waveform::{generate_waveform, WaveformFormat},
+    },
+    config::Config,
+    database::{
+        jobs::JobExt, libraries::LibraryExt, track::TrackExt, upload::UploadExt, users::UserExt,
+    },
+    jobs,
+    models::{User, ADMIN_ROLE, USER_ROLE},
+    storage, AppState,
+};
+
+const USAGE: &str = "Usage:
+  backend package-hls <output_dir> <input>...
+  backend waveform <input> <output.json|output.dat>
+  backend backfill <transcode|hls|loudness|waveform|fingerprint|preview|file-size>
+  backend import-library <username|email> <directory>
+  backend set-role <username|email> <user|admin>";
+
+// Commands run with `backend <command> [args...]` instead of starting the server
+pub async fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args {
+        [command, output_dir, inputs @ ..] if command == "package-hls" && !inputs.is_empty() => {
+            package_hls(Path::new(output_dir), inputs)
+        }
+        [command, input, output] if command == "waveform" => {
+            waveform(Path::new(input), Path::new(output))
+        }
+        [command, job] if command == "backfill" => backfill(job).await,
+        [command, user, directory] if command == "import-library" => {
+            import_library(user, Path::new(directory)).await
+        }
+        [command, user, role] if command == "set-role" => set_role(user, role).await,
+        _ => Err(USAGE.into()),
+    }
+}
+
+// Packages local files into an HLS tree, one variant per input named after
+// the file: `backend package-hls out/ opus_160.opus opus_96.opus`
+fn package_hls(output_dir: &Path, inputs: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut variants: Vec<(String, HlsVariant)> = Vec::new();
+
+    for input in inputs {
+        let input = Path::new(input);
+        let name: String = input
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        if name.is_empty() || variants.iter().any(|(existing, _)| *existing == name) {
+            return Err(format!("{} needs a file name of its own", input.display()).into());
+        }
+
+        let variant = hls::package_variant(input, &output_dir.join(&name))
+            .map_err(|e| format!("{}: {}", input.display(), e))?;
+        println!(
+            "{} -> {}/{} ({}, {} bits/s)",
+            input.display(),
+            name,
+            hls::MEDIA_PLAYLIST,
+            variant.codecs,
+            variant.bandwidth
+        );
+
+        variants.push((name, variant));
+    }
+
+    let master = output_dir.join(hls::MASTER_PLAYLIST);
+    std::fs::write(&master, hls::master_playlist(&variants))?;
+    println!("✅ Wrote {}", master.display());
+
+    Ok(())
+}
+
+// Same output as the waveform job, the format follows the output's extension
+fn waveform(input: &Path, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let format = output
+        .extension()
+        .and_then(|extension| WaveformFormat::from_name(&extension.to_string_lossy()))
+        .ok_or("The output must be a .json or .dat file")?;
+
+    let waveform = generate_waveform(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+    std::fs::write(output, waveform.encode(format))?;
+    println!("✅ Wrote {}", output.display());
+
+    Ok(())
+}
+
+// Queues a job for every complete track, for tracks uploaded before the job
+// existed. Running servers pick them up, jobs with nothing to do finish at once.
+async fn backfill(job: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let kind = match job {
+        "transcode" => jobs::TRANSCODE,
+        "hls" => jobs::PACKAGE_HLS,
+        "loudness" => jobs::ANALYZE_LOUDNESS,
+        "waveform" => jobs::GENERATE_WAVEFORM,
+        "fingerprint" => jobs::FINGERPRINT,
+        "preview" => jobs::GENERATE_PREVIEW,
+        "file-size" => return backfill_file_sizes().await,
+        _ => return Err(USAGE.into()),
+    };
+    if !cfg!(feature = "opus") && [jobs::TRANSCODE, jobs::GENERATE_PREVIEW].contains(&kind) {
+        return Err(jobs::OPUS_MISSING.into());
+    }
+
+    let app_state = crate::connect(&Config::init()).await;
+    let queued = app_state.db_client.enqueue_backfill(kind).await?;
+    println!("✅ Queued {} {} jobs", queued, kind);
+
+    Ok(())
+}
+
+// Stats the stored originals of tracks uploaded before quotas, which count
+// as empty until they have their size
+async fn backfill_file_sizes() -> Result<(), Box<dyn std::error::Error>> {
+    let app_state = crate::connect(&Config::init()).await;
+    let track_ids = app_state.db_client.get_unsized_track_ids().await?;
+
+    let mut sized = 0;
+    for track_id in track_ids {
+        let Some(audio_key) = app_state
+            .db_client
+            .get_track(track_id)
+            .await?
+            .as_ref()
+            .and_then(storage::track_audio_key)
+        else {
+            continue;
+        };
+
+        match app_state.storage.stat(&audio_key).await? {
+            Some(object) => {
+                app_state
+                    .db_client
+                    .save_file_size(track_id, object.size as i64)
+                    .await?;
+                sized += 1;
+            }
+            None => eprintln!("{}: {} is missing from storage", track_id, audio_key),
+        }
+    }
+    println!("✅ Stored the file size of {} tracks", sized);
+
+    Ok(())
+}
+
+async fn find_user(app_state: &AppState, user: &str) -> Result<User, Box<dyn std::error::Error>> {
+    let by_name = app_state.db_client.get_user(None, Some(user), None).await?;
+    let found = match by_name {
+        Some(found) => Some(found),
+        None => app_state.db_client.get_user(None, None, Some(user)).await?,
+    };
+
+    found.ok_or_else(|| format!("No user named {}", user).into())
+}
+
+// Queues a scan of a directory into the user's tracks, the same as the admin
+// API but for any directory the server can read. Importing the same directory
+// again rescans it.
+async fn import_library(user: &str, directory: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let directory =
+        std::fs::canonicalize(directory).map_err(|e| format!("{}: {}", directory.display(), e))?;
+    if !directory.is_dir() {
+        return Err(format!("{} is not a directory", directory.display()).into());
+    }
+
+    let app_state = crate::connect(&Config::init()).await;
+    let user = find_user(&app_state, user).await?;
+    let library = app_state
+        .db_client
+        .save_library(user.id, &directory.to_string_lossy())
+        .await?;
+
+    if jobs::library::queue_scan(&app_state, library.id).await? {
+        println!(
+            "✅ Queued a scan of {} for {} ({})",
+            directory.display(),
+            user.username,
+            library.id
+        );
+    } else {
+        println!(
+            "✅ {} is already being scanned ({})",
+            directory.display(),
+            library.id
+        );
+    }
+
+    Ok(())
+}
+
+async fn set_role(user: &str, role: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if role != USER_ROLE && role != ADMIN_ROLE {
+        return Err(USAGE.into());
+    }
+
+    let app_state = crate::connect(&Config::init()).await;
+    let user = find_user(&app_state, user).await?;
+    app_state.db_client.update_role(user.id, role).await?;
+    println!("✅ {} is now {}", user.username, role);
+
+    Ok(())
+}
@@ -4,6 +4,51 @@ pub struct Config {
     pub jwt_secret: String,
     pub jwt_maxage: i64,
     pub port: u16,
+    pub storage_backend: String,
+    pub storage_dir: String,
+    pub s3_bucket: Option<String>,
+    pub s3_endpoint: Option<String>,
+    pub s3_region: Option<String>,
+    pub allowed_containers: Vec<String>,
+    pub allowed_codecs: Vec<String>,
+    pub max_track_duration: i64,
+    pub max_upload_size: i64,
+    pub allowed_image_types: Vec<String>,
+    pub max_image_dimension: u32,
+    pub max_image_size: usize,
+    pub job_workers: usize,
+    pub normalized_rendition: bool,
+    pub preview_duration: u32,
+    pub upload_ttl: u64,
+    pub storage_quota: i64,
+    pub track_quota: i64,
+    pub library_root: Option<String>,
+}
+
+// Comma separated, case insensitive list such as `mp3,flac,ogg`
+fn env_list(name: &str, default: &str) -> Vec<String> {
+    std::env::var(name)
+        .unwrap_or_else(|_| default.to_string())
+        .split(',')
+        .map(|item| item.trim().to_ascii_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn env_number<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} must be a number", name)),
+        Err(_) => default,
+    }
+}
+
+fn env_flag(name: &str) -> bool {
+    matches!(
+        std::env::var(name).map(|value| value.to_ascii_lowercase()).as_deref(),
+        Ok("true" | "1" | "yes")
+    )
 }
 
 impl Config {
@@ -12,11 +57,42 @@ impl Config {
         let jwt_secret = std::env::var("JWT_SECRET_KEY").expect("JWT_SECRET_KEY must be set");
         let jwt_maxage = std::env::var("JWT_MAXAGE").expect("JWT_MAXAGE must be set");
 
+        // Local disk by default, `s3` for shared object storage between replicas
+        let storage_backend =
+            std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "local".to_string());
+        let storage_dir = std::env::var("STORAGE_DIR").unwrap_or_else(|_| ".".to_string());
+
         Config {
             database_url,
             jwt_secret,
             jwt_maxage: jwt_maxage.parse::<i64>().unwrap(),
             port: 8000,
+            storage_backend,
+            storage_dir,
+            s3_bucket: std::env::var("S3_BUCKET").ok(),
+            s3_endpoint: std::env::var("S3_ENDPOINT").ok(),
+            s3_region: std::env::var("S3_REGION").ok(),
+            allowed_containers: env_list("ALLOWED_AUDIO_CONTAINERS", "mp3,flac,ogg,wav,aac,mp4"),
+            allowed_codecs: env_list("ALLOWED_AUDIO_CODECS", "mp3,aac,flac,vorbis,opus,alac,pcm"),
+            // Seconds, two hours by default
+            max_track_duration: env_number("MAX_TRACK_DURATION", 2 * 60 * 60),
+            // Bytes, 500 MiB by default
+            max_upload_size: env_number("MAX_UPLOAD_SIZE", 500 * 1024 * 1024),
+            allowed_image_types: env_list("ALLOWED_IMAGE_TYPES", "jpeg,png,webp,gif"),
+            max_image_dimension: env_number("MAX_IMAGE_DIMENSION", 4096),
+            max_image_size: env_number("MAX_IMAGE_SIZE", 10 * 1024 * 1024),
+            job_workers: env_number("JOB_WORKERS", 2),
+            // Extra Opus rendition normalized to -18 LUFS, off by default
+            normalized_rendition: env_flag("NORMALIZED_RENDITION"),
+            // Seconds of the preview clip anyone may play
+            preview_duration: env_number("PREVIEW_DURATION", 30),
+            // Seconds an unfinished upload may sit idle before it is deleted, a day by default
+            upload_ttl: env_number("UPLOAD_TTL", 24 * 60 * 60),
+            // Per account unless set on the user, 10 GiB and 1000 tracks by default
+            storage_quota: env_number("USER_STORAGE_QUOTA", 10 * 1024 * 1024 * 1024),
+            track_quota: env_number("USER_TRACK_QUOTA", 1000),
+            // Directory admins may import libraries from over the API, none if unset
+            library_root: std::env::var("LIBRARY_ROOT").ok(),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,217 @@
+use async_trait::async_trait;
+use sqlx::{query, query_as};
+use uuid::Uuid;
+
+use crate::{db::DBClient, dtos::AlbumFileDto, models::Album};
+
+#[async_trait]
+pub trait AlbumExt {
+    async fn create_album(&self, album_id: Uuid, user_id: Uuid, title: &str)
+        -> Result<(), sqlx::Error>;
+
+    async fn get_album(&self, album_id: Uuid) -> Result<Option<Album>, sqlx::Error>;
+
+    async fn get_user_albums(&self, user_id: Uuid) -> Result<Vec<Album>, sqlx::Error>;
+
+    async fn set_album_cover(&self, album_id: Uuid, thumbnail_name: &str)
+        -> Result<(), sqlx::Error>;
+
+    // Tracks keep the title and cover their tags gave them, the file name and
+    // the album's cover are only fallbacks
+    async fn add_album_track(
+        &self,
+        album_id: Uuid,
+        track_id: Uuid,
+        title: &str,
+        thumbnail_name: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn save_album_file(
+        &self,
+        album_id: Uuid,
+        path: &str,
+        track_id: Option<Uuid>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_album_files(&self, album_id: Uuid) -> Result<Vec<AlbumFileDto>, sqlx::Error>;
+
+    // Takes the album's title, artist and year from the tags of its first
+    // track, and marks the import as done
+    async fn finish_album_import(&self, album_id: Uuid) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+impl AlbumExt for DBClient {
+    async fn create_album(
+        &self,
+        album_id: Uuid,
+        user_id: Uuid,
+        title: &str,
+    ) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            INSERT INTO albums (id, user_id, title)
+            VALUES ($1, $2, $3)
+            "#,
+            album_id,
+            user_id,
+            title,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_album(&self, album_id: Uuid) -> Result<Option<Album>, sqlx::Error> {
+        let album = query_as!(
+            Album,
+            r#"
+            SELECT id, user_id, title, artist, release_year, thumbnail_name, import_status,
+                created_at, updated_at
+            FROM albums
+            WHERE id = $1
+            "#,
+            album_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(album)
+    }
+
+    async fn get_user_albums(&self, user_id: Uuid) -> Result<Vec<Album>, sqlx::Error> {
+        let albums = query_as!(
+            Album,
+            r#"
+            SELECT id, user_id, title, artist, release_year, thumbnail_name, import_status,
+                created_at, updated_at
+            FROM albums
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(albums)
+    }
+
+    async fn set_album_cover(
+        &self,
+        album_id: Uuid,
+        thumbnail_name: &str,
+    ) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            UPDATE albums
+            SET thumbnail_name = $2, updated_at = Now()
+            WHERE id = $1
+            "#,
+            album_id,
+            thumbnail_name,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn add_album_track(
+        &self,
+        album_id: Uuid,
+        track_id: Uuid,
+        title: &str,
+        thumbnail_name: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            UPDATE tracks
+            SET album_id = $1,
+                title = COALESCE(title, $3),
+                thumbnail_name = COALESCE(thumbnail_name, $4),
+                updated_at = Now()
+            WHERE id = $2
+            "#,
+            album_id,
+            track_id,
+            title,
+            thumbnail_name,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn save_album_file(
+        &self,
+        album_id: Uuid,
+        path: &str,
+        track_id: Option<Uuid>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            INSERT INTO album_files (album_id, path, track_id, error)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (album_id, path)
+            DO UPDATE SET track_id = EXCLUDED.track_id, error = EXCLUDED.error
+            "#,
+            album_id,
+            path,
+            track_id,
+            error,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_album_files(&self, album_id: Uuid) -> Result<Vec<AlbumFileDto>, sqlx::Error> {
+        let files = query_as!(
+            AlbumFileDto,
+            r#"
+            SELECT f.path, f.track_id, t.title, t.upload_status, COALESCE(f.error, t.failure_reason) AS error
+            FROM album_files f
+            LEFT JOIN tracks t ON t.id = f.track_id
+            WHERE f.album_id = $1
+            ORDER BY f.path
+            "#,
+            album_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(files)
+    }
+
+    async fn finish_album_import(&self, album_id: Uuid) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            WITH first AS (
+                SELECT album, COALESCE(album_artist, artist) AS artist, release_year
+                FROM tracks
+                WHERE album_id = $1 AND upload_status = 'complete'
+                ORDER BY disc_number NULLS LAST, track_number NULLS LAST, created_at
+                LIMIT 1
+            )
+            UPDATE albums
+            SET title = COALESCE((SELECT album FROM first), title),
+                artist = COALESCE((SELECT artist FROM first), artist),
+                release_year = COALESCE((SELECT release_year FROM first), release_year),
+                import_status = 'complete',
+                updated_at = Now()
+            WHERE id = $1
+            "#,
+            album_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -80,7 +80,12 @@ impl FavoritesExt for DBClient {
                 true AS is_favorite,
                 COALESCE(ph.played_at, NULL) AS played_at,
                 COALESCE(ph.duration_played, INTERVAL '0 seconds') AS duration_played,  -- Default to 0 if no playback history
-                CASE WHEN t.user_id = $1 THEN true ELSE false END as is_created_by_user
+                CASE WHEN t.user_id = $1 THEN true ELSE false END as is_created_by_user,
+                t.loudness,
+                t.loudness_range,
+                t.true_peak,
+                t.album_loudness,
+                t.album_true_peak
             FROM 
                 tracks t
             JOIN 
@@ -92,7 +92,12 @@ impl HistoryExt for DBClient {
                 t.upload_status,
                 t.thumbnail_name,
                 CASE WHEN uf.id IS NOT NULL THEN TRUE ELSE FALSE END AS is_favorite,
-                CASE WHEN t.user_id = $1 THEN true ELSE false END as is_created_by_user
+                CASE WHEN t.user_id = $1 THEN true ELSE false END as is_created_by_user,
+                t.loudness,
+                t.loudness_range,
+                t.true_peak,
+                t.album_loudness,
+                t.album_true_peak
             FROM
                 playback_history ph
             JOIN
@@ -0,0 +1,170 @@
+use async_trait::async_trait;
+use sqlx::{query, query_as};
+use uuid::Uuid;
+
+use crate::{
+    db::{interval, DBClient},
+    models::Job,
+};
+
+#[async_trait]
+pub trait JobExt {
+    async fn enqueue_job(
+        &self,
+        kind: &str,
+        track_id: Option<Uuid>,
+        payload: Option<&str>,
+    ) -> Result<Uuid, sqlx::Error>;
+
+    // Queues `kind` for every complete track that doesn't have one waiting
+    async fn enqueue_backfill(&self, kind: &str) -> Result<u64, sqlx::Error>;
+
+    // Takes the oldest due job, concurrent workers never get the same one
+    async fn claim_job(&self) -> Result<Option<Job>, sqlx::Error>;
+
+    async fn complete_job(&self, job_id: Uuid) -> Result<(), sqlx::Error>;
+
+    // Puts the job back in the queue after `retry_in`, or gives up on it
+    async fn fail_job(
+        &self,
+        job_id: Uuid,
+        error: &str,
+        retry_in: Option<std::time::Duration>,
+    ) -> Result<(), sqlx::Error>;
+
+    // Jobs left running by a worker that died with its process
+    async fn requeue_stale_jobs(
+        &self,
+        stale_after: std::time::Duration,
+    ) -> Result<u64, sqlx::Error>;
+}
+
+#[async_trait]
+impl JobExt for DBClient {
+    async fn enqueue_job(
+        &self,
+        kind: &str,
+        track_id: Option<Uuid>,
+        payload: Option<&str>,
+    ) -> Result<Uuid, sqlx::Error> {
+        let job = query!(
+            r#"
+            INSERT INTO jobs (kind, track_id, payload)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            "#,
+            kind,
+            track_id,
+            payload,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(job.id)
+    }
+
+    async fn enqueue_backfill(&self, kind: &str) -> Result<u64, sqlx::Error> {
+        let result = query!(
+            r#"
+            INSERT INTO jobs (kind, track_id)
+            SELECT $1::varchar, t.id
+            FROM tracks t
+            WHERE t.upload_status = 'complete'
+                AND t.content_hash IS NOT NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM jobs j
+                    WHERE j.track_id = t.id
+                        AND j.kind = $1::varchar
+                        AND j.status IN ('pending', 'running')
+                )
+            "#,
+            kind
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn claim_job(&self) -> Result<Option<Job>, sqlx::Error> {
+        query_as!(
+            Job,
+            r#"
+            UPDATE jobs
+            SET status = 'running',
+                attempts = attempts + 1,
+                updated_at = Now()
+            WHERE id = (
+                SELECT id FROM jobs
+                WHERE status = 'pending' AND run_after <= Now()
+                ORDER BY run_after
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, kind, track_id, payload, attempts
+            "#
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn complete_job(&self, job_id: Uuid) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            UPDATE jobs
+            SET status = 'done', last_error = NULL, updated_at = Now()
+            WHERE id = $1
+            "#,
+            job_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn fail_job(
+        &self,
+        job_id: Uuid,
+        error: &str,
+        retry_in: Option<std::time::Duration>,
+    ) -> Result<(), sqlx::Error> {
+        let retry_in = retry_in.map(interval);
+
+        query!(
+            r#"
+            UPDATE jobs
+            SET status = CASE WHEN $3::interval IS NULL THEN 'failed' ELSE 'pending' END,
+                last_error = $2,
+                run_after = Now() + COALESCE($3::interval, '0'::interval),
+                updated_at = Now()
+            WHERE id = $1
+            "#,
+            job_id,
+            error,
+            retry_in,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn requeue_stale_jobs(
+        &self,
+        stale_after: std::time::Duration,
+    ) -> Result<u64, sqlx::Error> {
+        let result = query!(
+            r#"
+            UPDATE jobs
+            SET status = 'pending', updated_at = Now()
+            WHERE status = 'running' AND updated_at < Now() - $1::interval
+            "#,
+            interval(stale_after)
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,280 @@
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use sqlx::{query, query_as};
+use uuid::Uuid;
+
+use crate::{db::DBClient, dtos::LibraryFileDto, models::Library};
+
+#[async_trait]
+pub trait LibraryExt {
+    // The user's library at `root_path`, made on the first import
+    async fn save_library(&self, user_id: Uuid, root_path: &str) -> Result<Library, sqlx::Error>;
+
+    async fn get_library(&self, library_id: Uuid) -> Result<Option<Library>, sqlx::Error>;
+
+    async fn get_libraries(&self) -> Result<Vec<Library>, sqlx::Error>;
+
+    // Marks a scan as queued, unless one already is or is running
+    async fn queue_library_scan(&self, library_id: Uuid) -> Result<bool, sqlx::Error>;
+
+    async fn set_scan_status(&self, library_id: Uuid, scan_status: &str)
+        -> Result<(), sqlx::Error>;
+
+    async fn finish_library_scan(&self, library_id: Uuid) -> Result<(), sqlx::Error>;
+
+    async fn get_library_files(&self, library_id: Uuid)
+        -> Result<Vec<LibraryFileDto>, sqlx::Error>;
+
+    async fn save_library_file(
+        &self,
+        library_id: Uuid,
+        path: &str,
+        file_size: i64,
+        modified_at: NaiveDateTime,
+        track_id: Option<Uuid>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
+
+    // Forgets files that are gone from disk, and deletes their tracks
+    async fn remove_library_files(
+        &self,
+        library_id: Uuid,
+        paths: &[String],
+    ) -> Result<u64, sqlx::Error>;
+
+    // Everything a library track got from its old audio's tags, so the new
+    // audio's tags take over
+    async fn clear_library_track(&self, track_id: Uuid) -> Result<(), sqlx::Error>;
+
+    // Tracks without a title in their tags are named after their file
+    async fn fill_track_title(&self, track_id: Uuid, title: &str) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+impl LibraryExt for DBClient {
+    async fn save_library(&self, user_id: Uuid, root_path: &str) -> Result<Library, sqlx::Error> {
+        let library = query_as!(
+            Library,
+            r#"
+            INSERT INTO libraries (user_id, root_path)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id, root_path) DO UPDATE SET user_id = EXCLUDED.user_id
+            RETURNING id, user_id, root_path, scan_status, scanned_at, created_at, updated_at
+            "#,
+            user_id,
+            root_path,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(library)
+    }
+
+    async fn get_library(&self, library_id: Uuid) -> Result<Option<Library>, sqlx::Error> {
+        let library = query_as!(
+            Library,
+            r#"
+            SELECT id, user_id, root_path, scan_status, scanned_at, created_at, updated_at
+            FROM libraries
+            WHERE id = $1
+            "#,
+            library_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(library)
+    }
+
+    async fn get_libraries(&self) -> Result<Vec<Library>, sqlx::Error> {
+        let libraries = query_as!(
+            Library,
+            r#"
+            SELECT id, user_id, root_path, scan_status, scanned_at, created_at, updated_at
+            FROM libraries
+            ORDER BY created_at DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(libraries)
+    }
+
+    async fn queue_library_scan(&self, library_id: Uuid) -> Result<bool, sqlx::Error> {
+        let queued = query!(
+            r#"
+            UPDATE libraries
+            SET scan_status = 'pending', updated_at = Now()
+            WHERE id = $1 AND scan_status NOT IN ('pending', 'scanning')
+            RETURNING id
+            "#,
+            library_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(queued.is_some())
+    }
+
+    async fn set_scan_status(
+        &self,
+        library_id: Uuid,
+        scan_status: &str,
+    ) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            UPDATE libraries
+            SET scan_status = $2, updated_at = Now()
+            WHERE id = $1
+            "#,
+            library_id,
+            scan_status,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn finish_library_scan(&self, library_id: Uuid) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            UPDATE libraries
+            SET scan_status = 'idle', scanned_at = Now(), updated_at = Now()
+            WHERE id = $1
+            "#,
+            library_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_library_files(
+        &self,
+        library_id: Uuid,
+    ) -> Result<Vec<LibraryFileDto>, sqlx::Error> {
+        let files = query_as!(
+            LibraryFileDto,
+            r#"
+            SELECT f.path, f.file_size, f.modified_at, f.track_id, t.title, t.upload_status,
+                COALESCE(f.error, t.failure_reason) AS error
+            FROM library_files f
+            LEFT JOIN tracks t ON t.id = f.track_id
+            WHERE f.library_id = $1
+            ORDER BY f.path
+            "#,
+            library_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(files)
+    }
+
+    async fn save_library_file(
+        &self,
+        library_id: Uuid,
+        path: &str,
+        file_size: i64,
+        modified_at: NaiveDateTime,
+        track_id: Option<Uuid>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            INSERT INTO library_files (library_id, path, file_size, modified_at, track_id, error)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (library_id, path)
+            DO UPDATE SET file_size = EXCLUDED.file_size,
+                modified_at = EXCLUDED.modified_at,
+                track_id = EXCLUDED.track_id,
+                error = EXCLUDED.error
+            "#,
+            library_id,
+            path,
+            file_size,
+            modified_at,
+            track_id,
+            error,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn remove_library_files(
+        &self,
+        library_id: Uuid,
+        paths: &[String],
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // The audio stays in storage, it may be shared with other tracks
+        query!(
+            r#"
+            DELETE FROM tracks t
+            USING library_files f, libraries l
+            WHERE f.library_id = $1 AND f.path = ANY($2)
+                AND t.id = f.track_id
+                AND l.id = f.library_id AND t.user_id = l.user_id
+            "#,
+            library_id,
+            paths,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let removed = query!(
+            r#"
+            DELETE FROM library_files
+            WHERE library_id = $1 AND path = ANY($2)
+            "#,
+            library_id,
+            paths,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(removed.rows_affected())
+    }
+
+    async fn clear_library_track(&self, track_id: Uuid) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            UPDATE tracks
+            SET title = NULL, artist = NULL, album = NULL, album_artist = NULL,
+                track_number = NULL, disc_number = NULL, release_year = NULL,
+                genre = NULL, isrc = NULL, lyrics = NULL, thumbnail_name = NULL,
+                updated_at = Now()
+            WHERE id = $1
+            "#,
+            track_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn fill_track_title(&self, track_id: Uuid, title: &str) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            UPDATE tracks
+            SET title = COALESCE(title, $2), updated_at = Now()
+            WHERE id = $1
+            "#,
+            track_id,
+            title,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
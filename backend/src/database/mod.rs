@@ -3,4 +3,9 @@ pub mod upload;
 pub mod track;
 pub mod favorites;
 pub mod playlists;
-pub mod history;
\ No newline at end of file
+pub mod history;
+pub mod jobs;
+pub mod renditions;
+pub mod albums;
+pub mod libraries;
+pub mod versions;
\ No newline at end of file
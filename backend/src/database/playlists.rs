@@ -139,7 +139,12 @@ impl PlaylistsExt for DBClient {
                 COALESCE(ph.played_at, NULL) AS played_at,
                 CASE WHEN uf.track_id IS NOT NULL THEN true ELSE false END as "is_favorite?",
                 COALESCE(ph.duration_played, INTERVAL '0 seconds') AS duration_played,  -- Default to 0 if no playback history
-                CASE WHEN t.user_id = $2 THEN true ELSE false END as is_created_by_user
+                CASE WHEN t.user_id = $2 THEN true ELSE false END as is_created_by_user,
+                t.loudness,
+                t.loudness_range,
+                t.true_peak,
+                t.album_loudness,
+                t.album_true_peak
             FROM playlist_tracks pt
             INNER JOIN tracks t ON pt.track_id = t.id
             LEFT JOIN user_favorites uf ON t.id = uf.track_id AND uf.user_id = $2
@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+use sqlx::{query, query_as};
+use uuid::Uuid;
+
+use crate::{db::DBClient, models::TrackRendition};
+
+// A rendition as produced by the transcode job
+pub struct NewRendition<'a> {
+    pub quality: &'a str,
+    pub codec: &'a str,
+    pub mime_type: &'a str,
+    pub bitrate: Option<i32>,
+    pub storage_key: &'a str,
+    pub file_size: i64,
+}
+
+#[async_trait]
+pub trait RenditionExt {
+    async fn save_rendition(
+        &self,
+        track_id: Uuid,
+        rendition: NewRendition<'_>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_renditions(&self, track_id: Uuid) -> Result<Vec<TrackRendition>, sqlx::Error>;
+}
+
+#[async_trait]
+impl RenditionExt for DBClient {
+    async fn save_rendition(
+        &self,
+        track_id: Uuid,
+        rendition: NewRendition<'_>,
+    ) -> Result<(), sqlx::Error> {
+        // Re-running the job replaces what an earlier run produced
+        query!(
+            r#"
+            INSERT INTO track_renditions (
+                track_id, quality, codec, mime_type, bitrate, storage_key, file_size
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7
+            )
+            ON CONFLICT (track_id, quality) DO UPDATE
+            SET codec = EXCLUDED.codec,
+                mime_type = EXCLUDED.mime_type,
+                bitrate = EXCLUDED.bitrate,
+                storage_key = EXCLUDED.storage_key,
+                file_size = EXCLUDED.file_size,
+                created_at = Now()
+            "#,
+            track_id,
+            rendition.quality,
+            rendition.codec,
+            rendition.mime_type,
+            rendition.bitrate,
+            rendition.storage_key,
+            rendition.file_size,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_renditions(&self, track_id: Uuid) -> Result<Vec<TrackRendition>, sqlx::Error> {
+        query_as!(
+            TrackRendition,
+            r#"
+            SELECT id, track_id, quality, codec, mime_type, bitrate, storage_key, file_size, created_at
+            FROM track_renditions
+            WHERE track_id = $1
+            "#,
+            track_id
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -1,10 +1,144 @@
 use async_trait::async_trait;
 
-use crate::{db::DBClient, dtos::TrackDto};
+use crate::{
+    audio::loudness::Loudness,
+    db::DBClient,
+    dtos::TrackDto,
+    models::{Track, TrackDetails},
+};
+
+// A track a new upload may be a copy of
+pub struct FingerprintCandidate {
+    pub id: uuid::Uuid,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub content_hash: Option<String>,
+    pub fingerprint: Vec<u32>,
+}
+
+// Files a deleted track leaves behind that nothing else refers to anymore
+pub struct DeletedTrack {
+    // Originals and everything derived from them. An upload may take one up
+    // again before they're deleted, check under `lock_content_hash` first.
+    pub content_hashes: Vec<String>,
+    // Audio of tracks uploaded before content addressing
+    pub legacy_key: Option<String>,
+    pub thumbnail_name: Option<String>,
+    // Replacements of the track that were still being uploaded
+    pub upload_ids: Vec<uuid::Uuid>,
+}
+
+// Held while audio is stored or deleted, so a delete can't take away a stored
+// original an upload just decided to reuse. Released when dropped.
+pub struct ContentHashLock {
+    tx: sqlx::Transaction<'static, sqlx::Postgres>,
+    content_hash: String,
+}
+
+impl ContentHashLock {
+    // Whether any track or kept version still has the audio
+    pub async fn is_used(&mut self) -> Result<bool, sqlx::Error> {
+        let used = sqlx::query_scalar!(
+            r#"
+            SELECT
+                EXISTS (SELECT 1 FROM tracks WHERE content_hash = $1)
+                OR EXISTS (SELECT 1 FROM track_versions WHERE content_hash = $1)
+                AS "used!"
+            "#,
+            self.content_hash
+        )
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        Ok(used)
+    }
+
+    // Makes the track refer to the audio before the lock goes, a delete
+    // waiting on it then sees the audio is used
+    pub async fn link_track(mut self, track_id: uuid::Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE tracks SET content_hash = $2 WHERE id = $1
+            "#,
+            track_id,
+            self.content_hash
+        )
+        .execute(&mut *self.tx)
+        .await?;
+
+        self.tx.commit().await
+    }
+
+    pub async fn release(self) -> Result<(), sqlx::Error> {
+        self.tx.commit().await
+    }
+}
 
 #[async_trait]
 pub trait TrackExt {
     async fn get_random_tracks(&self, user_id: uuid::Uuid) -> Result<Vec<TrackDto>, sqlx::Error>;
+
+    async fn get_track(&self, track_id: uuid::Uuid) -> Result<Option<Track>, sqlx::Error>;
+
+    // The track as listings show it to the user
+    async fn get_track_dto(
+        &self,
+        track_id: uuid::Uuid,
+        user_id: uuid::Uuid,
+    ) -> Result<Option<TrackDto>, sqlx::Error>;
+
+    // Stores the track's measurements and refreshes the album's, which are
+    // shared by every track of the owner with the same album name
+    async fn save_loudness(
+        &self,
+        track_id: uuid::Uuid,
+        loudness: &Loudness,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn save_fingerprint(
+        &self,
+        track_id: uuid::Uuid,
+        fingerprint: &[u32],
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_fingerprint(&self, track_id: uuid::Uuid) -> Result<Option<Vec<u32>>, sqlx::Error>;
+
+    // Earlier tracks of about the same length the track's owner can see, that
+    // aren't copies themselves
+    async fn get_fingerprint_candidates(
+        &self,
+        track_id: uuid::Uuid,
+    ) -> Result<Vec<FingerprintCandidate>, sqlx::Error>;
+
+    async fn set_duplicate_of(
+        &self,
+        track_id: uuid::Uuid,
+        duplicate_of: Option<uuid::Uuid>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn lock_content_hash(&self, content_hash: &str) -> Result<ContentHashLock, sqlx::Error>;
+
+    async fn update_track_details(
+        &self,
+        track_id: uuid::Uuid,
+        details: &TrackDetails,
+    ) -> Result<(), sqlx::Error>;
+
+    // Tracks and albums can share a cover, it's only deleted once unused
+    async fn is_thumbnail_used(&self, thumbnail_name: &str) -> Result<bool, sqlx::Error>;
+
+    // Deletes the track with everything that points at it. Audio is shared by
+    // content, so only what no other track or version still has is returned.
+    async fn delete_track(&self, track_id: uuid::Uuid) -> Result<DeletedTrack, sqlx::Error>;
+
+    // Swaps the user's copy for the existing track in their favorites and
+    // playlists, then deletes the copy like `delete_track` does
+    async fn link_duplicate(
+        &self,
+        track_id: uuid::Uuid,
+        existing_id: uuid::Uuid,
+        user_id: uuid::Uuid,
+    ) -> Result<DeletedTrack, sqlx::Error>;
 }
 
 #[async_trait]
@@ -24,13 +158,26 @@ impl TrackExt for DBClient {
                 COALESCE(ph.played_at, NULL) AS played_at,
                 CASE WHEN uf.id IS NOT NULL THEN true ELSE false END as is_favorite,
                 COALESCE(ph.duration_played, INTERVAL '0 seconds') AS duration_played,  -- Default to 0 if no playback history
-                CASE WHEN t.user_id = $1 THEN true ELSE false END as is_created_by_user
+                CASE WHEN t.user_id = $1 THEN true ELSE false END as is_created_by_user,
+                t.loudness,
+                t.loudness_range,
+                t.true_peak,
+                t.album_loudness,
+                t.album_true_peak
             FROM tracks t
             LEFT JOIN user_favorites uf 
                 ON t.id = uf.track_id AND uf.user_id = $1
             LEFT JOIN playback_history ph 
                 ON t.id = ph.track_id AND ph.user_id = $1 -- Join to get duration_played
             WHERE t.upload_status = 'complete'
+                AND (t.visibility = 'public' OR t.user_id = $1)
+                -- Copies are left out when the original shows up instead
+                AND NOT EXISTS (
+                    SELECT 1 FROM tracks o
+                    WHERE o.id = t.duplicate_of
+                        AND o.upload_status = 'complete'
+                        AND (o.visibility = 'public' OR o.user_id = $1)
+                )
             ORDER BY RANDOM()
             LIMIT 20
             "#,
@@ -41,4 +188,482 @@ impl TrackExt for DBClient {
 
         Ok(tracks)
     }
+
+    async fn get_track(&self, track_id: uuid::Uuid) -> Result<Option<Track>, sqlx::Error> {
+        let track = sqlx::query_as!(
+            Track,
+            r#"
+            SELECT 
+                id,
+                user_id,
+                title,
+                artist,
+                duration,
+                file_name,
+                upload_status,
+                thumbnail_name,
+                container,
+                codec,
+                mime_type,
+                visibility,
+                content_hash,
+                album,
+                album_artist,
+                track_number,
+                disc_number,
+                release_year,
+                genre,
+                isrc,
+                lyrics,
+                failure_reason,
+                duplicate_of,
+                preview_start,
+                description,
+                created_at,
+                updated_at
+            FROM tracks
+            WHERE id = $1
+            "#,
+            track_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(track)
+    }
+
+    async fn get_track_dto(
+        &self,
+        track_id: uuid::Uuid,
+        user_id: uuid::Uuid,
+    ) -> Result<Option<TrackDto>, sqlx::Error> {
+        let track = sqlx::query_as!(
+            TrackDto,
+            r#"
+            SELECT 
+                t.id,
+                t.title,
+                t.artist,
+                t.upload_status,
+                t.duration,
+                t.file_name,
+                t.thumbnail_name,
+                COALESCE(ph.played_at, NULL) AS played_at,
+                CASE WHEN uf.id IS NOT NULL THEN true ELSE false END as is_favorite,
+                COALESCE(ph.duration_played, INTERVAL '0 seconds') AS duration_played,
+                CASE WHEN t.user_id = $2 THEN true ELSE false END as is_created_by_user,
+                t.loudness,
+                t.loudness_range,
+                t.true_peak,
+                t.album_loudness,
+                t.album_true_peak
+            FROM tracks t
+            LEFT JOIN user_favorites uf 
+                ON t.id = uf.track_id AND uf.user_id = $2
+            LEFT JOIN playback_history ph 
+                ON t.id = ph.track_id AND ph.user_id = $2
+            WHERE t.id = $1
+            "#,
+            track_id,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(track)
+    }
+
+    async fn save_loudness(
+        &self,
+        track_id: uuid::Uuid,
+        loudness: &Loudness,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE tracks
+            SET loudness = $2, loudness_range = $3, true_peak = $4, updated_at = Now()
+            WHERE id = $1
+            "#,
+            track_id,
+            loudness.integrated,
+            loudness.range,
+            loudness.true_peak,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Energy average of the tracks weighted by their duration, close enough
+        // to gating the whole album at once
+        sqlx::query!(
+            r#"
+            UPDATE tracks t
+            SET album_loudness = a.loudness, album_true_peak = a.true_peak
+            FROM (
+                SELECT
+                    user_id,
+                    album,
+                    10 * LOG(
+                        SUM(EXTRACT(EPOCH FROM duration) * POWER(10, loudness / 10))
+                            / SUM(EXTRACT(EPOCH FROM duration))
+                    ) AS loudness,
+                    MAX(true_peak) AS true_peak
+                FROM tracks
+                WHERE (user_id, album) = (SELECT user_id, album FROM tracks WHERE id = $1)
+                    AND upload_status = 'complete'
+                    AND loudness IS NOT NULL
+                    AND duration > INTERVAL '0'
+                GROUP BY user_id, album
+            ) a
+            WHERE t.user_id = a.user_id AND t.album = a.album
+            "#,
+            track_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn save_fingerprint(
+        &self,
+        track_id: uuid::Uuid,
+        fingerprint: &[u32],
+    ) -> Result<(), sqlx::Error> {
+        // Postgres has no unsigned integers, the bits are stored as they are
+        let fingerprint: Vec<i32> = fingerprint.iter().map(|word| *word as i32).collect();
+
+        sqlx::query!(
+            r#"
+            UPDATE tracks
+            SET fingerprint = $2, updated_at = Now()
+            WHERE id = $1
+            "#,
+            track_id,
+            &fingerprint,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_fingerprint(&self, track_id: uuid::Uuid) -> Result<Option<Vec<u32>>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT fingerprint FROM tracks WHERE id = $1
+            "#,
+            track_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row
+            .and_then(|row| row.fingerprint)
+            .map(|fingerprint| fingerprint.into_iter().map(|word| word as u32).collect()))
+    }
+
+    async fn get_fingerprint_candidates(
+        &self,
+        track_id: uuid::Uuid,
+    ) -> Result<Vec<FingerprintCandidate>, sqlx::Error> {
+        // Copies rarely differ by more than some silence at either end
+        let rows = sqlx::query!(
+            r#"
+            SELECT c.id, c.title, c.artist, c.content_hash, c.fingerprint AS "fingerprint!"
+            FROM tracks t
+            JOIN tracks c ON c.id <> t.id
+            WHERE t.id = $1
+                AND c.upload_status = 'complete'
+                AND c.fingerprint IS NOT NULL
+                AND c.duplicate_of IS NULL
+                AND (c.visibility = 'public' OR c.user_id = t.user_id)
+                AND (c.created_at, c.id) < (t.created_at, t.id)
+                AND ABS(EXTRACT(EPOCH FROM c.duration - t.duration)) <= 10
+            ORDER BY c.created_at, c.id
+            "#,
+            track_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| FingerprintCandidate {
+                id: row.id,
+                title: row.title,
+                artist: row.artist,
+                content_hash: row.content_hash,
+                fingerprint: row.fingerprint.into_iter().map(|word| word as u32).collect(),
+            })
+            .collect())
+    }
+
+    async fn set_duplicate_of(
+        &self,
+        track_id: uuid::Uuid,
+        duplicate_of: Option<uuid::Uuid>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Links always go straight to the original. Tracks fingerprinted out of
+        // order (backfills) can find a copy before the copy knows it is one.
+        sqlx::query!(
+            r#"
+            UPDATE tracks
+            SET duplicate_of = COALESCE((SELECT duplicate_of FROM tracks WHERE id = $2), $2),
+                updated_at = Now()
+            WHERE id = $1
+            "#,
+            track_id,
+            duplicate_of,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE tracks
+            SET duplicate_of = COALESCE((SELECT duplicate_of FROM tracks WHERE id = $1), $1),
+                updated_at = Now()
+            WHERE duplicate_of = $1
+            "#,
+            track_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn link_duplicate(
+        &self,
+        track_id: uuid::Uuid,
+        existing_id: uuid::Uuid,
+        user_id: uuid::Uuid,
+    ) -> Result<DeletedTrack, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_favorites (user_id, track_id)
+            SELECT $1, $2
+            WHERE NOT EXISTS (
+                SELECT 1 FROM user_favorites WHERE user_id = $1 AND track_id = $2
+            )
+            "#,
+            user_id,
+            existing_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // The existing track takes the copy's place, the copy's own entries go
+        // with it below. Playlists that already have the track keep theirs.
+        sqlx::query!(
+            r#"
+            INSERT INTO playlist_tracks (playlist_id, track_id, track_order)
+            SELECT playlist_id, $2, track_order
+            FROM playlist_tracks
+            WHERE track_id = $1
+            ON CONFLICT (playlist_id, track_id) DO NOTHING
+            "#,
+            track_id,
+            existing_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let deleted = delete_track_in(&mut tx, track_id).await?;
+
+        tx.commit().await?;
+
+        Ok(deleted)
+    }
+
+    async fn update_track_details(
+        &self,
+        track_id: uuid::Uuid,
+        details: &TrackDetails,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE tracks
+            SET title = $2,
+                artist = $3,
+                album = $4,
+                genre = $5,
+                description = $6,
+                thumbnail_name = $7,
+                updated_at = Now()
+            WHERE id = $1
+            "#,
+            track_id,
+            details.title,
+            details.artist,
+            details.album,
+            details.genre,
+            details.description,
+            details.thumbnail_name,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn is_thumbnail_used(&self, thumbnail_name: &str) -> Result<bool, sqlx::Error> {
+        let used = sqlx::query_scalar!(
+            r#"
+            SELECT
+                EXISTS (SELECT 1 FROM tracks WHERE thumbnail_name = $1)
+                OR EXISTS (SELECT 1 FROM albums WHERE thumbnail_name = $1)
+                AS "used!"
+            "#,
+            thumbnail_name
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(used)
+    }
+
+    async fn lock_content_hash(&self, content_hash: &str) -> Result<ContentHashLock, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            SELECT pg_advisory_xact_lock(hashtextextended($1, 0))
+            "#,
+            content_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(ContentHashLock {
+            tx,
+            content_hash: content_hash.to_string(),
+        })
+    }
+
+    async fn delete_track(&self, track_id: uuid::Uuid) -> Result<DeletedTrack, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = delete_track_in(&mut tx, track_id).await?;
+        tx.commit().await?;
+
+        Ok(deleted)
+    }
+}
+
+// Deletes the track as part of the caller's transaction and works out which of
+// its files nothing else refers to anymore
+async fn delete_track_in(
+    conn: &mut sqlx::PgConnection,
+    track_id: uuid::Uuid,
+) -> Result<DeletedTrack, sqlx::Error> {
+    let track = sqlx::query!(
+        r#"
+        SELECT content_hash, file_name, upload_status, thumbnail_name
+        FROM tracks
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        track_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let mut content_hashes = sqlx::query_scalar!(
+        r#"
+        SELECT content_hash FROM track_versions WHERE track_id = $1
+        "#,
+        track_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    content_hashes.extend(track.content_hash.clone());
+
+    // They'd go with the track anyway, but their chunks need the ids
+    let upload_ids = sqlx::query_scalar!(
+        r#"
+        DELETE FROM tracks WHERE replaces = $1 RETURNING id
+        "#,
+        track_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    // Favorites, playlists, history, renditions and versions cascade
+    sqlx::query!(
+        r#"
+        DELETE FROM tracks WHERE id = $1
+        "#,
+        track_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let content_hashes = sqlx::query_scalar!(
+        r#"
+        SELECT DISTINCT h AS "content_hash!"
+        FROM UNNEST($1::TEXT[]) h
+        WHERE NOT EXISTS (SELECT 1 FROM tracks WHERE content_hash = h)
+            AND NOT EXISTS (SELECT 1 FROM track_versions WHERE content_hash = h)
+        "#,
+        &content_hashes
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    // Before content addressing the audio was stored under its file name
+    let legacy_file_name = match (&track.content_hash, track.upload_status.as_deref()) {
+        (None, Some("complete")) => track.file_name,
+        _ => None,
+    };
+    let legacy_key = match legacy_file_name {
+        Some(file_name) => {
+            let used = sqlx::query_scalar!(
+                r#"
+                SELECT EXISTS (
+                    SELECT 1 FROM tracks
+                    WHERE file_name = $1 AND content_hash IS NULL
+                        AND upload_status = 'complete'
+                ) AS "used!"
+                "#,
+                file_name
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+
+            (!used).then(|| format!("uploads/{}", file_name))
+        }
+        None => None,
+    };
+
+    let thumbnail_name = match track.thumbnail_name {
+        Some(thumbnail_name) => {
+            let used = sqlx::query_scalar!(
+                r#"
+                SELECT
+                    EXISTS (SELECT 1 FROM tracks WHERE thumbnail_name = $1)
+                    OR EXISTS (SELECT 1 FROM albums WHERE thumbnail_name = $1)
+                    AS "used!"
+                "#,
+                thumbnail_name
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+
+            (!used).then_some(thumbnail_name)
+        }
+        None => None,
+    };
+
+    Ok(DeletedTrack {
+        content_hashes,
+        legacy_key,
+        thumbnail_name,
+        upload_ids,
+    })
 }
@@ -2,7 +2,55 @@ use async_trait::async_trait;
 use sqlx::{postgres::types::PgInterval, query, query_as};
 use uuid::Uuid;
 
-use crate::{db::DBClient, dtos::IncompleteTrackInfo, models::AudioFile};
+use crate::{
+    audio::probe::AudioInfo,
+    db::{interval, DBClient},
+    dtos::IncompleteTrackInfo,
+    models::{AudioFile, TrackMetadata},
+};
+
+// A chunk that has been verified and written to storage
+pub struct ReceivedChunk<'a> {
+    pub number: i32,
+    pub path: &'a str,
+    pub hash: &'a str,
+    pub size: i64,
+}
+
+// What an account holds against its quotas
+pub struct Usage {
+    // Originals of the account's tracks
+    pub stored_bytes: i64,
+    // Received so far, or announced up front by tus uploads
+    pub pending_bytes: i64,
+    // Every track, unfinished uploads included
+    pub track_count: i64,
+    // Overrides of the configured quotas
+    pub storage_quota: Option<i64>,
+    pub track_quota: Option<i32>,
+}
+
+// A tus upload's audio_files row, held from the offset check until the chunk
+// is recorded so PATCHes racing for the same offset go one at a time
+pub struct LockedTusUpload {
+    tx: sqlx::Transaction<'static, sqlx::Postgres>,
+    pub track_id: Uuid,
+    pub upload_length: i64,
+    pub total_chunks: i32,
+    pub current_chunk: i32,
+    pub offset: i64,
+}
+
+impl LockedTusUpload {
+    pub async fn append(mut self, chunk: ReceivedChunk<'_>) -> Result<(), sqlx::Error> {
+        record_chunk(&mut self.tx, self.track_id, self.total_chunks, &chunk, None).await?;
+        self.tx.commit().await
+    }
+
+    pub async fn release(self) -> Result<(), sqlx::Error> {
+        self.tx.rollback().await
+    }
+}
 
 #[async_trait]
 pub trait UploadExt {
@@ -12,22 +60,78 @@ pub trait UploadExt {
         &self,
         track_id: Uuid,
         total_chunks: i32,
-        uploaded_chunks: i32,
-        current_chunk: i32,
-        chunk_path: &String,
+        chunk: ReceivedChunk<'_>,
+        file_hash: Option<&str>,
     ) -> Result<(), sqlx::Error>;
 
     async fn get_audio_file(&self, track_id: Uuid) -> Result<Option<AudioFile>, sqlx::Error>;
 
+    async fn get_uploaded_chunks(&self, track_id: Uuid) -> Result<Vec<i32>, sqlx::Error>;
+
+    async fn get_chunk_size(
+        &self,
+        track_id: Uuid,
+        chunk_number: i32,
+    ) -> Result<Option<i64>, sqlx::Error>;
+
+    async fn create_tus_upload(&self, track_id: Uuid, upload_length: i64)
+        -> Result<(), sqlx::Error>;
+
+    async fn get_upload_offset(&self, track_id: Uuid) -> Result<i64, sqlx::Error>;
+
+    async fn lock_tus_upload(&self, track_id: Uuid)
+        -> Result<Option<LockedTusUpload>, sqlx::Error>;
+
+    async fn seal_chunk_count(&self, track_id: Uuid) -> Result<i32, sqlx::Error>;
+
+    async fn delete_incomplete_track(&self, track_id: Uuid) -> Result<bool, sqlx::Error>;
+
+    // Deletes the incomplete uploads nobody has touched for `ttl` and returns
+    // their ids, so their chunks can go too
+    async fn expire_incomplete_uploads(
+        &self,
+        ttl: std::time::Duration,
+    ) -> Result<Vec<Uuid>, sqlx::Error>;
+
+    // The ones among `track_ids` that are still being uploaded
+    async fn get_incomplete_track_ids(&self, track_ids: &[Uuid]) -> Result<Vec<Uuid>, sqlx::Error>;
+
+    async fn claim_assembly(&self, track_id: Uuid) -> Result<bool, sqlx::Error>;
+
+    async fn release_assembly(&self, track_id: Uuid) -> Result<(), sqlx::Error>;
+
     async fn upload_thumbnail(
         &self,
         track_id: Uuid,
-        thumbnail_name: &String,
-        title: &String,
-        artist: &String,
+        thumbnail_name: Option<&str>,
+        metadata: &TrackMetadata,
+        visibility: Option<&str>,
+        preview_start: Option<i32>,
     ) -> Result<(), sqlx::Error>;
 
-    async fn update_status(&self, track_id: Uuid, duration: i64) -> Result<(), sqlx::Error>;
+    async fn update_status(
+        &self,
+        track_id: Uuid,
+        audio_info: &AudioInfo,
+        content_hash: &str,
+        cover_name: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn save_file_size(&self, track_id: Uuid, file_size: i64) -> Result<(), sqlx::Error>;
+
+    // Complete tracks uploaded before file sizes were kept
+    async fn get_unsized_track_ids(&self) -> Result<Vec<Uuid>, sqlx::Error>;
+
+    async fn get_usage(&self, user_id: Uuid) -> Result<Usage, sqlx::Error>;
+
+    async fn reset_upload(&self, track_id: Uuid) -> Result<(), sqlx::Error>;
+
+    async fn mark_failed(
+        &self,
+        track_id: Uuid,
+        reason: &str,
+        content_hash: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
 
     async fn get_incomplete_uploads(
         &self,
@@ -60,100 +164,299 @@ impl UploadExt for DBClient {
         &self,
         track_id: Uuid,
         total_chunks: i32,
-        uploaded_chunks: i32,
-        current_chunk: i32,
-        chunk_path: &String,
+        chunk: ReceivedChunk<'_>,
+        file_hash: Option<&str>,
     ) -> Result<(), sqlx::Error> {
-        let existing_file = query_as!(
+        let mut tx = self.pool.begin().await?;
+        record_chunk(&mut tx, track_id, total_chunks, &chunk, file_hash).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_audio_file(&self, track_id: Uuid) -> Result<Option<AudioFile>, sqlx::Error> {
+        let audio_file = query_as!(
             AudioFile,
             r#"
             SELECT * FROM audio_files 
             WHERE track_id = $1
             "#,
+            track_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(audio_file)
+    }
+
+    async fn get_uploaded_chunks(&self, track_id: Uuid) -> Result<Vec<i32>, sqlx::Error> {
+        let chunks = query!(
+            r#"
+            SELECT chunk_number FROM upload_chunks
+            WHERE track_id = $1
+            ORDER BY chunk_number
+            "#,
+            track_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(chunks.into_iter().map(|chunk| chunk.chunk_number).collect())
+    }
+
+    async fn get_chunk_size(
+        &self,
+        track_id: Uuid,
+        chunk_number: i32,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let chunk_size = sqlx::query_scalar!(
+            r#"
+            SELECT chunk_size FROM upload_chunks
+            WHERE track_id = $1 AND chunk_number = $2
+            "#,
             track_id,
+            chunk_number
         )
         .fetch_optional(&self.pool)
         .await?;
 
-        if let Some(mut audio_file) = existing_file {
-            audio_file.uploaded_chunks = uploaded_chunks as i32;
-            audio_file.current_chunk = current_chunk as i32;
-
-            query!(
-                r#"
-                UPDATE audio_files
-                SET uploaded_chunks = $1,
-                    current_chunk = $2,
-                    chunk_path = $3,
-                    upload_status = CASE
-                        WHEN $2::INTEGER = $4::INTEGER THEN 'complete'
-                        ELSE 'incomplete'
-                    END,
-                    updated_at = Now()
-                WHERE id = $5
-                "#,
-                audio_file.uploaded_chunks,
-                audio_file.current_chunk,
-                chunk_path,
-                total_chunks as i32,
-                audio_file.id,
-            )
-            .execute(&self.pool)
-            .await?;
-        } else {
-            query!(
-                r#"
-                INSERT INTO audio_files (track_id, total_chunks, uploaded_chunks, current_chunk, chunk_path, upload_status)
-                VALUES ($1, $2, $3, $4, $5, 'incomplete')
-                "#,
-                track_id,
-                total_chunks,
-                uploaded_chunks,
-                current_chunk,
-                chunk_path
-            )
-            .execute(&self.pool)
-            .await?;
-        }
+        Ok(chunk_size)
+    }
+
+    // tus uploads don't know their chunk count up front, it is fixed by seal_chunk_count
+    async fn create_tus_upload(
+        &self,
+        track_id: Uuid,
+        upload_length: i64,
+    ) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            INSERT INTO audio_files (track_id, total_chunks, uploaded_chunks, current_chunk, chunk_path, upload_status, upload_length)
+            VALUES ($1, 0, 0, -1, '', 'incomplete', $2)
+            "#,
+            track_id,
+            upload_length
+        )
+        .execute(&self.pool)
+        .await?;
 
         Ok(())
     }
 
-    async fn get_audio_file(&self, track_id: Uuid) -> Result<Option<AudioFile>, sqlx::Error> {
-        let audio_file = query_as!(
-            AudioFile,
+    async fn get_upload_offset(&self, track_id: Uuid) -> Result<i64, sqlx::Error> {
+        let offset = query!(
             r#"
-            SELECT * FROM audio_files 
+            SELECT COALESCE(SUM(chunk_size), 0)::BIGINT AS "offset!"
+            FROM upload_chunks
+            WHERE track_id = $1
+            "#,
+            track_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(offset.offset)
+    }
+
+    async fn lock_tus_upload(
+        &self,
+        track_id: Uuid,
+    ) -> Result<Option<LockedTusUpload>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(audio_file) = query!(
+            r#"
+            SELECT upload_length AS "upload_length!", total_chunks, current_chunk
+            FROM audio_files
+            WHERE track_id = $1 AND upload_length IS NOT NULL
+            FOR UPDATE
+            "#,
+            track_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let offset = query!(
+            r#"
+            SELECT COALESCE(SUM(chunk_size), 0)::BIGINT AS "offset!"
+            FROM upload_chunks
             WHERE track_id = $1
             "#,
             track_id
         )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Ok(Some(LockedTusUpload {
+            tx,
+            track_id,
+            upload_length: audio_file.upload_length,
+            total_chunks: audio_file.total_chunks,
+            current_chunk: audio_file.current_chunk,
+            offset: offset.offset,
+        }))
+    }
+
+    // Called once all bytes are in, so claim_assembly sees the upload as complete
+    async fn seal_chunk_count(&self, track_id: Uuid) -> Result<i32, sqlx::Error> {
+        let sealed = query!(
+            r#"
+            UPDATE audio_files
+            SET total_chunks = (SELECT COUNT(*) FROM upload_chunks WHERE track_id = $1),
+                updated_at = Now()
+            WHERE track_id = $1
+            RETURNING total_chunks
+            "#,
+            track_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(sealed.total_chunks)
+    }
+
+    // Chunk rows and audio_files go with the track (ON DELETE CASCADE). Uploads
+    // being assembled are about to complete, they're left alone.
+    async fn delete_incomplete_track(&self, track_id: Uuid) -> Result<bool, sqlx::Error> {
+        let deleted = query!(
+            r#"
+            DELETE FROM tracks
+            WHERE id = $1 AND upload_status = 'incomplete'
+                AND NOT EXISTS (
+                    SELECT 1 FROM audio_files
+                    WHERE track_id = $1 AND upload_status = 'assembling'
+                )
+            RETURNING id
+            "#,
+            track_id
+        )
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(audio_file)
+        Ok(deleted.is_some())
+    }
+
+    // Every chunk bumps audio_files, metadata edits bump the track. Assemblies
+    // that never finished (the server went down) expire like the rest.
+    async fn expire_incomplete_uploads(
+        &self,
+        ttl: std::time::Duration,
+    ) -> Result<Vec<Uuid>, sqlx::Error> {
+        let expired = query!(
+            r#"
+            DELETE FROM tracks t
+            WHERE t.upload_status = 'incomplete'
+                AND t.updated_at < Now() - $1::interval
+                AND NOT EXISTS (
+                    SELECT 1 FROM audio_files af
+                    WHERE af.track_id = t.id AND af.updated_at >= Now() - $1::interval
+                )
+            RETURNING t.id
+            "#,
+            interval(ttl)
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(expired.into_iter().map(|track| track.id).collect())
+    }
+
+    async fn get_incomplete_track_ids(&self, track_ids: &[Uuid]) -> Result<Vec<Uuid>, sqlx::Error> {
+        let tracks = query!(
+            r#"
+            SELECT id FROM tracks
+            WHERE id = ANY($1) AND upload_status = 'incomplete'
+            "#,
+            track_ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(tracks.into_iter().map(|track| track.id).collect())
+    }
+
+    // Only one request gets to assemble a track, even when the last chunks
+    // arrive in parallel.
+    async fn claim_assembly(&self, track_id: Uuid) -> Result<bool, sqlx::Error> {
+        let claimed = query!(
+            r#"
+            UPDATE audio_files
+            SET upload_status = 'assembling',
+                uploaded_chunks = (SELECT COUNT(*) FROM upload_chunks WHERE track_id = $1),
+                updated_at = Now()
+            WHERE track_id = $1
+              AND upload_status = 'incomplete'
+              AND (SELECT COUNT(*) FROM upload_chunks WHERE track_id = $1) >= total_chunks
+            RETURNING id
+            "#,
+            track_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(claimed.is_some())
+    }
+
+    async fn release_assembly(&self, track_id: Uuid) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            UPDATE audio_files
+            SET upload_status = 'incomplete',
+                updated_at = Now()
+            WHERE track_id = $1 AND upload_status = 'assembling'
+            "#,
+            track_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
     }
 
     async fn upload_thumbnail(
         &self,
         track_id: Uuid,
-        thumbnail_name: &String,
-        title: &String,
-        artist: &String,
+        thumbnail_name: Option<&str>,
+        metadata: &TrackMetadata,
+        visibility: Option<&str>,
+        preview_start: Option<i32>,
     ) -> Result<(), sqlx::Error> {
         query!(
             r#"
             UPDATE tracks
-            SET title = $1,
-                artist = $2,
-                thumbnail_name = $3,
+            SET title = COALESCE($2, title),
+                artist = COALESCE($3, artist),
+                album = COALESCE($4, album),
+                album_artist = COALESCE($5, album_artist),
+                track_number = COALESCE($6, track_number),
+                disc_number = COALESCE($7, disc_number),
+                release_year = COALESCE($8, release_year),
+                genre = COALESCE($9, genre),
+                isrc = COALESCE($10, isrc),
+                lyrics = COALESCE($11, lyrics),
+                thumbnail_name = COALESCE($12, thumbnail_name),
+                visibility = COALESCE($13, visibility),
+                preview_start = COALESCE($14, preview_start),
                 updated_at = Now()
-            WHERE id = $4
+            WHERE id = $1
             "#,
-            title,
-            artist,
+            track_id,
+            metadata.title,
+            metadata.artist,
+            metadata.album,
+            metadata.album_artist,
+            metadata.track_number,
+            metadata.disc_number,
+            metadata.year,
+            metadata.genre,
+            metadata.isrc,
+            metadata.lyrics,
             thumbnail_name,
-            track_id
+            visibility,
+            preview_start
         )
         .execute(&self.pool)
         .await?;
@@ -161,26 +464,190 @@ impl UploadExt for DBClient {
         Ok(())
     }
 
-    async fn update_status(&self, track_id: Uuid, duration: i64) -> Result<(), sqlx::Error> {
+    async fn update_status(
+        &self,
+        track_id: Uuid,
+        audio_info: &AudioInfo,
+        content_hash: &str,
+        cover_name: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
         let pg_duration = PgInterval {
             days: 0,
             months: 0,
-            microseconds: duration * 1_000_000,
+            microseconds: audio_info.duration.num_microseconds().unwrap_or(i64::MAX),
         };
+        let tags = &audio_info.metadata;
+
+        // Tags only fill in what the user hasn't already typed into the form
         query!(
             r#"
             UPDATE tracks
             SET upload_status = 'complete',
                 duration = $2,
+                container = $3,
+                codec = $4,
+                mime_type = $5,
+                content_hash = $6,
+                title = COALESCE(title, $7),
+                artist = COALESCE(artist, $8),
+                album = COALESCE(album, $9),
+                album_artist = COALESCE(album_artist, $10),
+                track_number = COALESCE(track_number, $11),
+                disc_number = COALESCE(disc_number, $12),
+                release_year = COALESCE(release_year, $13),
+                genre = COALESCE(genre, $14),
+                isrc = COALESCE(isrc, $15),
+                lyrics = COALESCE(lyrics, $16),
+                thumbnail_name = COALESCE(thumbnail_name, $17),
+                failure_reason = NULL,
+                updated_at = Now()
+            WHERE id = $1
+            "#,
+            track_id,
+            pg_duration,
+            audio_info.container.as_str(),
+            audio_info.codec,
+            audio_info.mime_type,
+            content_hash,
+            tags.title,
+            tags.artist,
+            tags.album,
+            tags.album_artist,
+            tags.track_number,
+            tags.disc_number,
+            tags.year,
+            tags.genre,
+            tags.isrc,
+            tags.lyrics,
+            cover_name,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        query!(
+            r#"
+                DELETE FROM audio_files WHERE track_id = $1;
+            "#,
+            track_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        query!(
+            r#"
+                DELETE FROM upload_chunks WHERE track_id = $1;
+            "#,
+            track_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // The assembled file is kept (content_hash) so the track can be processed again later
+    async fn mark_failed(
+        &self,
+        track_id: Uuid,
+        reason: &str,
+        content_hash: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            UPDATE tracks
+            SET upload_status = 'failed',
+                failure_reason = $2,
+                content_hash = $3,
                 updated_at = Now()
             WHERE id = $1
             "#,
             track_id,
-            pg_duration
+            reason,
+            content_hash,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.reset_upload(track_id).await
+    }
+
+    async fn save_file_size(&self, track_id: Uuid, file_size: i64) -> Result<(), sqlx::Error> {
+        query!(
+            r#"
+            UPDATE tracks
+            SET file_size = $2, updated_at = Now()
+            WHERE id = $1
+            "#,
+            track_id,
+            file_size,
         )
         .execute(&self.pool)
         .await?;
 
+        Ok(())
+    }
+
+    async fn get_unsized_track_ids(&self) -> Result<Vec<Uuid>, sqlx::Error> {
+        let tracks = query!(
+            r#"
+            SELECT id FROM tracks
+            WHERE file_size IS NULL AND upload_status = 'complete'
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(tracks.into_iter().map(|track| track.id).collect())
+    }
+
+    async fn get_usage(&self, user_id: Uuid) -> Result<Usage, sqlx::Error> {
+        let usage = query_as!(
+            Usage,
+            r#"
+            SELECT
+                (
+                    (SELECT COALESCE(SUM(file_size), 0) FROM tracks WHERE user_id = $1)
+                    -- Earlier versions kept for rollback, once per audio
+                    + (
+                        SELECT COALESCE(SUM(file_size), 0)
+                        FROM (
+                            SELECT DISTINCT ON (v.content_hash) v.file_size
+                            FROM track_versions v
+                            JOIN tracks t ON t.id = v.track_id
+                            WHERE t.user_id = $1
+                                AND NOT EXISTS (
+                                    SELECT 1 FROM tracks c
+                                    WHERE c.user_id = $1 AND c.content_hash = v.content_hash
+                                )
+                        ) kept
+                    )
+                )::BIGINT AS "stored_bytes!",
+                (
+                    SELECT COALESCE(SUM(GREATEST(
+                        af.upload_length,
+                        (SELECT SUM(chunk_size) FROM upload_chunks c WHERE c.track_id = t.id)
+                    )), 0)
+                    FROM tracks t
+                    JOIN audio_files af ON af.track_id = t.id
+                    WHERE t.user_id = $1 AND t.upload_status = 'incomplete'
+                )::BIGINT AS "pending_bytes!",
+                -- Replacement audio on its way isn't a track of its own
+                (SELECT COUNT(*) FROM tracks WHERE user_id = $1 AND replaces IS NULL)
+                    AS "track_count!",
+                u.storage_quota,
+                u.track_quota
+            FROM users u
+            WHERE u.id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(usage)
+    }
+
+    async fn reset_upload(&self, track_id: Uuid) -> Result<(), sqlx::Error> {
         query!(
             r#"
                 DELETE FROM audio_files WHERE track_id = $1;
@@ -190,9 +657,19 @@ impl UploadExt for DBClient {
         .execute(&self.pool)
         .await?;
 
+        query!(
+            r#"
+                DELETE FROM upload_chunks WHERE track_id = $1;
+            "#,
+            track_id
+        )
+        .execute(&self.pool)
+        .await?;
+
         Ok(())
     }
 
+    // tus clients resume on their own, the web resume flow only lists multipart uploads
     async fn get_incomplete_uploads(
         &self,
         user_id: Uuid,
@@ -216,6 +693,7 @@ impl UploadExt for DBClient {
                 WHERE 
                     t.user_id = $1 
                     AND t.upload_status = 'incomplete'
+                    AND af.upload_length IS NULL
             "#,
             user_id
         )
@@ -225,3 +703,50 @@ impl UploadExt for DBClient {
         Ok(uploads)
     }
 }
+
+async fn record_chunk(
+    conn: &mut sqlx::PgConnection,
+    track_id: Uuid,
+    total_chunks: i32,
+    chunk: &ReceivedChunk<'_>,
+    file_hash: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    // Receiving the same chunk twice (a retry) just overwrites it
+    query!(
+        r#"
+        INSERT INTO upload_chunks (track_id, chunk_number, chunk_hash, chunk_size)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (track_id, chunk_number)
+        DO UPDATE SET chunk_hash = EXCLUDED.chunk_hash,
+            chunk_size = EXCLUDED.chunk_size,
+            created_at = Now()
+        "#,
+        track_id,
+        chunk.number,
+        chunk.hash,
+        chunk.size,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    query!(
+        r#"
+        INSERT INTO audio_files (track_id, total_chunks, uploaded_chunks, current_chunk, chunk_path, upload_status, file_hash)
+        VALUES ($1, $2, (SELECT COUNT(*) FROM upload_chunks WHERE track_id = $1), $3, $4, 'incomplete', $5)
+        ON CONFLICT (track_id)
+        DO UPDATE SET uploaded_chunks = (SELECT COUNT(*) FROM upload_chunks WHERE track_id = $1),
+            current_chunk = $3,
+            chunk_path = $4,
+            updated_at = Now()
+        "#,
+        track_id,
+        total_chunks,
+        chunk.number,
+        chunk.path,
+        file_hash,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
@@ -30,6 +30,18 @@ pub trait UserExt {
         user_id: Uuid,
         new_password_hash: String
     ) -> Result<User, sqlx::Error>;
+
+    async fn update_stream_quality(
+        &self,
+        user_id: Uuid,
+        stream_quality: &str,
+    ) -> Result<User, sqlx::Error>;
+
+    async fn update_role(
+        &self,
+        user_id: Uuid,
+        role: &str,
+    ) -> Result<User, sqlx::Error>;
 }
 
 #[async_trait]
@@ -48,7 +60,9 @@ impl UserExt for DBClient {
                 email, 
                 password_hash,  
                 created_at, 
-                updated_at 
+                updated_at,
+                stream_quality,
+                role
             FROM users 
             WHERE 
                 ($1::uuid IS NULL OR id = $1) AND
@@ -79,7 +93,7 @@ impl UserExt for DBClient {
             r#"
             INSERT INTO users (username, email, password_hash) 
             VALUES ($1, $2, $3) 
-            RETURNING id, username, email, password_hash, created_at, updated_at
+            RETURNING id, username, email, password_hash, created_at, updated_at, stream_quality, role
             "#,
             username.into(),
             email.into(),
@@ -101,7 +115,7 @@ impl UserExt for DBClient {
             UPDATE users
             SET username = $1, updated_at = Now()
             WHERE id = $2
-            RETURNING id, username, email, password_hash, created_at, updated_at
+            RETURNING id, username, email, password_hash, created_at, updated_at, stream_quality, role
             "#,
             username.into(),
             user_id
@@ -122,7 +136,7 @@ impl UserExt for DBClient {
             UPDATE users
             SET password_hash = $1, updated_at = Now()
             WHERE id = $2
-            RETURNING id, username, email, password_hash, created_at, updated_at
+            RETURNING id, username, email, password_hash, created_at, updated_at, stream_quality, role
             "#,
             new_password_hash,
             user_id
@@ -131,4 +145,45 @@ impl UserExt for DBClient {
 
         Ok(user)
     }
+
+    async fn update_stream_quality(
+        &self,
+        user_id: Uuid,
+        stream_quality: &str,
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET stream_quality = $1, updated_at = Now()
+            WHERE id = $2
+            RETURNING id, username, email, password_hash, created_at, updated_at, stream_quality, role
+            "#,
+            stream_quality,
+            user_id
+        ).fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+    async fn update_role(
+        &self,
+        user_id: Uuid,
+        role: &str,
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET role = $1, updated_at = Now()
+            WHERE id = $2
+            RETURNING id, username, email, password_hash, created_at, updated_at, stream_quality, role
+            "#,
+            role,
+            user_id
+        ).fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
 }
\ No newline at end of file
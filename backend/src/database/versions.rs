@@ -0,0 +1,242 @@
+use async_trait::async_trait;
+use sqlx::{query, query_as, query_scalar};
+use uuid::Uuid;
+
+use crate::{db::DBClient, dtos::TrackVersionDto};
+
+#[async_trait]
+pub trait VersionExt {
+    // An upload whose audio replaces the track's once it's assembled
+    async fn create_replacement(
+        &self,
+        user_id: Uuid,
+        file_name: &str,
+        track_id: Uuid,
+    ) -> Result<Uuid, sqlx::Error>;
+
+    async fn get_replaced_track(&self, upload_id: Uuid) -> Result<Option<Uuid>, sqlx::Error>;
+
+    // Records the track's current audio as its next version
+    async fn save_track_version(&self, track_id: Uuid) -> Result<i32, sqlx::Error>;
+
+    async fn get_track_versions(&self, track_id: Uuid)
+        -> Result<Vec<TrackVersionDto>, sqlx::Error>;
+
+    // Where the version's audio is stored, kept out of the listing above
+    async fn get_version_content_hash(
+        &self,
+        track_id: Uuid,
+        version: i32,
+    ) -> Result<Option<String>, sqlx::Error>;
+
+    // Plays an earlier version again, dropping what was worked out from the
+    // audio it takes over from. Versions stay as they are, only the current
+    // one changes.
+    async fn restore_track_version(&self, track_id: Uuid, version: i32) -> Result<(), sqlx::Error>;
+
+    // Drops whatever was worked out from the track's old audio, before the
+    // jobs run again for the new one
+    async fn reset_track_audio(&self, track_id: Uuid) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+impl VersionExt for DBClient {
+    async fn create_replacement(
+        &self,
+        user_id: Uuid,
+        file_name: &str,
+        track_id: Uuid,
+    ) -> Result<Uuid, sqlx::Error> {
+        let upload = query!(
+            r#"
+            INSERT INTO tracks (user_id, file_name, replaces)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            "#,
+            user_id,
+            file_name,
+            track_id,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(upload.id)
+    }
+
+    async fn get_replaced_track(&self, upload_id: Uuid) -> Result<Option<Uuid>, sqlx::Error> {
+        let upload = query!(
+            r#"
+            SELECT replaces FROM tracks WHERE id = $1
+            "#,
+            upload_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(upload.and_then(|upload| upload.replaces))
+    }
+
+    async fn save_track_version(&self, track_id: Uuid) -> Result<i32, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let saved = query!(
+            r#"
+            INSERT INTO track_versions (
+                track_id, version, content_hash, container, codec, mime_type, duration, file_size
+            )
+            SELECT
+                id,
+                (SELECT COALESCE(MAX(version), 0) + 1 FROM track_versions WHERE track_id = $1),
+                content_hash, container, codec, mime_type, duration, file_size
+            FROM tracks
+            WHERE id = $1 AND content_hash IS NOT NULL
+            RETURNING version
+            "#,
+            track_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        query!(
+            r#"
+            UPDATE tracks SET audio_version = $2 WHERE id = $1
+            "#,
+            track_id,
+            saved.version,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(saved.version)
+    }
+
+    async fn get_track_versions(
+        &self,
+        track_id: Uuid,
+    ) -> Result<Vec<TrackVersionDto>, sqlx::Error> {
+        let versions = query_as!(
+            TrackVersionDto,
+            r#"
+            SELECT
+                v.version,
+                v.container,
+                v.codec,
+                v.mime_type,
+                v.duration,
+                v.file_size,
+                v.created_at,
+                v.version = t.audio_version IS TRUE AS "is_current!"
+            FROM track_versions v
+            JOIN tracks t ON t.id = v.track_id
+            WHERE v.track_id = $1
+            ORDER BY v.version DESC
+            "#,
+            track_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(versions)
+    }
+
+    async fn get_version_content_hash(
+        &self,
+        track_id: Uuid,
+        version: i32,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let content_hash = query_scalar!(
+            r#"
+            SELECT content_hash FROM track_versions WHERE track_id = $1 AND version = $2
+            "#,
+            track_id,
+            version
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(content_hash)
+    }
+
+    async fn restore_track_version(&self, track_id: Uuid, version: i32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        reset_track_audio_in(&mut tx, track_id).await?;
+
+        query!(
+            r#"
+            UPDATE tracks t
+            SET content_hash = v.content_hash,
+                container = v.container,
+                codec = v.codec,
+                mime_type = v.mime_type,
+                duration = v.duration,
+                file_size = v.file_size,
+                audio_version = v.version,
+                upload_status = 'complete',
+                failure_reason = NULL,
+                updated_at = Now()
+            FROM track_versions v
+            WHERE t.id = $1 AND v.track_id = t.id AND v.version = $2
+            "#,
+            track_id,
+            version,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn reset_track_audio(&self, track_id: Uuid) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        reset_track_audio_in(&mut tx, track_id).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+async fn reset_track_audio_in(
+    conn: &mut sqlx::PgConnection,
+    track_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"
+        UPDATE tracks
+        SET loudness = NULL, loudness_range = NULL, true_peak = NULL,
+            album_loudness = NULL, album_true_peak = NULL,
+            fingerprint = NULL, duplicate_of = NULL,
+            updated_at = Now()
+        WHERE id = $1
+        "#,
+        track_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    // Copies of the old audio aren't copies of the new one
+    query!(
+        r#"
+        UPDATE tracks
+        SET duplicate_of = NULL, updated_at = Now()
+        WHERE duplicate_of = $1
+        "#,
+        track_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    query!(
+        r#"
+        DELETE FROM track_renditions WHERE track_id = $1
+        "#,
+        track_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
@@ -1,10 +1,19 @@
-use sqlx::{Pool, Postgres};
+use sqlx::{postgres::types::PgInterval, Pool, Postgres};
 
 #[derive(Debug, Clone)]
 pub struct DBClient {
     pub pool: Pool<Postgres>,
 }
 
+// For comparing timestamps with `Now() - $1::interval`
+pub fn interval(duration: std::time::Duration) -> PgInterval {
+    PgInterval {
+        months: 0,
+        days: 0,
+        microseconds: duration.as_micros() as i64,
+    }
+}
+
 impl DBClient {
     pub fn new(pool: Pool<Postgres>) -> Self {
         DBClient { pool }
@@ -3,7 +3,10 @@ use serde::{Deserialize, Serialize};
 use validator::{validate_email, Validate, ValidationError};
 use regex::Regex;
 
-use crate::models::{Duration, User};
+use crate::{
+    audio::loudness::replay_gain,
+    models::{Album, Duration, Library, User},
+};
 
 #[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
 pub struct RegisterUserDto {
@@ -80,6 +83,9 @@ pub struct FilterUserDto {
     pub created_at: NaiveDateTime,
     #[serde(rename = "updatedAt")]
     pub updated_at: NaiveDateTime,
+    #[serde(rename = "streamQuality")]
+    pub stream_quality: String,
+    pub role: String,
 }
 
 impl FilterUserDto {
@@ -90,6 +96,8 @@ impl FilterUserDto {
             email: user.email.to_owned(),
             created_at: user.created_at.unwrap(),
             updated_at: user.updated_at.unwrap(),
+            stream_quality: user.stream_quality.to_owned(),
+            role: user.role.to_owned(),
         }
     }
 }
@@ -106,6 +114,28 @@ pub struct UserResponseDto {
 }
 
 
+// Bytes count the originals of complete tracks and what unfinished uploads
+// have sent (or announced, for tus)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsageDto {
+    #[serde(rename = "usedBytes")]
+    pub used_bytes: i64,
+    #[serde(rename = "pendingBytes")]
+    pub pending_bytes: i64,
+    #[serde(rename = "storageQuota")]
+    pub storage_quota: i64,
+    #[serde(rename = "trackCount")]
+    pub track_count: i64,
+    #[serde(rename = "trackQuota")]
+    pub track_quota: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsageResponseDto {
+    pub status: String,
+    pub data: UsageDto,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserLoginResponseDto {
     pub status: String,
@@ -125,6 +155,22 @@ pub struct NameUpdateDto {
     pub name: String,
 }
 
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StreamQualityUpdateDto {
+    pub quality: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StreamQueryDto {
+    pub quality: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct WaveformQueryDto {
+    // `json` (default) or `dat`, the audiowaveform binary format
+    pub format: Option<String>,
+}
+
 #[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
 pub struct UserPasswordUpdateDto {
     #[validate(
@@ -152,6 +198,124 @@ pub struct UploadResponse {
    pub track_id: uuid::Uuid,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UploadStatusResponse {
+    pub track_id: uuid::Uuid,
+    pub upload_status: String,
+    pub total_chunks: i32,
+    pub uploaded_chunks: i32,
+    pub missing_chunks: Vec<i32>,
+    pub failure_reason: Option<String>,
+    // Earlier track with the same recording, once the fingerprint job has run
+    pub duplicate_of: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateTrackDto {
+    pub track_id: uuid::Uuid,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    // Share of identical fingerprint bits, 1.0 for the same audio
+    pub similarity: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicatesResponse {
+    pub status: &'static str,
+    // False until the fingerprint job has run
+    pub fingerprinted: bool,
+    pub duplicate_of: Option<uuid::Uuid>,
+    pub duplicates: Vec<DuplicateTrackDto>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkTrackDto {
+    // The existing track to keep instead of the upload
+    pub track_id: uuid::Uuid,
+}
+
+// One audio file of an imported archive
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlbumFileDto {
+    pub path: String,
+    pub track_id: Option<uuid::Uuid>,
+    pub title: Option<String>,
+    pub upload_status: Option<String>,
+    // Why the file didn't become a playable track
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AlbumResponse {
+    pub status: &'static str,
+    pub album: Album,
+    pub files: Vec<AlbumFileDto>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AlbumsResponse {
+    pub status: &'static str,
+    pub albums: Vec<Album>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportLibraryDto {
+    // Directory on the server, below LIBRARY_ROOT
+    pub path: String,
+    // Owner of the imported tracks
+    pub user_id: uuid::Uuid,
+}
+
+// One audio file of a library as of its last scan
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LibraryFileDto {
+    pub path: String,
+    pub file_size: i64,
+    pub modified_at: NaiveDateTime,
+    pub track_id: Option<uuid::Uuid>,
+    pub title: Option<String>,
+    pub upload_status: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LibraryResponse {
+    pub status: &'static str,
+    pub library: Library,
+    pub files: Vec<LibraryFileDto>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LibrariesResponse {
+    pub status: &'static str,
+    pub libraries: Vec<Library>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrackDetailsResponse {
+    pub status: &'static str,
+    pub track: FilterTrackDto,
+}
+
+// One audio of a track, the current one or one it can be rolled back to
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrackVersionDto {
+    pub version: i32,
+    pub container: Option<String>,
+    pub codec: Option<String>,
+    pub mime_type: Option<String>,
+    pub duration: Duration,
+    pub file_size: Option<i64>,
+    pub created_at: Option<NaiveDateTime>,
+    pub is_current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrackVersionsResponse {
+    pub status: &'static str,
+    pub versions: Vec<TrackVersionDto>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct IncompleteTrackInfo {
     pub title: Option<String>,
@@ -177,27 +341,41 @@ pub struct FilterTrackDto {
     pub duration_minutes: f64, // Duration in minutes
     pub duration_seconds: f64, // Duration in seconds
     pub duration_played: f64, // Duration in seconds
-    pub file_name: Option<String>,
     pub thumbnail_name: Option<String>,
     pub is_favorite: Option<bool>,
     pub played_at: Option<chrono::NaiveDateTime>,
     pub is_created_by_user: Option<bool>,
+    // EBU R128, missing until the track has been analysed
+    pub integrated_loudness: Option<f64>, // LUFS
+    pub loudness_range: Option<f64>, // LU
+    pub true_peak: Option<f64>, // dBTP
+    // ReplayGain 2.0: gain in dB to the -18 LUFS reference, peak as a linear amplitude
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
 }
 
 impl FilterTrackDto {
     pub fn filter_track(track: &TrackDto) -> Self {
         FilterTrackDto {
-            id: track.id.clone(),
+            id: track.id,
             title: track.title.clone(),
             artist: track.artist.clone(),
             duration_minutes: convert_duration_to_minutes(&track.duration),
             duration_seconds: convert_duration_to_seconds(&track.duration),
             duration_played: convert_duration_to_seconds(&track.duration_played),
-            file_name: track.file_name.clone(),
-            thumbnail_name: track.thumbnail_name.clone(),      
-            is_favorite: track.is_favorite.clone(),      
-            played_at: track.played_at.clone(),
-            is_created_by_user: track.is_created_by_user.clone()
+            thumbnail_name: track.thumbnail_name.clone(),
+            is_favorite: track.is_favorite,
+            played_at: track.played_at,
+            is_created_by_user: track.is_created_by_user,
+            integrated_loudness: track.loudness,
+            loudness_range: track.loudness_range,
+            true_peak: track.true_peak,
+            track_gain: track.loudness.map(replay_gain),
+            track_peak: track.true_peak.map(peak_amplitude),
+            album_gain: track.album_loudness.map(replay_gain),
+            album_peak: track.album_true_peak.map(peak_amplitude),
         }
     }
 
@@ -206,6 +384,10 @@ impl FilterTrackDto {
     }
 }
 
+fn peak_amplitude(true_peak: f64) -> f64 {
+    10f64.powf(true_peak / 20.0)
+}
+
 fn convert_duration_to_minutes(duration: &Duration) -> f64 {
     // Convert duration to total seconds
     let total_seconds = (duration.months * 30 * 24 * 60 * 60) as f64 // Approximation: 30 days in a month
@@ -213,9 +395,7 @@ fn convert_duration_to_minutes(duration: &Duration) -> f64 {
         + (duration.microseconds as f64 / 1_000_000.0); // Convert microseconds to seconds
 
     // Convert total seconds to minutes
-    let total_minutes = total_seconds / 60.0;
-    
-    total_minutes
+    total_seconds / 60.0
 }
 
 fn convert_duration_to_seconds(duration: &Duration) -> f64 {
@@ -250,6 +430,11 @@ pub struct TrackDto {
     pub duration_played: Duration,
     pub played_at: Option<chrono::NaiveDateTime>,
     pub is_created_by_user: Option<bool>,
+    pub loudness: Option<f64>,
+    pub loudness_range: Option<f64>,
+    pub true_peak: Option<f64>,
+    pub album_loudness: Option<f64>,
+    pub album_true_peak: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
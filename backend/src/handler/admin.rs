@@ -0,0 +1,151 @@
+use std::{path::PathBuf, sync::Arc};
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+
+use crate::{
+    database::{libraries::LibraryExt, users::UserExt},
+    dtos::{ImportLibraryDto, LibrariesResponse, LibraryResponse},
+    error::HttpError,
+    jobs::library::queue_scan,
+    models::Library,
+    AppState,
+};
+
+// Behind `auth` and `admin`, see routes.rs
+pub fn admin_handler() -> Router {
+    Router::new()
+        .route("/libraries", post(import_library))
+        .route("/libraries", get(get_libraries))
+        .route("/libraries/:library_id", get(get_library))
+        .route("/libraries/:library_id/scan", post(scan_library))
+}
+
+// Only directories below LIBRARY_ROOT, whatever `..` or symlinks the path has
+fn library_path(app_state: &AppState, path: &str) -> Result<PathBuf, HttpError> {
+    let root = app_state.env.library_root.as_deref().ok_or(HttpError::new(
+        "Library imports are disabled, set LIBRARY_ROOT to enable them",
+        StatusCode::FORBIDDEN,
+    ))?;
+    let root = std::fs::canonicalize(root)
+        .map_err(|e| HttpError::server_error(format!("LIBRARY_ROOT: {}", e)))?;
+
+    let path = std::fs::canonicalize(root.join(path))
+        .map_err(|_| HttpError::bad_request("Directory not found"))?;
+    if !path.starts_with(&root) || !path.is_dir() {
+        return Err(HttpError::bad_request(
+            "Path must be a directory below LIBRARY_ROOT",
+        ));
+    }
+
+    Ok(path)
+}
+
+async fn library_response(
+    app_state: &AppState,
+    library: Library,
+) -> Result<Json<LibraryResponse>, HttpError> {
+    let files = app_state
+        .db_client
+        .get_library_files(library.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(Json(LibraryResponse {
+        status: "success",
+        library,
+        files,
+    }))
+}
+
+// Starts mirroring a directory into the user's tracks, or rescans it if it
+// already is
+pub async fn import_library(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<ImportLibraryDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let path = library_path(&app_state, &body.path)?;
+
+    app_state
+        .db_client
+        .get_user(Some(body.user_id), None, None)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::bad_request("User not found"))?;
+
+    let library = app_state
+        .db_client
+        .save_library(body.user_id, &path.to_string_lossy())
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    scan_response(&app_state, library).await
+}
+
+pub async fn get_libraries(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, HttpError> {
+    let libraries = app_state
+        .db_client
+        .get_libraries()
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(Json(LibrariesResponse {
+        status: "success",
+        libraries,
+    }))
+}
+
+// The library with every audio file of its last scan and the track it became
+pub async fn get_library(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Path(library_id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, HttpError> {
+    let library = app_state
+        .db_client
+        .get_library(library_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::not_found("Library not found"))?;
+
+    library_response(&app_state, library).await
+}
+
+pub async fn scan_library(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Path(library_id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, HttpError> {
+    let library = app_state
+        .db_client
+        .get_library(library_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::not_found("Library not found"))?;
+
+    scan_response(&app_state, library).await
+}
+
+// A scan that is already queued or running isn't queued twice
+async fn scan_response(
+    app_state: &AppState,
+    library: Library,
+) -> Result<Json<LibraryResponse>, HttpError> {
+    queue_scan(app_state, library.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let library = app_state
+        .db_client
+        .get_library(library.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::not_found("Library not found"))?;
+
+    library_response(app_state, library).await
+}
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use axum::{extract::Path, response::IntoResponse, routing::get, Extension, Json, Router};
+
+use crate::{
+    auth::JWTAuthMiddleware,
+    database::albums::AlbumExt,
+    dtos::{AlbumResponse, AlbumsResponse},
+    error::HttpError,
+    AppState,
+};
+
+// Albums are made by uploading a ZIP archive, see `upload::import_archive`
+pub fn albums_handler() -> Router {
+    Router::new()
+        .route("/", get(get_user_albums))
+        .route("/:album_id", get(get_album))
+}
+
+pub async fn get_user_albums(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let albums = app_state
+        .db_client
+        .get_user_albums(user.user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(Json(AlbumsResponse {
+        status: "success",
+        albums,
+    }))
+}
+
+// The album with what became of every file of its archive. The import is
+// done once `import_status` is complete.
+pub async fn get_album(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Path(album_id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, HttpError> {
+    let album = app_state
+        .db_client
+        .get_album(album_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|album| album.user_id == user.user.id)
+        .ok_or(HttpError::not_found("Album not found"))?;
+
+    let files = app_state
+        .db_client
+        .get_album_files(album_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(Json(AlbumResponse {
+        status: "success",
+        album,
+        files,
+    }))
+}
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Request},
+    response::IntoResponse,
+    routing::get,
+    Extension, Router,
+};
+
+use crate::{
+    error::HttpError,
+    utils::{cache::ASSET_CACHE_CONTROL, stream::serve_object},
+    AppState,
+};
+
+pub fn assets_handler() -> Router {
+    Router::new().route("/*path", get(get_asset))
+}
+
+// Thumbnails and playlist covers, served from whichever storage backend is active
+pub async fn get_asset(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Path(path): Path<String>,
+    req: Request,
+) -> Result<impl IntoResponse, HttpError> {
+    if path.split('/').any(|part| part.is_empty() || part == "..") {
+        return Err(HttpError::not_found("Asset not found"));
+    }
+
+    let content_type = mime_guess::from_path(&path)
+        .first_or_octet_stream()
+        .to_string();
+
+    serve_object(
+        app_state.storage.as_ref(),
+        &format!("assets/{}", path),
+        &content_type,
+        ASSET_CACHE_CONTROL,
+        None,
+        req.headers(),
+    )
+    .await
+}
@@ -1,13 +1,6 @@
-use std::{path::PathBuf, sync::Arc};
+use std::sync::Arc;
 
-use axum::{
-    body::Body,
-    extract::{Path, Request},
-    http::{header, Response},
-    response::IntoResponse,
-    routing::get,
-    Extension, Json, Router,
-};
+use axum::{response::IntoResponse, routing::get, Extension, Json, Router};
 
 use crate::{
     auth::JWTAuthMiddleware,
@@ -17,13 +10,10 @@ use crate::{
     AppState,
 };
 
-use tokio::fs;
-
 pub fn get_file_handler() -> Router {
     Router::new()
         .route("/incomplete", get(get_incomplete_uploads_handler))
         .route("/track", get(get_random_tracks_handler))
-        .route("/play/:file_name", get(stream_audio))
 }
 
 pub async fn get_incomplete_uploads_handler(
@@ -68,64 +58,3 @@ pub async fn get_random_tracks_handler(
 
     Ok(Json(response))
 }
-
-async fn stream_audio(
-    Path(file_name): Path<String>,
-    Extension(_app_state): Extension<Arc<AppState>>,
-    Extension(_user): Extension<JWTAuthMiddleware>,
-    req: Request<Body>,
-) -> Result<impl IntoResponse, HttpError> {
-    let file_path = PathBuf::from("uploads/").join(file_name);
-
-    let headers = req.headers();
-
-    match fs::read(&file_path).await {
-        Ok(content) => {
-            // Log or process headers if needed
-            if let Some(range) = headers.get("Range") {
-                println!("Range header: {:?}", range);
-                // Implement range handling logic here if needed
-                let range_str = range.to_str().unwrap_or("");
-                let parts: Vec<&str> = range_str.split('=').collect();
-                if parts.len() == 2 && parts[0] == "bytes" {
-                    let ranges: Vec<&str> = parts[1].split('-').collect();
-                    if let Some(start) = ranges.get(0).and_then(|s| s.parse::<u64>().ok()) {
-                        let end = if let Some(end_str) = ranges.get(1) {
-                            end_str
-                                .parse::<u64>()
-                                .ok()
-                                .unwrap_or_else(|| content.len() as u64 - 1)
-                        } else {
-                            content.len() as u64 - 1
-                        };
-
-                        // Create a Vec<u8> for the body
-                        let body = content[start as usize..=end as usize].to_vec();
-                        let response: Response<Body> = Response::builder()
-                            .status(206) // Partial content status
-                            .header(header::CONTENT_TYPE, "audio/mpeg")
-                            .header(
-                                header::CONTENT_RANGE,
-                                format!("bytes {}-{}/{}", start, end, content.len()),
-                            )
-                            .body(Body::from(body))
-                            .unwrap();
-
-                        return Ok(response);
-                    }
-                }
-            }
-
-            // If no Range header, return the whole content
-            let response: Response<Body> = Response::builder()
-                .header(header::CONTENT_TYPE, "audio/mpeg")
-                .header(header::CONTENT_DISPOSITION, "inline")
-                .header(header::ACCEPT_RANGES, "bytes") // Allow range requests
-                .body(Body::from(content)) // Take ownership of content
-                .unwrap();
-
-            Ok(response)
-        }
-        Err(_) => Err(HttpError::server_error("File Not Found")),
-    }
-}
@@ -4,4 +4,9 @@ pub mod upload;
 pub mod getfile;
 pub mod favorites;
 pub mod playlists;
-pub mod history;
\ No newline at end of file
+pub mod history;
+pub mod tracks;
+pub mod assets;
+pub mod tus;
+pub mod albums;
+pub mod admin;
\ No newline at end of file
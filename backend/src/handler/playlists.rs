@@ -1,8 +1,4 @@
-use std::{
-    fs::{self, File},
-    io::Write,
-    sync::Arc,
-};
+use std::sync::Arc;
 
 use axum::{
     extract::{Multipart, Path},
@@ -10,12 +6,16 @@ use axum::{
     routing::{get, post},
     Extension, Json, Router,
 };
+use bytes::Bytes;
 
 use crate::{
     auth::JWTAuthMiddleware,
     database::playlists::PlaylistsExt,
     dtos::{AddTrackPlaylist, FilterTrackDto, PlayListResponse, Response, TrackResponseDto},
     error::HttpError,
+    handler::upload::image_file_name,
+    storage,
+    utils::validate,
     AppState,
 };
 
@@ -64,21 +64,18 @@ pub async fn create_playlist(
         return Err(HttpError::bad_request("Thumbnail is missing"));
     }
 
-    let thumbnail_dir = format!("assets/playlist/");
-    if let Err(_err) = fs::create_dir_all(&thumbnail_dir) {
-        return Err(HttpError::server_error("Createing failed"));
-    }
-
-    let thumbnail_file_path = format!("{}/{}", &thumbnail_dir, &thumbnail_name);
-    let mut file = match File::create(&thumbnail_file_path) {
-        Ok(f) => f,
-        Err(err) => {
-            return Err(HttpError::server_error(err.to_string()));
-        }
-    };
+    let extension = validate::check_image(&app_state.env, &thumbnail_data)?;
+    let thumbnail_name = image_file_name(&thumbnail_name, extension);
 
-    if let Err(_err) = file.write_all(&thumbnail_data) {
-        return Err(HttpError::server_error("Createing failed"));
+    if let Err(err) = app_state
+        .storage
+        .put(
+            &storage::playlist_cover_key(&thumbnail_name),
+            Bytes::from(thumbnail_data),
+        )
+        .await
+    {
+        return Err(HttpError::server_error(err.to_string()));
     }
 
     app_state
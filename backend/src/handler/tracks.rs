@@ -0,0 +1,752 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Multipart, Path, Query, Request},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, patch, post},
+    Extension, Json, Router,
+};
+
+use bytes::Bytes;
+
+use crate::{
+    audio::{hls, waveform::WaveformFormat},
+    auth::JWTAuthMiddleware,
+    database::{
+        renditions::RenditionExt,
+        track::{DeletedTrack, TrackExt},
+        versions::VersionExt,
+    },
+    dtos::{
+        DuplicateTrackDto, DuplicatesResponse, FilterTrackDto, LinkTrackDto, Response,
+        StreamQueryDto, TrackDetailsResponse, TrackVersionsResponse, UploadResponse,
+        WaveformQueryDto,
+    },
+    error::HttpError,
+    handler::upload::{enqueue_processing, receive_chunk, text_field},
+    jobs::fingerprint::find_duplicates,
+    models::{Track, TrackDetails, TrackRendition, STREAM_QUALITIES},
+    storage,
+    utils::{
+        cache::{ASSET_CACHE_CONTROL, AUDIO_CACHE_CONTROL},
+        stream::{serve_object, stream_audio},
+        validate,
+    },
+    AppState,
+};
+
+const HLS_PLAYLIST_TYPE: &str = "application/vnd.apple.mpegurl";
+const MAX_DESCRIPTION_LENGTH: usize = 5000;
+
+pub fn tracks_handler() -> Router {
+    Router::new()
+        .route("/:track_id", patch(update_track).delete(delete_track))
+        .route("/:track_id/stream", get(stream_track))
+        .route("/:track_id/hls/master.m3u8", get(hls_master_playlist))
+        .route("/:track_id/hls/:variant/:file", get(hls_file))
+        .route("/:track_id/waveform", get(track_waveform))
+        .route("/:track_id/duplicates", get(track_duplicates))
+        .route("/:track_id/link", post(link_track))
+        .route(
+            "/:track_id/versions",
+            get(track_versions).post(upload_track_version),
+        )
+        .route(
+            "/:track_id/versions/:version/restore",
+            post(restore_track_version),
+        )
+}
+
+// Served without auth, so anyone can listen in before signing up
+pub fn track_previews_handler() -> Router {
+    Router::new().route("/:track_id/preview", get(track_preview))
+}
+
+// Private tracks are only visible to their owner, public and unlisted ones to
+// anyone holding the track id.
+fn can_view(track: &Track, user_id: uuid::Uuid) -> bool {
+    track.visibility != "private" || track.user_id == Some(user_id)
+}
+
+async fn get_playable_track(
+    app_state: &AppState,
+    track_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+) -> Result<Track, HttpError> {
+    let track = app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // Tracks the user may not see are reported exactly like missing ones
+    let track = match track {
+        Some(track) if can_view(&track, user_id) => track,
+        _ => return Err(HttpError::not_found("Track not found")),
+    };
+
+    if track.upload_status.as_deref() != Some("complete") {
+        return Err(HttpError::new(
+            "Track upload is not complete",
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    Ok(track)
+}
+
+// Renditions to try for a quality, in order. Running out means the original,
+// which is never worse than what was asked for.
+fn rendition_preference(quality: &str) -> &'static [&'static str] {
+    match quality {
+        "auto" | "high" => &["high"],
+        "low" => &["low", "high"],
+        "lossless" => &["lossless"],
+        "normalized" => &["normalized", "high"],
+        _ => &[],
+    }
+}
+
+pub async fn stream_track(
+    Path(track_id): Path<uuid::Uuid>,
+    Query(query): Query<StreamQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    req: Request<Body>,
+) -> Result<impl IntoResponse, HttpError> {
+    let track = get_playable_track(&app_state, track_id, user.user.id).await?;
+
+    // An explicit `quality` wins over the user's preference
+    let quality = query.quality.unwrap_or(user.user.stream_quality);
+    if !STREAM_QUALITIES.contains(&quality.as_str()) {
+        return Err(HttpError::bad_request(
+            "Quality must be one of auto, original, lossless, high, low or normalized",
+        ));
+    }
+
+    let preference = rendition_preference(&quality);
+    let renditions = if preference.is_empty() {
+        Vec::new()
+    } else {
+        app_state
+            .db_client
+            .get_renditions(track_id)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?
+    };
+
+    let rendition: Option<&TrackRendition> = preference.iter().find_map(|quality| {
+        renditions
+            .iter()
+            .find(|rendition| rendition.quality == *quality)
+    });
+
+    if let Some(rendition) = rendition {
+        let etag = format!(
+            "{}-{}",
+            track.content_hash.as_deref().unwrap_or_default(),
+            rendition.quality
+        );
+
+        return serve_object(
+            app_state.storage.as_ref(),
+            &rendition.storage_key,
+            &rendition.mime_type,
+            AUDIO_CACHE_CONTROL,
+            Some(&etag),
+            req.headers(),
+        )
+        .await;
+    }
+
+    let audio_key =
+        storage::track_audio_key(&track).ok_or(HttpError::not_found("Track not found"))?;
+
+    stream_audio(
+        app_state.storage.as_ref(),
+        &audio_key,
+        track.mime_type,
+        track.content_hash.as_deref(),
+        req.headers(),
+    )
+    .await
+}
+
+// Master playlist of the track's HLS variants. Its URIs are relative, so the
+// variant playlists and segments resolve to `hls_file` below.
+pub async fn hls_master_playlist(
+    Path(track_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    req: Request<Body>,
+) -> Result<impl IntoResponse, HttpError> {
+    let (track, hls_key) = get_hls_track(&app_state, track_id, user.user.id).await?;
+    let content_hash = track.content_hash.unwrap_or_default();
+
+    serve_object(
+        app_state.storage.as_ref(),
+        &hls_key,
+        HLS_PLAYLIST_TYPE,
+        AUDIO_CACHE_CONTROL,
+        Some(&format!("{}-hls", content_hash)),
+        req.headers(),
+    )
+    .await
+}
+
+pub async fn hls_file(
+    Path((track_id, variant, file)): Path<(uuid::Uuid, String, String)>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    req: Request<Body>,
+) -> Result<impl IntoResponse, HttpError> {
+    // Only names the packager writes, anything else could walk out of the track's directory
+    let valid_variant = !variant.is_empty()
+        && variant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_variant {
+        return Err(HttpError::not_found("File Not Found"));
+    }
+    let content_type = match file.as_str() {
+        hls::MEDIA_PLAYLIST => HLS_PLAYLIST_TYPE,
+        hls::INIT_SEGMENT => "audio/mp4",
+        file if is_segment_name(file) => "audio/mp4",
+        _ => return Err(HttpError::not_found("File Not Found")),
+    };
+
+    let (track, _) = get_hls_track(&app_state, track_id, user.user.id).await?;
+    let content_hash = track.content_hash.unwrap_or_default();
+
+    serve_object(
+        app_state.storage.as_ref(),
+        &storage::hls_key(&content_hash, &format!("{}/{}", variant, file)),
+        content_type,
+        AUDIO_CACHE_CONTROL,
+        Some(&format!("{}-hls-{}-{}", content_hash, variant, file)),
+        req.headers(),
+    )
+    .await
+}
+
+// `seg_{n}.m4s`
+fn is_segment_name(file: &str) -> bool {
+    file.strip_prefix("seg_")
+        .and_then(|rest| rest.strip_suffix(".m4s"))
+        .is_some_and(|index| !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()))
+}
+
+// The track and the key of its master playlist, once the packaging job is done
+async fn get_hls_track(
+    app_state: &AppState,
+    track_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+) -> Result<(Track, String), HttpError> {
+    let track = get_playable_track(app_state, track_id, user_id).await?;
+
+    let renditions = app_state
+        .db_client
+        .get_renditions(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let hls_key = renditions
+        .into_iter()
+        .find(|rendition| rendition.quality == "hls")
+        .map(|rendition| rendition.storage_key)
+        .ok_or(HttpError::not_found("HLS is not available for this track yet"))?;
+
+    Ok((track, hls_key))
+}
+
+pub async fn track_waveform(
+    Path(track_id): Path<uuid::Uuid>,
+    Query(query): Query<WaveformQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    req: Request<Body>,
+) -> Result<impl IntoResponse, HttpError> {
+    let format = WaveformFormat::from_name(query.format.as_deref().unwrap_or("json"))
+        .ok_or(HttpError::bad_request("Format must be json or dat"))?;
+
+    let track = get_playable_track(&app_state, track_id, user.user.id).await?;
+    // Tracks stored before content addressing have none
+    let content_hash = track
+        .content_hash
+        .ok_or(HttpError::not_found("Waveform not found"))?;
+
+    serve_object(
+        app_state.storage.as_ref(),
+        &storage::waveform_key(&content_hash, format.extension()),
+        format.content_type(),
+        AUDIO_CACHE_CONTROL,
+        Some(&format!("{}-waveform-{}", content_hash, format.extension())),
+        req.headers(),
+    )
+    .await
+}
+
+// The uploader's own complete track, for the duplicate lookup, linking and
+// replacing its audio
+async fn get_own_track(
+    app_state: &AppState,
+    track_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+) -> Result<Track, HttpError> {
+    let track = app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|track| track.user_id == Some(user_id))
+        .ok_or(HttpError::not_found("Track not found"))?;
+
+    if track.upload_status.as_deref() != Some("complete") {
+        return Err(HttpError::new(
+            "Track upload is not complete",
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    Ok(track)
+}
+
+// Earlier tracks with the same recording, so the uploader can link to one of
+// them instead of keeping a copy
+pub async fn track_duplicates(
+    Path(track_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let track = get_own_track(&app_state, track_id, user.user.id).await?;
+
+    let fingerprint = app_state
+        .db_client
+        .get_fingerprint(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let duplicates = match &fingerprint {
+        Some(fingerprint) => find_duplicates(&app_state, &track, fingerprint)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?,
+        None => Vec::new(),
+    };
+
+    Ok(Json(DuplicatesResponse {
+        status: "success",
+        fingerprinted: fingerprint.is_some(),
+        duplicate_of: track.duplicate_of,
+        duplicates: duplicates
+            .into_iter()
+            .map(|(candidate, similarity)| DuplicateTrackDto {
+                track_id: candidate.id,
+                title: candidate.title,
+                artist: candidate.artist,
+                similarity,
+            })
+            .collect(),
+    }))
+}
+
+// Replaces the upload with one of its duplicates: the existing track goes to
+// the user's favorites and playlists, the upload is deleted
+pub async fn link_track(
+    Path(track_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<LinkTrackDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let track = get_own_track(&app_state, track_id, user.user.id).await?;
+
+    let fingerprint = app_state
+        .db_client
+        .get_fingerprint(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::new(
+            "Track has not been fingerprinted yet",
+            StatusCode::CONFLICT,
+        ))?;
+
+    // Only a track the lookup would offer, anything else isn't a duplicate
+    let duplicates = find_duplicates(&app_state, &track, &fingerprint)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+    if !duplicates
+        .iter()
+        .any(|(candidate, _)| candidate.id == body.track_id)
+    {
+        return Err(HttpError::bad_request(
+            "track_id is not a duplicate of this track",
+        ));
+    }
+
+    let deleted = app_state
+        .db_client
+        .link_duplicate(track_id, body.track_id, user.user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+    remove_track_files(&app_state, track_id, deleted).await;
+
+    Ok(Json(UploadResponse {
+        track_id: body.track_id,
+    }))
+}
+
+// Private tracks have no public preview, they 404 like missing ones
+pub async fn track_preview(
+    Path(track_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request<Body>,
+) -> Result<impl IntoResponse, HttpError> {
+    let track = app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|track| {
+            track.visibility != "private" && track.upload_status.as_deref() == Some("complete")
+        })
+        .ok_or(HttpError::not_found("Track not found"))?;
+
+    let preview = app_state
+        .db_client
+        .get_renditions(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .into_iter()
+        .find(|rendition| rendition.quality == "preview");
+    let Some(preview) = preview else {
+        // Nothing encodes previews in builds without libopus, there's no point waiting
+        if !cfg!(feature = "opus") {
+            return Err(HttpError::new(
+                "Previews need a build with the `opus` feature",
+                StatusCode::NOT_IMPLEMENTED,
+            ));
+        }
+        return Err(HttpError::not_found(
+            "Preview is not available for this track yet",
+        ));
+    };
+
+    // Moving the start swaps the file, the name says which part it holds
+    let file_name = preview.storage_key.rsplit('/').next().unwrap_or_default();
+    let etag = format!("{}-{}", track.content_hash.unwrap_or_default(), file_name);
+
+    serve_object(
+        app_state.storage.as_ref(),
+        &preview.storage_key,
+        &preview.mime_type,
+        ASSET_CACHE_CONTROL,
+        Some(&etag),
+        req.headers(),
+    )
+    .await
+}
+
+// New audio for the track through the chunked upload protocol, chunks after
+// the first carry the upload's id as trackId. Once assembled the upload goes
+// away and the track plays the new audio under its own id.
+pub async fn upload_track_version(
+    Path(track_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    multipart: Multipart,
+) -> Result<impl IntoResponse, HttpError> {
+    get_own_track(&app_state, track_id, user.user.id).await?;
+
+    let response = receive_chunk(&app_state, user.user.id, multipart, Some(track_id)).await?;
+
+    Ok(Json(response))
+}
+
+pub async fn track_versions(
+    Path(track_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    get_own_track(&app_state, track_id, user.user.id).await?;
+
+    let versions = app_state
+        .db_client
+        .get_track_versions(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(Json(TrackVersionsResponse {
+        status: "success",
+        versions,
+    }))
+}
+
+// Rolls the track back to an earlier audio. Renditions and analysis are
+// redone for it like for a new upload.
+pub async fn restore_track_version(
+    Path((track_id, version)): Path<(uuid::Uuid, i32)>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    get_own_track(&app_state, track_id, user.user.id).await?;
+
+    let restored = app_state
+        .db_client
+        .get_track_versions(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .into_iter()
+        .find(|restored| restored.version == version)
+        .ok_or(HttpError::not_found("Version not found"))?;
+
+    if restored.is_current {
+        return Err(HttpError::new(
+            "Version is already the current one",
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    let content_hash = app_state
+        .db_client
+        .get_version_content_hash(track_id, version)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::not_found("Version not found"))?;
+
+    let audio_key = storage::audio_key(&content_hash);
+    let stored = app_state
+        .storage
+        .stat(&audio_key)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+    if stored.is_none() {
+        return Err(HttpError::new(
+            "Audio of this version is no longer stored",
+            StatusCode::GONE,
+        ));
+    }
+
+    app_state
+        .db_client
+        .restore_track_version(track_id, version)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    enqueue_processing(&app_state, track_id).await;
+
+    Ok(Json(Response {
+        status: "success",
+        message: format!("Restored version {}", version),
+    }))
+}
+
+// Partial edit of the track's details as multipart fields. Fields that aren't
+// sent stay as they are, empty ones are cleared. An empty `thumbnail` removes
+// the artwork.
+pub async fn update_track(
+    Path(track_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, HttpError> {
+    let track = app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|track| track.user_id == Some(user.user.id))
+        .ok_or(HttpError::not_found("Track not found"))?;
+
+    let mut details = TrackDetails {
+        title: track.title.clone(),
+        artist: track.artist.clone(),
+        album: track.album.clone(),
+        genre: track.genre.clone(),
+        description: track.description.clone(),
+        thumbnail_name: track.thumbnail_name.clone(),
+    };
+    let mut thumbnail: Option<Vec<u8>> = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| HttpError::bad_request(e.to_string()))?
+    {
+        let field_name = field.name().unwrap_or_default().to_string();
+        if field_name == "thumbnail" {
+            let data = field
+                .bytes()
+                .await
+                .map_err(|e| HttpError::bad_request(e.to_string()))?;
+            thumbnail = Some(data.to_vec());
+            continue;
+        }
+
+        let value = field
+            .text()
+            .await
+            .map_err(|e| HttpError::bad_request(e.to_string()))?;
+        match field_name.as_str() {
+            "title" => {
+                details.title = Some(
+                    text_field("title", value)?
+                        .ok_or(HttpError::bad_request("title can't be empty"))?,
+                );
+            }
+            "artist" => details.artist = text_field("artist", value)?,
+            "album" => details.album = text_field("album", value)?,
+            "genre" => details.genre = text_field("genre", value)?,
+            "description" => {
+                let value = value.trim();
+                if value.chars().count() > MAX_DESCRIPTION_LENGTH {
+                    return Err(HttpError::bad_request("description is too long"));
+                }
+                details.description = (!value.is_empty()).then(|| value.to_string());
+            }
+            _ => {
+                return Err(HttpError::bad_request(format!(
+                    "{} can't be edited",
+                    field_name
+                )));
+            }
+        }
+    }
+
+    // Named after the track like embedded covers, a new one takes the old one's place
+    if let Some(data) = thumbnail {
+        details.thumbnail_name = if data.is_empty() {
+            None
+        } else {
+            let extension = validate::check_image(&app_state.env, &data)?;
+            let thumbnail_name = format!("{}.{}", track_id, extension);
+            app_state
+                .storage
+                .put(&storage::thumbnail_key(&thumbnail_name), Bytes::from(data))
+                .await
+                .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+            Some(thumbnail_name)
+        };
+    }
+
+    app_state
+        .db_client
+        .update_track_details(track_id, &details)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if let Some(old_name) = track
+        .thumbnail_name
+        .filter(|old_name| details.thumbnail_name.as_ref() != Some(old_name))
+    {
+        delete_thumbnail(&app_state, &old_name).await?;
+    }
+
+    let track = app_state
+        .db_client
+        .get_track_dto(track_id, user.user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::not_found("Track not found"))?;
+
+    Ok(Json(TrackDetailsResponse {
+        status: "success",
+        track: FilterTrackDto::filter_track(&track),
+    }))
+}
+
+// Artwork is shared with the album and other tracks of it, it stays while used
+async fn delete_thumbnail(app_state: &AppState, thumbnail_name: &str) -> Result<(), HttpError> {
+    let used = app_state
+        .db_client
+        .is_thumbnail_used(thumbnail_name)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if !used {
+        app_state
+            .storage
+            .delete(&storage::thumbnail_key(thumbnail_name))
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+// Deletes the track wherever it's used. Its audio, renditions and artwork go
+// from storage unless another track still has them.
+pub async fn delete_track(
+    Path(track_id): Path<uuid::Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|track| track.user_id == Some(user.user.id))
+        .ok_or(HttpError::not_found("Track not found"))?;
+
+    let deleted = app_state
+        .db_client
+        .delete_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+    remove_track_files(&app_state, track_id, deleted).await;
+
+    Ok(Json(Response {
+        status: "success",
+        message: "Track deleted".to_string(),
+    }))
+}
+
+// Storage side of a deleted track. The rows are gone already, files that fail
+// to go are only logged.
+async fn remove_track_files(app_state: &AppState, track_id: uuid::Uuid, deleted: DeletedTrack) {
+    let storage = app_state.storage.as_ref();
+    let mut freed = 0;
+    for upload_id in std::iter::once(track_id).chain(deleted.upload_ids) {
+        match storage::delete_upload_files(storage, upload_id).await {
+            Ok(bytes) => freed += bytes,
+            Err(e) => tracing::warn!("Failed to delete the chunks of {}: {}", upload_id, e),
+        }
+    }
+    for content_hash in &deleted.content_hashes {
+        let mut lock = match app_state.db_client.lock_content_hash(content_hash).await {
+            Ok(lock) => lock,
+            Err(e) => {
+                tracing::error!("Failed to lock the audio {}: {}", content_hash, e);
+                continue;
+            }
+        };
+        // An upload may have taken the audio up again since the track was deleted
+        match lock.is_used().await {
+            Ok(false) => {}
+            Ok(true) => continue,
+            Err(e) => {
+                tracing::error!("Failed to check the audio {}: {}", content_hash, e);
+                continue;
+            }
+        }
+        match storage::delete_audio_files(storage, content_hash).await {
+            Ok(bytes) => freed += bytes,
+            Err(e) => tracing::warn!("Failed to delete the audio {}: {}", content_hash, e),
+        }
+        if let Err(e) = lock.release().await {
+            tracing::error!("Failed to unlock the audio {}: {}", content_hash, e);
+        }
+    }
+    let thumbnail_key = deleted
+        .thumbnail_name
+        .as_deref()
+        .map(storage::thumbnail_key);
+    for key in deleted.legacy_key.into_iter().chain(thumbnail_key) {
+        if let Err(e) = storage.delete(&key).await {
+            tracing::warn!("Failed to delete {}: {}", key, e);
+        }
+    }
+    tracing::info!("Deleted track {}, {} bytes freed", track_id, freed);
+}
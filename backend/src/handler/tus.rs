@@ -0,0 +1,456 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Request},
+    http::{header, HeaderMap, HeaderValue, Method, Response, StatusCode},
+    middleware::{self, Next},
+    response::IntoResponse,
+    routing::{options, post},
+    Extension, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    auth::JWTAuthMiddleware,
+    database::{
+        track::TrackExt,
+        upload::{ReceivedChunk, UploadExt},
+    },
+    error::HttpError,
+    handler::upload::{check_quota, discard_upload, finish_upload, sanitize_filename},
+    models::Track,
+    storage,
+    utils::validate,
+    AppState,
+};
+
+// tus 1.0 (https://tus.io/protocols/resumable-upload) with the creation,
+// termination and checksum (sha256) extensions, on top of the same track
+// records and assembly as the multipart protocol. Every PATCH body is stored
+// as the next chunk, the upload offset is the sum of their sizes.
+
+const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,termination,checksum";
+const TUS_CHECKSUM_ALGORITHM: &str = "sha256";
+const OFFSET_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+const TUS_RESUMABLE: &str = "tus-resumable";
+const UPLOAD_LENGTH: &str = "upload-length";
+const UPLOAD_OFFSET: &str = "upload-offset";
+const UPLOAD_METADATA: &str = "upload-metadata";
+const UPLOAD_CHECKSUM: &str = "upload-checksum";
+const UPLOAD_DEFER_LENGTH: &str = "upload-defer-length";
+
+pub fn tus_handler() -> Router {
+    Router::new()
+        .route("/", post(create_upload).options(server_options))
+        .route(
+            "/:track_id",
+            options(server_options)
+                .head(upload_offset)
+                .patch(append_chunk)
+                .delete(terminate_upload),
+        )
+        .layer(middleware::from_fn(tus_resumable))
+}
+
+// Rejects clients speaking another protocol version and tags every response.
+// OPTIONS is how a client finds the version out, so it needs no header.
+async fn tus_resumable(req: Request, next: Next) -> axum::response::Response {
+    let supported = req.method() == Method::OPTIONS
+        || req
+            .headers()
+            .get(TUS_RESUMABLE)
+            .is_some_and(|version| version == TUS_VERSION);
+
+    let mut response = if supported {
+        next.run(req).await
+    } else {
+        let mut response = StatusCode::PRECONDITION_FAILED.into_response();
+        response
+            .headers_mut()
+            .insert("tus-version", HeaderValue::from_static(TUS_VERSION));
+        response
+    };
+
+    response
+        .headers_mut()
+        .insert(TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION));
+    response
+}
+
+fn header_i64(headers: &HeaderMap, name: &str) -> Option<i64> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .filter(|value: &i64| *value >= 0)
+}
+
+// `Upload-Metadata: filename d29ybGQuZmxhYw==,filetype YXVkaW8vZmxhYw==`
+fn parse_metadata(headers: &HeaderMap) -> HashMap<String, String> {
+    let Some(metadata) = headers
+        .get(UPLOAD_METADATA)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return HashMap::new();
+    };
+
+    metadata
+        .split(',')
+        .filter_map(|pair| {
+            let mut parts = pair.trim().splitn(2, ' ');
+            let key = parts.next().filter(|key| !key.is_empty())?;
+            let value = match parts.next() {
+                Some(value) => STANDARD.decode(value.trim()).ok()?,
+                None => Vec::new(),
+            };
+
+            Some((key.to_string(), String::from_utf8_lossy(&value).into_owned()))
+        })
+        .collect()
+}
+
+// checksum extension, `Upload-Checksum: sha256 <base64 digest>`. Chunks sent
+// without one are taken as they are.
+fn check_checksum(headers: &HeaderMap, chunk_hash: &[u8]) -> Result<(), HttpError> {
+    let Some(checksum) = headers.get(UPLOAD_CHECKSUM) else {
+        return Ok(());
+    };
+    let checksum = checksum.to_str().unwrap_or_default();
+    let (algorithm, digest) = checksum.split_once(' ').unwrap_or((checksum, ""));
+
+    if algorithm != TUS_CHECKSUM_ALGORITHM {
+        return Err(HttpError::bad_request("Unsupported checksum algorithm"));
+    }
+
+    if STANDARD.decode(digest.trim()).ok().as_deref() != Some(chunk_hash) {
+        return Err(HttpError::new(
+            "Checksum Mismatch",
+            StatusCode::from_u16(460).unwrap(),
+        ));
+    }
+
+    Ok(())
+}
+
+// What the server supports, for clients that ask before uploading
+pub async fn server_options(Extension(app_state): Extension<Arc<AppState>>) -> impl IntoResponse {
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header("tus-version", TUS_VERSION)
+        .header("tus-extension", TUS_EXTENSIONS)
+        .header("tus-checksum-algorithm", TUS_CHECKSUM_ALGORITHM)
+        .header("tus-max-size", app_state.env.max_upload_size)
+        .body(Body::empty())
+        .unwrap()
+}
+
+async fn owned_track(
+    app_state: &AppState,
+    track_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+) -> Result<Track, HttpError> {
+    app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|track| track.user_id == Some(user_id))
+        .ok_or(HttpError::not_found("Upload not found"))
+}
+
+pub async fn create_upload(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, HttpError> {
+    if headers.contains_key(UPLOAD_DEFER_LENGTH) {
+        return Err(HttpError::bad_request(
+            "Upload-Defer-Length is not supported",
+        ));
+    }
+
+    let upload_length = header_i64(&headers, UPLOAD_LENGTH)
+        .ok_or(HttpError::bad_request("Upload-Length is missing or invalid"))?;
+    if upload_length == 0 {
+        return Err(HttpError::bad_request("Empty uploads are not accepted"));
+    }
+    validate::check_upload_size(&app_state.env, upload_length)?;
+    // The whole length is reserved against the quota, so the chunks need no check
+    check_quota(&app_state, user.user.id, true, upload_length).await?;
+
+    let metadata = parse_metadata(&headers);
+    let file_name = metadata
+        .get("filename")
+        .or(metadata.get("name"))
+        .map(|file_name| sanitize_filename(file_name))
+        .filter(|file_name| !file_name.is_empty())
+        .unwrap_or_else(|| "untitled".to_string());
+
+    let track_id = app_state
+        .db_client
+        .upload_file(user.user.id, &file_name)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    app_state
+        .db_client
+        .create_tus_upload(track_id, upload_length)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(Response::builder()
+        .status(StatusCode::CREATED)
+        .header(header::LOCATION, format!("/api/upload/tus/{}", track_id))
+        .header(UPLOAD_OFFSET, 0)
+        .body(Body::empty())
+        .unwrap())
+}
+
+pub async fn upload_offset(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Path(track_id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, HttpError> {
+    let track = owned_track(&app_state, track_id, user.user.id).await?;
+
+    let (offset, length) = if track.upload_status.as_deref() == Some("complete") {
+        // The upload rows are gone once the track is assembled
+        let audio_key =
+            storage::track_audio_key(&track).ok_or(HttpError::not_found("Upload not found"))?;
+        let object = app_state
+            .storage
+            .stat(&audio_key)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?
+            .ok_or(HttpError::not_found("Upload not found"))?;
+
+        (object.size as i64, object.size as i64)
+    } else {
+        let upload_length = app_state
+            .db_client
+            .get_audio_file(track_id)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?
+            .and_then(|audio_file| audio_file.upload_length)
+            .ok_or(HttpError::not_found("Upload not found"))?;
+
+        let offset = app_state
+            .db_client
+            .get_upload_offset(track_id)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        (offset, upload_length)
+    };
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(UPLOAD_OFFSET, offset)
+        .header(UPLOAD_LENGTH, length)
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::empty())
+        .unwrap())
+}
+
+pub async fn append_chunk(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Path(track_id): Path<uuid::Uuid>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, HttpError> {
+    if headers
+        .get(header::CONTENT_TYPE)
+        .is_none_or(|content_type| content_type != OFFSET_CONTENT_TYPE)
+    {
+        return Err(HttpError::new(
+            "Content-Type must be application/offset+octet-stream",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ));
+    }
+
+    let offset = header_i64(&headers, UPLOAD_OFFSET)
+        .ok_or(HttpError::bad_request("Upload-Offset is missing or invalid"))?;
+
+    let track = owned_track(&app_state, track_id, user.user.id).await?;
+    if track.upload_status.as_deref() == Some("complete") {
+        return Err(HttpError::new(
+            "Upload is already complete",
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    // Held until the chunk is recorded, a PATCH racing for the same offset
+    // waits here and then finds the offset moved on
+    let upload = app_state
+        .db_client
+        .lock_tus_upload(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::not_found("Upload not found"))?;
+    let upload_length = upload.upload_length;
+
+    if offset != upload.offset {
+        return Err(HttpError::new(
+            "Upload-Offset does not match the current offset",
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    if offset + body.len() as i64 > upload_length {
+        return Err(HttpError::bad_request("Chunk exceeds the Upload-Length"));
+    }
+
+    // The file's magic bytes are at the start of the first PATCH
+    if offset == 0 && !body.is_empty() {
+        validate::check_upload_header(&app_state.env, &body)?;
+    }
+
+    let chunk_hash = Sha256::digest(&body);
+    check_checksum(&headers, &chunk_hash)?;
+
+    let new_offset = offset + body.len() as i64;
+
+    if !body.is_empty() {
+        let chunk_number = upload.current_chunk + 1;
+        let chunk_key = storage::chunk_key(track_id, chunk_number);
+
+        app_state
+            .storage
+            .put(&chunk_key, body)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        upload
+            .append(ReceivedChunk {
+                number: chunk_number,
+                path: &chunk_key,
+                hash: &hex::encode(chunk_hash),
+                size: new_offset - offset,
+            })
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+    } else {
+        // Sealing below updates the row, it can't still be held
+        upload
+            .release()
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+    }
+
+    if new_offset == upload_length {
+        let total_chunks = app_state
+            .db_client
+            .seal_chunk_count(track_id)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        finish_upload(&app_state, track_id, total_chunks, None).await?;
+    }
+
+    Ok(Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header(UPLOAD_OFFSET, new_offset)
+        .body(Body::empty())
+        .unwrap())
+}
+
+// termination extension
+pub async fn terminate_upload(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Path(track_id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, HttpError> {
+    owned_track(&app_state, track_id, user.user.id).await?;
+    discard_upload(&app_state, track_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    header::HeaderName::from_static(name),
+                    HeaderValue::from_str(value).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_upload_metadata() {
+        let metadata = parse_metadata(&headers(&[(
+            UPLOAD_METADATA,
+            "filename d29ybGQuZmxhYw==, filetype YXVkaW8vZmxhYw==,is_confidential",
+        )]));
+
+        assert_eq!(metadata.len(), 3);
+        assert_eq!(metadata["filename"], "world.flac");
+        assert_eq!(metadata["filetype"], "audio/flac");
+        assert_eq!(metadata["is_confidential"], "");
+    }
+
+    #[test]
+    fn skips_undecodable_metadata() {
+        let metadata = parse_metadata(&headers(&[(
+            UPLOAD_METADATA,
+            "filename not-base64!,,title dGl0bGU=",
+        )]));
+
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata["title"], "title");
+        assert!(parse_metadata(&HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    fn reads_non_negative_numbers() {
+        let headers = headers(&[
+            (UPLOAD_OFFSET, "1024"),
+            (UPLOAD_LENGTH, "-1"),
+            (UPLOAD_DEFER_LENGTH, "one"),
+        ]);
+
+        assert_eq!(header_i64(&headers, UPLOAD_OFFSET), Some(1024));
+        assert_eq!(header_i64(&headers, UPLOAD_LENGTH), None);
+        assert_eq!(header_i64(&headers, UPLOAD_DEFER_LENGTH), None);
+        assert_eq!(header_i64(&headers, UPLOAD_METADATA), None);
+    }
+
+    #[test]
+    fn checks_sha256_checksums() {
+        let chunk_hash = Sha256::digest(b"chunk");
+        let checksum = format!("sha256 {}", STANDARD.encode(chunk_hash));
+
+        assert!(check_checksum(&HeaderMap::new(), &chunk_hash).is_ok());
+        assert!(check_checksum(&headers(&[(UPLOAD_CHECKSUM, &checksum)]), &chunk_hash).is_ok());
+
+        let other_hash = Sha256::digest(b"other chunk");
+        let mismatch = check_checksum(&headers(&[(UPLOAD_CHECKSUM, &checksum)]), &other_hash);
+        assert_eq!(mismatch.unwrap_err().status.as_u16(), 460);
+
+        let garbage = check_checksum(&headers(&[(UPLOAD_CHECKSUM, "sha256 ???")]), &chunk_hash);
+        assert_eq!(garbage.unwrap_err().status.as_u16(), 460);
+    }
+
+    #[test]
+    fn rejects_other_checksum_algorithms() {
+        let chunk_hash = Sha256::digest(b"chunk");
+
+        for checksum in ["md5 AAAA", "sha1", ""] {
+            let result = check_checksum(&headers(&[(UPLOAD_CHECKSUM, checksum)]), &chunk_hash);
+            assert_eq!(result.unwrap_err().status, StatusCode::BAD_REQUEST);
+        }
+    }
+}
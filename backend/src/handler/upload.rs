@@ -1,112 +1,467 @@
-use std::{
-    fs::{self, File, OpenOptions},
-    io::Write,
-    sync::Arc,
-};
+use std::{io::Write, sync::Arc};
 
-use axum::{extract::Multipart, response::IntoResponse, routing::post, Extension, Json, Router};
-use chrono::Duration;
-use symphonia::core::{
-    formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+use axum::{
+    extract::{Multipart, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, post},
+    Extension, Json, Router,
 };
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
 
 use crate::{
+    audio::{
+        probe::{probe_audio, AudioInfo},
+        tags::normalize_isrc,
+    },
     auth::JWTAuthMiddleware,
-    database::upload::UploadExt,
-    dtos::{Response, UploadResponse},
+    database::{
+        albums::AlbumExt,
+        jobs::JobExt,
+        track::TrackExt,
+        upload::{ReceivedChunk, UploadExt},
+        versions::VersionExt,
+    },
+    dtos::{Response, UploadResponse, UploadStatusResponse},
     error::HttpError,
+    jobs,
+    models::{TrackMetadata, TRACK_VISIBILITIES},
+    storage,
+    utils::{archive, validate},
     AppState,
 };
 
-fn sanitize_filename(filename: &str) -> String {
+pub(crate) fn sanitize_filename(filename: &str) -> String {
     filename.replace(&['/', '\\'][..], "").replace("..", "")
 }
 
-fn is_upload_complete(temp_dir: &str, total_chunks: usize) -> bool {
-    match fs::read_dir(temp_dir) {
-        Ok(entries) => entries.count() == total_chunks,
-        Err(_) => false,
+// Names a stored image after the type it really is, whatever the client called it
+pub(crate) fn image_file_name(file_name: &str, extension: &str) -> String {
+    let stem = match sanitize_filename(file_name).rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+        _ => sanitize_filename(file_name),
+    };
+
+    format!("{}.{}", stem, extension)
+}
+
+// Hashes are sent as lowercase or uppercase hex SHA-256 digests
+fn parse_sha256(value: &str) -> Option<String> {
+    let value = value.trim().to_ascii_lowercase();
+    if value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(value)
+    } else {
+        None
     }
 }
 
-fn get_audio_duration(file_path: &str) -> Result<Duration, Box<dyn std::error::Error>> {
-    // Open the audio file
-    let file = File::open(file_path)?;
-    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+// Saves the embedded cover as the thumbnail, unless the user already picked one.
+// Covers that aren't acceptable images are left out, the track itself is fine.
+async fn store_cover(
+    app_state: &AppState,
+    track_id: uuid::Uuid,
+    audio_info: &AudioInfo,
+) -> std::io::Result<Option<String>> {
+    let Some(cover) = &audio_info.cover else {
+        return Ok(None);
+    };
+    if validate::check_image(&app_state.env, &cover.data).is_err() {
+        return Ok(None);
+    }
+
+    let has_thumbnail = app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?
+        .is_some_and(|track| track.thumbnail_name.is_some());
+    if has_thumbnail {
+        return Ok(None);
+    }
+
+    let cover_name = format!("{}.{}", track_id, cover.extension);
+    app_state
+        .storage
+        .put(
+            &storage::thumbnail_key(&cover_name),
+            Bytes::copy_from_slice(&cover.data),
+        )
+        .await?;
 
-    // Create a hint to help the format registry guess the format
-    let mut hint = Hint::new();
+    Ok(Some(cover_name))
+}
 
-    // Provide the file extension as a format hint
-    if let Some(extension) = file_path.split('.').last() {
-        hint.with_extension(extension);
+pub(crate) async fn assemble_file(
+    total_chunks: usize,
+    track_id: uuid::Uuid,
+    file_hash: Option<&str>,
+    app_state: Arc<AppState>,
+) -> std::io::Result<()> {
+    let storage = app_state.storage.as_ref();
+
+    // Chunks may live on object storage, the track is put together in a local
+    // scratch file so it can be hashed and probed.
+    let scratch_path = storage::scratch_path(track_id);
+    if let Some(parent) = scratch_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
     }
+    let mut output_file = tokio::fs::File::create(&scratch_path)
+        .await?
+        .into_std()
+        .await;
 
-    // Probe the media file
-    let probed = symphonia::default::get_probe().format(
-        &hint,
-        mss,
-        &FormatOptions::default(),
-        &MetadataOptions::default(),
-    )?;
+    // Hash while writing so the content address is known once the last chunk
+    // lands. Both happen off the async workers, a chunk at a time.
+    let mut hasher = Sha256::new();
 
-    // Get the default track
-    let track = probed.format.default_track().ok_or("No track found")?;
+    for chunk_number in 0..total_chunks {
+        let chunk_key = storage::chunk_key(track_id, chunk_number as i32);
+        let chunk_data = storage::read_range(storage, &chunk_key, None).await?;
+        (output_file, hasher) = tokio::task::spawn_blocking(move || {
+            hasher.update(&chunk_data);
+            output_file.write_all(&chunk_data)?;
+            Ok::<_, std::io::Error>((output_file, hasher))
+        })
+        .await??;
+    }
+
+    // Clean up the temporary chunks
+    storage::delete_prefix(storage, &storage::temp_prefix(track_id)).await?;
 
-    // Get the time base for the track
-    let time_base = track.codec_params.time_base.ok_or("No time base")?;
+    let content_hash = hex::encode(hasher.finalize());
 
-    // Get the frame count, handling the `Result<u64, &str>`
-    let n_frames = track.codec_params.n_frames.ok_or("No frame count")?;
+    // Every chunk was verified on arrival, a mismatch here means the client
+    // sent chunks of a different file or declared the wrong hash.
+    if file_hash.is_some_and(|file_hash| file_hash != content_hash) {
+        tokio::fs::remove_file(&scratch_path).await?;
+        app_state
+            .db_client
+            .reset_upload(track_id)
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
 
-    // Calculate the duration safely after handling the result
-    let duration_seconds = (n_frames as f64 * time_base.numer as f64) / time_base.denom as f64;
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Assembled file does not match fileHash, please upload the file again",
+        ));
+    }
 
-    // Return the duration as a `Duration` object
-    Ok(Duration::seconds(duration_seconds as i64))
+    let replaced_track = app_state
+        .db_client
+        .get_replaced_track(track_id)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    if let Some(replaced_track) = replaced_track {
+        return replace_track_audio(
+            &app_state,
+            track_id,
+            replaced_track,
+            &scratch_path,
+            &content_hash,
+        )
+        .await;
+    }
+
+    // Albums come as archives, they're unpacked in the background
+    let is_zip = {
+        let scratch_path = scratch_path.clone();
+        tokio::task::spawn_blocking(move || archive::is_zip_file(&scratch_path)).await??
+    };
+    if is_zip {
+        return import_archive(&app_state, track_id, &scratch_path).await;
+    }
+
+    store_track_audio(&app_state, track_id, &scratch_path, &content_hash).await
 }
 
-async fn assemble_file(
-    temp_dir: &str,
-    file_name: &str,
-    total_chunks: usize,
+// Probes an audio file, stores it content addressed and completes the track
+// with what its tags say. The file is consumed. Audio the track can't be made
+// from is reported as InvalidData, with the track marked as failed.
+pub(crate) async fn store_track_audio(
+    app_state: &AppState,
     track_id: uuid::Uuid,
-    app_state: Arc<AppState>,
+    scratch_path: &std::path::Path,
+    content_hash: &str,
 ) -> std::io::Result<()> {
-    let output_path = format!("uploads/{}", file_name);
-    let mut output_file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .open(&output_path)?;
+    let probed = probe_blocking(scratch_path).await;
 
-    for chunk_number in 0..total_chunks {
-        let chunk_path = format!("{}/chunk_{}", temp_dir, chunk_number);
-        let chunk_data = fs::read(&chunk_path)?;
-        output_file.write_all(&chunk_data)?;
+    // Audio that breaks the upload limits isn't kept
+    if let Err(reason) = probed
+        .as_ref()
+        .map_or(Ok(()), |audio_info| validate::check_audio(&app_state.env, audio_info))
+    {
+        tokio::fs::remove_file(scratch_path).await?;
+        app_state
+            .db_client
+            .mark_failed(track_id, &reason, None)
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, reason));
     }
 
-    // Clean up the temporary chunks
-    fs::remove_dir_all(temp_dir)?;
+    store_probed_audio(app_state, track_id, scratch_path, content_hash, probed).await
+}
+
+// Probing may demux the whole file to count its frames, it runs off the async
+// workers. The probe error isn't Send, only its message is kept.
+async fn probe_blocking(scratch_path: &std::path::Path) -> Result<AudioInfo, String> {
+    let input = scratch_path.to_path_buf();
+    tokio::task::spawn_blocking(move || probe_audio(&input).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Stores audio that passed the upload limits as the track's next version
+async fn store_probed_audio(
+    app_state: &AppState,
+    track_id: uuid::Uuid,
+    scratch_path: &std::path::Path,
+    content_hash: &str,
+    probed: Result<AudioInfo, String>,
+) -> std::io::Result<()> {
+    let storage = app_state.storage.as_ref();
+    let audio_key = storage::audio_key(content_hash);
+
+    // Identical audio uploaded before (by anyone) is reused, only the track row is new.
+    // Audio that can't be processed is stored too, so nothing the user sent is lost.
+    let file_size = tokio::fs::metadata(scratch_path).await?.len() as i64;
+    let lock = app_state
+        .db_client
+        .lock_content_hash(content_hash)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    if storage.stat(&audio_key).await?.is_some() {
+        tokio::fs::remove_file(scratch_path).await?;
+    } else {
+        storage.put_file(&audio_key, scratch_path).await?;
+    }
+    lock.link_track(track_id)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    // Shared audio still counts against every owner's quota
+    app_state
+        .db_client
+        .save_file_size(track_id, file_size)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
 
-    let duration = match get_audio_duration(&output_path) {
-        Ok(duration) => duration,
+    let audio_info = match probed {
+        Ok(audio_info) => audio_info,
         Err(e) => {
-            // Convert the error to std::io::Error and return
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                e.to_string(),
-            ))?;
+            let reason = format!("Audio could not be processed: {}", e);
+            app_state
+                .db_client
+                .mark_failed(track_id, &reason, Some(content_hash))
+                .await
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, reason));
         }
     };
 
-    let duration_seconds = duration.num_seconds(); // Assuming `duration` is of type `Duration`
+    let cover_name = store_cover(app_state, track_id, &audio_info).await?;
 
     app_state
         .db_client
-        .update_status(track_id.clone(), duration_seconds)
+        .update_status(track_id, &audio_info, content_hash, cover_name.as_deref())
         .await
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
 
+    // Kept so the track can go back to this audio after it's replaced
+    app_state
+        .db_client
+        .save_track_version(track_id)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    enqueue_processing(app_state, track_id).await;
+
+    Ok(())
+}
+
+// The track already plays from the original, renditions and analysis follow
+// in the background. Without the Opus encoder there are no renditions or
+// preview to make, the original is packaged for HLS right away instead.
+pub(crate) async fn enqueue_processing(app_state: &AppState, track_id: uuid::Uuid) {
+    let kinds: &[&str] = if cfg!(feature = "opus") {
+        &[
+            jobs::TRANSCODE,
+            jobs::ANALYZE_LOUDNESS,
+            jobs::GENERATE_WAVEFORM,
+            jobs::FINGERPRINT,
+            jobs::GENERATE_PREVIEW,
+        ]
+    } else {
+        &[
+            jobs::PACKAGE_HLS,
+            jobs::ANALYZE_LOUDNESS,
+            jobs::GENERATE_WAVEFORM,
+            jobs::FINGERPRINT,
+        ]
+    };
+    for &kind in kinds {
+        if let Err(e) = app_state
+            .db_client
+            .enqueue_job(kind, Some(track_id), None)
+            .await
+        {
+            eprintln!("Failed to queue {} of {}: {}", kind, track_id, e);
+        }
+    }
+}
+
+// Gives an existing track the uploaded audio as its new version. The track
+// keeps its id, so favorites, playlists and history still point at it. Audio
+// that isn't usable fails the upload and leaves the track as it was.
+async fn replace_track_audio(
+    app_state: &AppState,
+    upload_id: uuid::Uuid,
+    track_id: uuid::Uuid,
+    scratch_path: &std::path::Path,
+    content_hash: &str,
+) -> std::io::Result<()> {
+    let probed = probe_blocking(scratch_path)
+        .await
+        .map_err(|e| format!("Audio could not be processed: {}", e))
+        .and_then(|audio_info| {
+            validate::check_audio(&app_state.env, &audio_info).map(|_| audio_info)
+        });
+
+    let audio_info = match probed {
+        Ok(audio_info) => audio_info,
+        Err(reason) => {
+            tokio::fs::remove_file(scratch_path).await?;
+            app_state
+                .db_client
+                .mark_failed(upload_id, &reason, None)
+                .await
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, reason));
+        }
+    };
+
+    let track = app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    // The same audio again, there's nothing to replace
+    if track.is_some_and(|track| track.content_hash.as_deref() != Some(content_hash)) {
+        app_state
+            .db_client
+            .reset_track_audio(track_id)
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        store_probed_audio(app_state, track_id, scratch_path, content_hash, Ok(audio_info))
+            .await?;
+    } else {
+        tokio::fs::remove_file(scratch_path).await?;
+    }
+
+    // The upload was only the way in, the track is what the client follows.
+    // Assembling uploads can't be deleted, the reset lets it go.
+    let result = async {
+        app_state.db_client.reset_upload(upload_id).await?;
+        app_state.db_client.delete_incomplete_track(upload_id).await
+    }
+    .await;
+
+    result
+        .map(|_| ())
+        .map_err(|e| std::io::Error::other(e.to_string()))
+}
+
+// The album takes over the upload's id, so the client can follow the import
+// with the id it already has. The upload's own track goes away.
+async fn import_archive(
+    app_state: &AppState,
+    track_id: uuid::Uuid,
+    scratch_path: &std::path::Path,
+) -> std::io::Result<()> {
+    let track = app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?
+        .ok_or(std::io::Error::other("Upload is gone"))?;
+    let user_id = track.user_id.ok_or(std::io::Error::other("Upload has no owner"))?;
+
+    // Until the tags say otherwise the album is named after the archive
+    let file_name = track.file_name.unwrap_or_default();
+    let title = match file_name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => &file_name,
+    };
+
+    app_state
+        .storage
+        .put_file(&storage::import_key(track_id), scratch_path)
+        .await?;
+
+    let result = async {
+        app_state
+            .db_client
+            .create_album(track_id, user_id, title)
+            .await?;
+        app_state
+            .db_client
+            .enqueue_job(jobs::IMPORT_ALBUM, None, Some(&track_id.to_string()))
+            .await?;
+
+        // Assembling uploads can't be deleted, the reset lets it go
+        app_state.db_client.reset_upload(track_id).await?;
+        app_state.db_client.delete_incomplete_track(track_id).await
+    }
+    .await;
+
+    result
+        .map(|_| ())
+        .map_err(|e| std::io::Error::other(e.to_string()))
+}
+
+// Assembles the track if every chunk is in and no other request got there first
+pub(crate) async fn finish_upload(
+    app_state: &Arc<AppState>,
+    track_id: uuid::Uuid,
+    total_chunks: i32,
+    file_hash: Option<&str>,
+) -> Result<(), HttpError> {
+    let claimed = app_state
+        .db_client
+        .claim_assembly(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if !claimed {
+        return Ok(());
+    }
+
+    if let Err(err) = assemble_file(
+        total_chunks as usize,
+        track_id,
+        file_hash,
+        app_state.clone(),
+    )
+    .await
+    {
+        // Not retryable chunk by chunk, the upload was reset or marked as failed
+        if err.kind() == std::io::ErrorKind::InvalidData {
+            return Err(HttpError::bad_request(err.to_string()));
+        }
+
+        // Let the next chunk retry pick the assembly up again
+        let _ = app_state.db_client.release_assembly(track_id).await;
+
+        return Err(HttpError::server_error(
+            "Failed to complite file".to_string(),
+        ));
+    }
+
     Ok(())
 }
 
@@ -114,23 +469,190 @@ pub fn upload_handler() -> Router {
     Router::new()
         .route("/", post(upload_chunks))
         .route("/thumbnail", post(upload_thumbnail))
+        .route("/:track_id/status", get(upload_status))
+        .route("/:track_id", delete(cancel_upload))
+}
+
+// Room for `bytes` more, and for one more track when the upload is new. Uploads
+// larger than the whole quota are 413 like any other size limit, the rest 507.
+pub(crate) async fn check_quota(
+    app_state: &AppState,
+    user_id: uuid::Uuid,
+    new_track: bool,
+    bytes: i64,
+) -> Result<(), HttpError> {
+    let usage = app_state
+        .db_client
+        .get_usage(user_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+    let storage_quota = usage.storage_quota.unwrap_or(app_state.env.storage_quota);
+    let track_quota = usage
+        .track_quota
+        .map_or(app_state.env.track_quota, i64::from);
+
+    if new_track && usage.track_count >= track_quota {
+        return Err(HttpError::new(
+            format!("You have reached your limit of {} tracks", track_quota),
+            StatusCode::INSUFFICIENT_STORAGE,
+        ));
+    }
+
+    if bytes > storage_quota {
+        return Err(HttpError::new(
+            format!(
+                "File is larger than your {} storage quota",
+                validate::format_size(storage_quota)
+            ),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ));
+    }
+
+    let used = usage.stored_bytes + usage.pending_bytes;
+    if used + bytes > storage_quota {
+        return Err(HttpError::new(
+            format!(
+                "Storage quota exceeded, {} of {} used",
+                validate::format_size(used),
+                validate::format_size(storage_quota)
+            ),
+            StatusCode::INSUFFICIENT_STORAGE,
+        ));
+    }
+
+    Ok(())
+}
+
+// Drops an upload that hasn't completed along with its chunks
+pub(crate) async fn discard_upload(
+    app_state: &AppState,
+    track_id: uuid::Uuid,
+) -> Result<(), HttpError> {
+    let deleted = app_state
+        .db_client
+        .delete_incomplete_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    if !deleted {
+        return Err(HttpError::new(
+            "Upload is already complete",
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    storage::delete_upload_files(app_state.storage.as_ref(), track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(())
+}
+
+pub async fn cancel_upload(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Path(track_id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, HttpError> {
+    app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|track| track.user_id == Some(user.user.id))
+        .ok_or(HttpError::not_found("Track not found"))?;
+
+    discard_upload(&app_state, track_id).await?;
+
+    let response = Response {
+        status: "success",
+        message: "Upload cancelled".to_string(),
+    };
+
+    Ok(Json(response))
+}
+
+// Lets a client resume an interrupted upload by sending only what is missing
+pub async fn upload_status(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Path(track_id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, HttpError> {
+    let track = app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|track| track.user_id == Some(user.user.id))
+        .ok_or(HttpError::not_found("Track not found"))?;
+
+    if let Some(status @ ("complete" | "failed")) = track.upload_status.as_deref() {
+        return Ok(Json(UploadStatusResponse {
+            track_id,
+            upload_status: status.to_string(),
+            total_chunks: 0,
+            uploaded_chunks: 0,
+            missing_chunks: Vec::new(),
+            failure_reason: track.failure_reason,
+            duplicate_of: track.duplicate_of,
+        }));
+    }
+
+    let audio_file = app_state
+        .db_client
+        .get_audio_file(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::not_found("No upload in progress for this track"))?;
+
+    let uploaded = app_state
+        .db_client
+        .get_uploaded_chunks(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let missing_chunks = (0..audio_file.total_chunks)
+        .filter(|chunk_number| uploaded.binary_search(chunk_number).is_err())
+        .collect();
+
+    Ok(Json(UploadStatusResponse {
+        track_id,
+        upload_status: audio_file
+            .upload_status
+            .unwrap_or_else(|| "incomplete".to_string()),
+        total_chunks: audio_file.total_chunks,
+        uploaded_chunks: uploaded.len() as i32,
+        missing_chunks,
+        failure_reason: None,
+        duplicate_of: None,
+    }))
 }
 
 pub async fn upload_chunks(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user): Extension<JWTAuthMiddleware>,
-    mut multipart: Multipart,
+    multipart: Multipart,
 ) -> Result<impl IntoResponse, HttpError> {
-    let user = &user.user;
-    let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
+    let response = receive_chunk(&app_state, user.user.id, multipart, None).await?;
+
+    Ok(Json(response))
+}
+
+// One chunk of the chunked protocol. With `replaces` the upload is new audio
+// for that track rather than a track of its own.
+pub(crate) async fn receive_chunk(
+    app_state: &Arc<AppState>,
+    user_id: uuid::Uuid,
+    mut multipart: Multipart,
+    replaces: Option<uuid::Uuid>,
+) -> Result<UploadResponse, HttpError> {
     let mut file_name = String::new();
     let mut chunk_number = 0;
     let mut total_chunks = 0;
     let mut track_id: Option<uuid::Uuid> = None;
+    let mut chunk_hash = String::new();
+    let mut file_hash = String::new();
     let mut chunk_data = Vec::new();
 
-    let mut uploaded_chunks = 0;
-
     while let Ok(Some(field)) = multipart.next_field().await {
         let field_name = field.name().unwrap_or_default().to_string();
         match field_name.as_str() {
@@ -146,7 +668,16 @@ pub async fn upload_chunks(
             }
             "trackId" => {
                 let id = field.text().await.unwrap_or_default();
-                track_id = Some(uuid::Uuid::parse_str(&id).unwrap());
+                track_id = Some(
+                    uuid::Uuid::parse_str(&id)
+                        .map_err(|_| HttpError::bad_request("Invalid track ID format"))?,
+                );
+            }
+            "chunkHash" => {
+                chunk_hash = field.text().await.unwrap_or_default();
+            }
+            "fileHash" => {
+                file_hash = field.text().await.unwrap_or_default();
             }
             "chunk" => match field.bytes().await {
                 Ok(bytes) => chunk_data = bytes.to_vec(),
@@ -173,117 +704,288 @@ pub async fn upload_chunks(
         ));
     }
 
+    let (Some(chunk_hash), Some(file_hash)) = (parse_sha256(&chunk_hash), parse_sha256(&file_hash))
+    else {
+        return Err(HttpError::bad_request(
+            "chunkHash and fileHash must be hex encoded SHA-256 hashes",
+        ));
+    };
+
+    // Nothing is recorded for a chunk that got corrupted on the way, the client
+    // can simply send it again.
+    if hex::encode(Sha256::digest(&chunk_data)) != chunk_hash {
+        return Err(HttpError::new(
+            format!(
+                "Chunk {} failed checksum verification, please retry it",
+                chunk_number
+            ),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ));
+    }
+
+    if total_chunks <= 0 || chunk_number < 0 || chunk_number >= total_chunks {
+        return Err(HttpError::bad_request(
+            "chunkNumber must be between 0 and totalChunks - 1",
+        ));
+    }
+
+    // Only the first chunk starts with the container's magic bytes
+    if chunk_number == 0 {
+        // A track's audio can't be replaced by an album
+        if replaces.is_some() {
+            validate::check_audio_header(&app_state.env, &chunk_data)?;
+        } else {
+            validate::check_upload_header(&app_state.env, &chunk_data)?;
+        }
+
+        // Chunks all have the size of the first one except the last, which
+        // has at least a byte. Catches most oversized files right away.
+        let min_size = chunk_data.len() as i64 * (total_chunks as i64 - 1) + 1;
+        validate::check_upload_size(&app_state.env, min_size.max(chunk_data.len() as i64))?;
+    }
+
+    let mut resent_bytes = 0;
     if let Some(track_id) = track_id {
+        let track = app_state
+            .db_client
+            .get_track(track_id)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?
+            .filter(|track| track.user_id == Some(user_id))
+            .ok_or(HttpError::not_found("Track not found"))?;
+
+        // Chunks of a replacement only go to the endpoint of its track
+        let replaced_track = app_state
+            .db_client
+            .get_replaced_track(track_id)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+        if replaced_track != replaces {
+            return Err(HttpError::not_found("Track not found"));
+        }
+
+        match track.upload_status.as_deref() {
+            Some("complete") => {
+                return Err(HttpError::new(
+                    "Track upload is already complete",
+                    StatusCode::CONFLICT,
+                ));
+            }
+            Some("failed") => {
+                return Err(HttpError::new(
+                    "Track processing failed, please upload the file again",
+                    StatusCode::CONFLICT,
+                ));
+            }
+            _ => {}
+        }
+
         if let Some(existing_file) = app_state
             .db_client
             .get_audio_file(track_id)
             .await
             .map_err(|e| HttpError::server_error(e.to_string()))?
         {
-            uploaded_chunks = existing_file.uploaded_chunks; // Get the uploaded_chunks value
+            if existing_file.total_chunks != total_chunks {
+                return Err(HttpError::bad_request(
+                    "totalChunks does not match the one sent with the first chunk",
+                ));
+            }
+
+            // The hash declared with the first chunk is the one the file is checked against
+            if existing_file
+                .file_hash
+                .is_some_and(|expected_hash| expected_hash != file_hash)
+            {
+                return Err(HttpError::bad_request(
+                    "fileHash does not match the one sent with the first chunk",
+                ));
+            }
         }
+
+        // A retried chunk takes the place of the one already counted
+        resent_bytes = app_state
+            .db_client
+            .get_chunk_size(track_id, chunk_number)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?
+            .unwrap_or_default();
     }
 
-    uploaded_chunks += 1;
+    // Checked before the track is created, so a full account gets no new ones
+    let new_track = track_id.is_none() && replaces.is_none();
+    let added_bytes = (chunk_data.len() as i64 - resent_bytes).max(0);
+    check_quota(app_state, user_id, new_track, added_bytes).await?;
 
-    if chunk_number == 0 {
-        track_id = Some(
-            app_state
-                .db_client
-                .upload_file(user_id.clone(), &file_name)
-                .await
-                .map_err(|e| HttpError::server_error(e.to_string()))?,
-        );
+    // The first chunk creates the track, a retried first chunk carries its id
+    if chunk_number == 0 && track_id.is_none() {
+        let created = match replaces {
+            Some(replaces) => {
+                app_state
+                    .db_client
+                    .create_replacement(user_id, &file_name, replaces)
+                    .await
+            }
+            None => app_state.db_client.upload_file(user_id, &file_name).await,
+        };
+        track_id = Some(created.map_err(|e| HttpError::server_error(e.to_string()))?);
     }
     let track_id = track_id.ok_or(HttpError::bad_request("track id missing"))?;
 
-    let temp_dir = format!("uploads/temp/{}", &file_name);
-    if let Err(_err) = fs::create_dir_all(&temp_dir) {
-        return Err(HttpError::server_error(
-            "Failed to create temp directory".to_string(),
-        ));
-    }
+    let chuck_path = storage::chunk_key(track_id, chunk_number);
+    let chunk_size = chunk_data.len() as i64;
 
-    let chuck_path = format!("{}/chunk_{}", temp_dir, chunk_number);
-    let mut file = match File::create(&chuck_path) {
-        Ok(f) => f,
-        Err(_err) => {
-            return Err(HttpError::server_error("failed to create chuck file"));
-        }
-    };
+    let uploaded_size = app_state
+        .db_client
+        .get_upload_offset(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // A retried chunk replaces its earlier copy. The chunks already received
+    // are kept, the client can still cancel or the upload expires.
+    validate::check_upload_size(&app_state.env, uploaded_size - resent_bytes + chunk_size)?;
+
+    // Write the chunk before recording it, a recorded chunk is always readable
+    if let Err(_err) = app_state
+        .storage
+        .put(&chuck_path, Bytes::from(chunk_data))
+        .await
+    {
+        return Err(HttpError::server_error("failed to create chuck file"));
+    }
 
     app_state
         .db_client
         .upload_chuck(
-            track_id.clone(),
+            track_id,
             total_chunks,
-            uploaded_chunks,
-            chunk_number as i32,
-            &chuck_path,
+            ReceivedChunk {
+                number: chunk_number,
+                path: &chuck_path,
+                hash: &chunk_hash,
+                size: chunk_size,
+            },
+            Some(&file_hash),
         )
         .await
         .map_err(|e| HttpError::server_error(e.to_string()))?;
 
-    if let Err(_err) = file.write_all(&chunk_data) {
-        return Err(HttpError::server_error("failed to create chuck file"));
+    finish_upload(app_state, track_id, total_chunks, Some(&file_hash)).await?;
+
+    Ok(UploadResponse { track_id })
+}
+
+pub(crate) fn text_field(name: &str, value: String) -> Result<Option<String>, HttpError> {
+    let value = value.trim();
+    if value.chars().count() > 255 {
+        return Err(HttpError::bad_request(format!("{} is too long", name)));
     }
 
-    if is_upload_complete(&temp_dir, total_chunks as usize) {
-        if let Err(_err) = assemble_file(
-            &temp_dir,
-            &file_name,
-            total_chunks as usize,
-            track_id.clone(),
-            app_state,
-        )
-        .await
-        {
-            return Err(HttpError::server_error(
-                "Failed to complite file".to_string(),
-            ));
-        }
+    Ok((!value.is_empty()).then(|| value.to_string()))
+}
+
+fn number_field(name: &str, value: String) -> Result<Option<i32>, HttpError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
     }
 
-    Ok(Json(UploadResponse { track_id }))
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| HttpError::bad_request(format!("{} must be a number", name)))
 }
 
+// Every field is optional, only the ones the user filled in replace what the
+// track already has (possibly read from the file's tags).
 pub async fn upload_thumbnail(
     Extension(app_state): Extension<Arc<AppState>>,
-    Extension(_user): Extension<JWTAuthMiddleware>,
+    Extension(user): Extension<JWTAuthMiddleware>,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, HttpError> {
-    // let user = &user.user;
-    // let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
-
     let mut track_id: Option<uuid::Uuid> = None;
-    let mut title = String::new();
-    let mut artist = String::new();
+    let mut metadata = TrackMetadata::default();
+    let mut visibility: Option<String> = None;
+    let mut preview_start: Option<i32> = None;
     let mut thumbnail_name = String::new();
     let mut thumbnail_data = Vec::new();
 
-    while let Some(field) = multipart.next_field().await.unwrap() {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| HttpError::bad_request(e.to_string()))?
+    {
         let field_name = field.name().unwrap_or_default().to_string();
+        if field_name == "thumbnail" {
+            thumbnail_name = field.file_name().unwrap_or_default().to_string();
+            match field.bytes().await {
+                Ok(bytes) => thumbnail_data = bytes.to_vec(),
+                Err(err) => {
+                    eprintln!("Error reading chunk data: {:?}", err);
+                    return Err(HttpError::bad_request("File Upload failed"));
+                }
+            }
+            continue;
+        }
+
+        let value = field
+            .text()
+            .await
+            .map_err(|e| HttpError::bad_request(e.to_string()))?;
         match field_name.as_str() {
             "track_id" => {
                 track_id = Some(
-                    uuid::Uuid::parse_str(&field.text().await.unwrap())
+                    uuid::Uuid::parse_str(&value)
                         .map_err(|_| HttpError::bad_request("Invalid track ID format"))?,
                 );
             }
             "title" => {
-                title = field.text().await.unwrap();
+                metadata.title = text_field("title", value)?;
             }
             "artist" => {
-                artist = field.text().await.unwrap();
+                metadata.artist = text_field("artist", value)?;
+            }
+            "album" => {
+                metadata.album = text_field("album", value)?;
+            }
+            "album_artist" => {
+                metadata.album_artist = text_field("album_artist", value)?;
+            }
+            "genre" => {
+                metadata.genre = text_field("genre", value)?;
+            }
+            "track_number" => {
+                metadata.track_number = number_field("track_number", value)?;
+            }
+            "disc_number" => {
+                metadata.disc_number = number_field("disc_number", value)?;
+            }
+            "year" => {
+                metadata.year = number_field("year", value)?;
             }
-            "thumbnail" => {
-                thumbnail_name = field.file_name().unwrap_or_default().to_string();
-                match field.bytes().await {
-                    Ok(bytes) => thumbnail_data = bytes.to_vec(),
-                    Err(err) => {
-                        eprintln!("Error reading chunk data: {:?}", err);
-                        return Err(HttpError::bad_request("File Upload failed"));
-                    }
+            "isrc" => {
+                if !value.trim().is_empty() {
+                    metadata.isrc = Some(
+                        normalize_isrc(&value)
+                            .ok_or(HttpError::bad_request("isrc must be 12 letters and digits"))?,
+                    );
+                }
+            }
+            "lyrics" => {
+                metadata.lyrics = (!value.trim().is_empty()).then_some(value);
+            }
+            "visibility" => {
+                if !TRACK_VISIBILITIES.contains(&value.as_str()) {
+                    return Err(HttpError::bad_request(
+                        "Visibility must be one of public, unlisted or private",
+                    ));
+                }
+                visibility = Some(value);
+            }
+            "preview_start" => {
+                preview_start = number_field("preview_start", value)?;
+                if preview_start.is_some_and(|start| start < 0) {
+                    return Err(HttpError::bad_request("preview_start can't be negative"));
                 }
             }
             _ => return Err(HttpError::bad_request("File Upload failed")),
@@ -292,33 +994,56 @@ pub async fn upload_thumbnail(
 
     let track_id = track_id.ok_or(HttpError::bad_request("track id missing"))?;
 
-    if thumbnail_name.is_empty() || thumbnail_data.is_empty() {
-        return Err(HttpError::bad_request("Thumbnail is missing"));
-    }
+    let track = app_state
+        .db_client
+        .get_track(track_id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .filter(|track| track.user_id == Some(user.user.id))
+        .ok_or(HttpError::not_found("Track not found"))?;
 
-    let thumbnail_dir = format!("assets/images/");
-    if let Err(_err) = fs::create_dir_all(&thumbnail_dir) {
-        return Err(HttpError::server_error("Createing failed"));
-    }
+    let thumbnail_name = if thumbnail_name.is_empty() || thumbnail_data.is_empty() {
+        None
+    } else {
+        let extension = validate::check_image(&app_state.env, &thumbnail_data)?;
+        Some(image_file_name(&thumbnail_name, extension))
+    };
 
-    let thumbnail_file_path = format!("{}/{}", &thumbnail_dir, &thumbnail_name);
-    let mut file = match File::create(&thumbnail_file_path) {
-        Ok(f) => f,
-        Err(err) => {
+    if let Some(thumbnail_name) = &thumbnail_name {
+        if let Err(err) = app_state
+            .storage
+            .put(
+                &storage::thumbnail_key(thumbnail_name),
+                Bytes::from(thumbnail_data),
+            )
+            .await
+        {
             return Err(HttpError::server_error(err.to_string()));
         }
-    };
-
-    if let Err(_err) = file.write_all(&thumbnail_data) {
-        return Err(HttpError::server_error("Createing failed"));
     }
 
     app_state
         .db_client
-        .upload_thumbnail(track_id.clone(), &thumbnail_name, &title, &artist)
+        .upload_thumbnail(
+            track_id,
+            thumbnail_name.as_deref(),
+            &metadata,
+            visibility.as_deref(),
+            preview_start,
+        )
         .await
         .map_err(|e| HttpError::server_error(e.to_string()))?;
 
+    // Incomplete tracks get their preview once the upload is assembled
+    let moved = preview_start.is_some() && preview_start != track.preview_start;
+    if cfg!(feature = "opus") && moved && track.upload_status.as_deref() == Some("complete") {
+        app_state
+            .db_client
+            .enqueue_job(jobs::GENERATE_PREVIEW, Some(track_id), None)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))?;
+    }
+
     let response = Response {
         status: "success",
         message: "Thumbnail updated successfull!".to_string(),
@@ -9,7 +9,7 @@ use axum::{
 };
 use validator::Validate;
 
-use crate::{auth::JWTAuthMiddleware, database::users::UserExt, dtos::{FilterUserDto, NameUpdateDto, Response, UserData, UserPasswordUpdateDto, UserResponseDto}, error::{ErrorMessage, HttpError}, utils::password, AppState};
+use crate::{auth::JWTAuthMiddleware, database::{upload::UploadExt, users::UserExt}, dtos::{FilterUserDto, NameUpdateDto, Response, StreamQualityUpdateDto, UsageDto, UsageResponseDto, UserData, UserPasswordUpdateDto, UserResponseDto}, error::{ErrorMessage, HttpError}, models::STREAM_QUALITIES, utils::password, AppState};
 
 pub fn users_handler() -> Router {
     Router::new()
@@ -19,6 +19,8 @@ pub fn users_handler() -> Router {
     )
     .route("/name", put(update_user_name))
     .route("/password", put(update_user_password))
+    .route("/stream-quality", put(update_stream_quality))
+    .route("/me/usage", get(get_usage))
 }
 
 pub async fn get_me(
@@ -107,5 +109,56 @@ pub async fn update_user_password(
         status: "success",
     };
 
+    Ok(Json(response))
+}
+
+pub async fn update_stream_quality(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    body: Json<StreamQualityUpdateDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    if !STREAM_QUALITIES.contains(&body.quality.as_str()) {
+        return Err(HttpError::bad_request(
+            "Quality must be one of auto, original, lossless, high, low or normalized",
+        ));
+    }
+
+    let result = app_state.db_client
+        .update_stream_quality(user.user.id, &body.quality)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let filtered_user = FilterUserDto::filter_user(&result);
+
+    let response = UserResponseDto {
+        data: UserData {
+            user: filtered_user,
+        },
+        status: "success".to_string()
+    };
+
+    Ok(Json(response))
+}
+
+pub async fn get_usage(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let usage = app_state.db_client
+        .get_usage(user.user.id)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let response = UsageResponseDto {
+        data: UsageDto {
+            used_bytes: usage.stored_bytes + usage.pending_bytes,
+            pending_bytes: usage.pending_bytes,
+            storage_quota: usage.storage_quota.unwrap_or(app_state.env.storage_quota),
+            track_count: usage.track_count,
+            track_quota: usage.track_quota.map_or(app_state.env.track_quota, i64::from),
+        },
+        status: "success".to_string()
+    };
+
     Ok(Json(response))
 }
\ No newline at end of file
@@ -0,0 +1,240 @@
+use std::{collections::HashSet, path::Path};
+
+use bytes::Bytes;
+
+use crate::{
+    database::{albums::AlbumExt, upload::UploadExt},
+    handler::upload::{check_quota, sanitize_filename, store_track_audio},
+    jobs::JobResult,
+    models::{Album, Job},
+    storage,
+    utils::{
+        archive::{self, ArchiveEntry},
+        validate,
+    },
+    AppState,
+};
+
+// Files imported as tracks, whatever their content turns out to be
+pub(crate) const AUDIO_EXTENSIONS: [&str; 9] = [
+    "mp3", "flac", "ogg", "oga", "opus", "wav", "aac", "m4a", "mp4",
+];
+const IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "webp", "gif"];
+// Image names that say they're the front cover, in order of preference
+const COVER_NAMES: [&str; 3] = ["cover", "front", "folder"];
+
+pub async fn import_album(app_state: &AppState, job: &Job) -> JobResult {
+    let album_id: uuid::Uuid = job
+        .payload
+        .as_deref()
+        .ok_or("Album import job without an album")?
+        .parse()?;
+
+    // Deleted since the job was queued, nothing to do
+    let Some(album) = app_state.db_client.get_album(album_id).await? else {
+        return Ok(());
+    };
+    if album.import_status != "pending" {
+        return Ok(());
+    }
+
+    let workdir = std::env::temp_dir()
+        .join("music-platform")
+        .join(job.id.to_string());
+    tokio::fs::create_dir_all(&workdir).await?;
+    let result = import(app_state, &album, &workdir).await;
+    let _ = tokio::fs::remove_dir_all(&workdir).await;
+    result?;
+
+    app_state.db_client.finish_album_import(album_id).await?;
+    app_state
+        .storage
+        .delete(&storage::import_key(album_id))
+        .await?;
+
+    Ok(())
+}
+
+async fn import(app_state: &AppState, album: &Album, workdir: &Path) -> JobResult {
+    let archive_path = workdir.join("archive.zip");
+    storage::download_to_file(
+        app_state.storage.as_ref(),
+        &storage::import_key(album.id),
+        &archive_path,
+    )
+    .await?;
+
+    let entries = {
+        let archive_path = archive_path.clone();
+        tokio::task::spawn_blocking(move || archive::list_entries(&archive_path)).await??
+    };
+
+    // A retried job picks up after the files it already got through
+    let done: HashSet<String> = app_state
+        .db_client
+        .get_album_files(album.id)
+        .await?
+        .into_iter()
+        .map(|file| file.path)
+        .collect();
+
+    let cover_name = match album.thumbnail_name.clone() {
+        Some(cover_name) => Some(cover_name),
+        None => store_album_cover(app_state, album, &archive_path, &entries).await?,
+    };
+
+    for entry in &entries {
+        if done.contains(&entry.path) || !AUDIO_EXTENSIONS.contains(&entry.extension().as_str()) {
+            continue;
+        }
+
+        let output = workdir.join(format!("{}.{}", entry.index, entry.extension()));
+        match import_track(app_state, album, &archive_path, entry, &output).await {
+            Ok(track_id) => {
+                let file_name = entry.file_name();
+                let title = file_name
+                    .rsplit_once('.')
+                    .map_or(file_name, |(stem, _)| stem);
+                app_state
+                    .db_client
+                    .add_album_track(album.id, track_id, title, cover_name.as_deref())
+                    .await?;
+            }
+            Err((track_id, message)) => {
+                app_state
+                    .db_client
+                    .save_album_file(album.id, &entry.path, track_id, Some(&message))
+                    .await?;
+            }
+        }
+        let _ = tokio::fs::remove_file(&output).await;
+    }
+
+    Ok(())
+}
+
+// The first image whose name says it's the cover, or else the only one
+async fn store_album_cover(
+    app_state: &AppState,
+    album: &Album,
+    archive_path: &Path,
+    entries: &[ArchiveEntry],
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let images: Vec<&ArchiveEntry> = entries
+        .iter()
+        .filter(|entry| IMAGE_EXTENSIONS.contains(&entry.extension().as_str()))
+        .collect();
+
+    let cover = COVER_NAMES
+        .iter()
+        .find_map(|name| {
+            images
+                .iter()
+                .find(|entry| entry.file_name().to_ascii_lowercase().contains(name))
+        })
+        .or(images.first().filter(|_| images.len() == 1));
+    let Some(cover) = cover else {
+        return Ok(None);
+    };
+
+    let output = archive_path.with_file_name("cover");
+    let extracted = {
+        let (archive_path, output) = (archive_path.to_path_buf(), output.clone());
+        let (index, max_size) = (cover.index, app_state.env.max_image_size as u64);
+        tokio::task::spawn_blocking(move || {
+            archive::extract_entry(&archive_path, index, &output, max_size)
+        })
+        .await?
+    };
+    let data = match extracted {
+        Ok(_) => Bytes::from(tokio::fs::read(&output).await?),
+        // Too large, the album just goes without
+        Err(_) => return Ok(None),
+    };
+    let _ = tokio::fs::remove_file(&output).await;
+
+    let Ok(extension) = validate::check_image(&app_state.env, &data) else {
+        return Ok(None);
+    };
+
+    let cover_name = format!("{}.{}", album.id, extension);
+    app_state
+        .storage
+        .put(&storage::thumbnail_key(&cover_name), data)
+        .await?;
+    app_state
+        .db_client
+        .set_album_cover(album.id, &cover_name)
+        .await?;
+
+    Ok(Some(cover_name))
+}
+
+// Makes a track of the entry the same way an upload of the file would. The
+// error is what the album's file report says about it, with the track if the
+// audio was kept as a failed one.
+async fn import_track(
+    app_state: &AppState,
+    album: &Album,
+    archive_path: &Path,
+    entry: &ArchiveEntry,
+    output: &Path,
+) -> Result<uuid::Uuid, (Option<uuid::Uuid>, String)> {
+    validate::check_upload_size(&app_state.env, entry.size as i64)
+        .map_err(|e| (None, e.message))?;
+    check_quota(app_state, album.user_id, true, entry.size as i64)
+        .await
+        .map_err(|e| (None, e.message))?;
+
+    let content_hash = {
+        let (archive_path, output) = (archive_path.to_path_buf(), output.to_path_buf());
+        // The declared size is what the limits and the quota were checked against
+        let (index, max_size) = (entry.index, entry.size);
+        tokio::task::spawn_blocking(move || {
+            archive::extract_entry(&archive_path, index, &output, max_size)
+        })
+        .await
+        .map_err(|e| (None, e.to_string()))?
+        .map_err(|e| (None, e.to_string()))?
+    };
+
+    let header = read_header(output)
+        .await
+        .map_err(|e| (None, e.to_string()))?;
+    validate::check_audio_header(&app_state.env, &header).map_err(|e| (None, e.message))?;
+
+    let track_id = app_state
+        .db_client
+        .upload_file(album.user_id, &sanitize_filename(entry.file_name()))
+        .await
+        .map_err(|e| (None, e.to_string()))?;
+
+    // Recorded first, so a retry doesn't make the track twice
+    app_state
+        .db_client
+        .save_album_file(album.id, &entry.path, Some(track_id), None)
+        .await
+        .map_err(|e| (None, e.to_string()))?;
+
+    if let Err(e) = store_track_audio(app_state, track_id, output, &content_hash).await {
+        // Audio that can't be used is kept as a failed track, like uploads are
+        if e.kind() == std::io::ErrorKind::InvalidData {
+            return Err((Some(track_id), e.to_string()));
+        }
+
+        let _ = app_state.db_client.delete_incomplete_track(track_id).await;
+        return Err((None, e.to_string()));
+    }
+
+    Ok(track_id)
+}
+
+// Enough of a file to look past an ID3 tag, like the first chunk of an upload
+pub(crate) async fn read_header(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut header = vec![0; 64 * 1024];
+    let mut file = tokio::fs::File::open(path).await?;
+    let read = tokio::io::AsyncReadExt::read(&mut file, &mut header).await?;
+    header.truncate(read);
+
+    Ok(header)
+}
@@ -0,0 +1,92 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use chrono::Utc;
+
+use crate::{database::upload::UploadExt, storage, AppState};
+
+const GC_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+// What a collection freed
+#[derive(Debug, Default)]
+pub struct Reclaimed {
+    pub uploads: usize,
+    pub bytes: u64,
+}
+
+// Periodically drops uploads that were started but never finished, so their
+// chunks don't pile up in storage and they stop showing up as resumable
+pub fn spawn_upload_gc(app_state: Arc<AppState>) {
+    tokio::spawn(async move {
+        loop {
+            match collect_abandoned_uploads(&app_state).await {
+                Ok(Reclaimed { uploads: 0, .. }) => {}
+                Ok(reclaimed) => println!(
+                    "Expired {} abandoned uploads, reclaimed {} bytes",
+                    reclaimed.uploads, reclaimed.bytes
+                ),
+                Err(e) => eprintln!("Failed to collect abandoned uploads: {}", e),
+            }
+
+            tokio::time::sleep(GC_INTERVAL).await;
+        }
+    });
+}
+
+pub async fn collect_abandoned_uploads(
+    app_state: &AppState,
+) -> Result<Reclaimed, Box<dyn std::error::Error + Send + Sync>> {
+    let ttl = Duration::from_secs(app_state.env.upload_ttl);
+    let storage = app_state.storage.as_ref();
+    let mut reclaimed = Reclaimed::default();
+
+    // Rows first, a chunk arriving now finds no upload instead of one without
+    // its earlier chunks
+    for track_id in app_state.db_client.expire_incomplete_uploads(ttl).await? {
+        reclaimed.uploads += 1;
+        reclaimed.bytes += storage::delete_upload_files(storage, track_id).await?;
+    }
+
+    // Chunks left without an upload: cancellations that failed halfway, and
+    // directories from before chunks were kept per track
+    let objects = storage.list(storage::TEMP_PREFIX).await?;
+    let directories: HashSet<&str> = objects
+        .iter()
+        .filter_map(|object| upload_directory(&object.key))
+        .collect();
+    let track_ids: Vec<uuid::Uuid> = directories
+        .iter()
+        .filter_map(|directory| uuid::Uuid::parse_str(directory).ok())
+        .collect();
+    let active: HashSet<String> = app_state
+        .db_client
+        .get_incomplete_track_ids(&track_ids)
+        .await?
+        .into_iter()
+        .map(|track_id| track_id.to_string())
+        .collect();
+
+    let expired_before = Utc::now() - ttl;
+    let mut orphaned = HashSet::new();
+    for object in &objects {
+        let Some(directory) = upload_directory(&object.key) else {
+            continue;
+        };
+        if active.contains(directory) || object.last_modified >= expired_before {
+            continue;
+        }
+
+        storage.delete(&object.key).await?;
+        orphaned.insert(directory);
+        reclaimed.bytes += object.size;
+    }
+    reclaimed.uploads += orphaned.len();
+
+    Ok(reclaimed)
+}
+
+// `uploads/temp/{directory}/chunk_0`
+fn upload_directory(key: &str) -> Option<&str> {
+    key.strip_prefix(storage::TEMP_PREFIX)?
+        .split_once('/')
+        .map(|(directory, _)| directory)
+}
@@ -0,0 +1,91 @@
+use crate::{
+    audio::fingerprint::{fingerprint_audio, is_distinctive, similarity},
+    database::track::{FingerprintCandidate, TrackExt},
+    jobs::JobResult,
+    models::{Job, Track},
+    storage, AppState,
+};
+
+// Re-encodes of the same recording stay above 0.9, different songs around 0.55
+pub const DUPLICATE_SIMILARITY: f64 = 0.75;
+
+pub async fn fingerprint(app_state: &AppState, job: &Job) -> JobResult {
+    let track_id = job.track_id.ok_or("Fingerprint job without a track")?;
+
+    // Deleted or re-uploaded since the job was queued, nothing to do
+    let Some(track) = app_state.db_client.get_track(track_id).await? else {
+        return Ok(());
+    };
+    if track.upload_status.as_deref() != Some("complete") {
+        return Ok(());
+    }
+
+    let content_hash = track
+        .content_hash
+        .clone()
+        .ok_or("Track audio is not content addressed")?;
+
+    let source = std::env::temp_dir()
+        .join("music-platform")
+        .join(format!("{}.source", job.id));
+    let fingerprint = async {
+        storage::download_to_file(
+            app_state.storage.as_ref(),
+            &storage::audio_key(&content_hash),
+            &source,
+        )
+        .await?;
+
+        let input = source.clone();
+        tokio::task::spawn_blocking(move || fingerprint_audio(&input)).await?
+    }
+    .await;
+    let _ = tokio::fs::remove_file(&source).await;
+    let fingerprint = fingerprint?;
+
+    app_state
+        .db_client
+        .save_fingerprint(track_id, &fingerprint)
+        .await?;
+
+    let duplicates = find_duplicates(app_state, &track, &fingerprint).await?;
+    app_state
+        .db_client
+        .set_duplicate_of(track_id, duplicates.first().map(|(track, _)| track.id))
+        .await?;
+
+    Ok(())
+}
+
+// Earlier tracks with the same recording, closest then oldest first. The same
+// file is always a duplicate, whatever its fingerprint.
+pub async fn find_duplicates(
+    app_state: &AppState,
+    track: &Track,
+    fingerprint: &[u32],
+) -> Result<Vec<(FingerprintCandidate, f64)>, sqlx::Error> {
+    let candidates = app_state
+        .db_client
+        .get_fingerprint_candidates(track.id)
+        .await?;
+    let distinctive = is_distinctive(fingerprint);
+
+    let mut duplicates: Vec<(FingerprintCandidate, f64)> = candidates
+        .into_iter()
+        .map(|candidate| {
+            let score =
+                if track.content_hash.is_some() && candidate.content_hash == track.content_hash {
+                    1.0
+                } else if distinctive && is_distinctive(&candidate.fingerprint) {
+                    similarity(fingerprint, &candidate.fingerprint)
+                } else {
+                    0.0
+                };
+            (candidate, score)
+        })
+        .filter(|(_, score)| *score >= DUPLICATE_SIMILARITY)
+        .collect();
+    duplicates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    Ok(duplicates)
+}
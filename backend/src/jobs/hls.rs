@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use bytes::Bytes;
+
+use crate::{
+    audio::hls::{self, HlsVariant},
+    database::{
+        renditions::{NewRendition, RenditionExt},
+        track::TrackExt,
+    },
+    jobs::JobResult,
+    models::Job,
+    storage, AppState,
+};
+
+// Renditions that become HLS variants, best first. The original follows when
+// its codec can go into fMP4 as it is.
+const VARIANT_QUALITIES: [&str; 2] = ["high", "low"];
+
+pub async fn package_hls(app_state: &AppState, job: &Job) -> JobResult {
+    let track_id = job.track_id.ok_or("HLS job without a track")?;
+
+    // Deleted or re-uploaded since the job was queued, nothing to do
+    let Some(track) = app_state.db_client.get_track(track_id).await? else {
+        return Ok(());
+    };
+    if track.upload_status.as_deref() != Some("complete") {
+        return Ok(());
+    }
+
+    let content_hash = track
+        .content_hash
+        .clone()
+        .ok_or("Track audio is not content addressed")?;
+    let storage = app_state.storage.as_ref();
+    let master_key = storage::hls_key(&content_hash, hls::MASTER_PLAYLIST);
+
+    // Another track with the same audio may have packaged it already
+    if storage.stat(&master_key).await?.is_none() {
+        let renditions = app_state.db_client.get_renditions(track_id).await?;
+
+        let mut sources: Vec<(String, String)> = VARIANT_QUALITIES
+            .iter()
+            .filter_map(|quality| {
+                renditions
+                    .iter()
+                    .find(|rendition| {
+                        rendition.quality == *quality
+                            && hls::PACKAGEABLE_CODECS.contains(&rendition.codec.as_str())
+                    })
+                    .map(|rendition| (quality.to_string(), rendition.storage_key.clone()))
+            })
+            .collect();
+        if hls::PACKAGEABLE_CODECS.contains(&track.codec.as_deref().unwrap_or_default()) {
+            sources.push(("original".to_string(), storage::audio_key(&content_hash)));
+        }
+
+        // Vorbis or PCM without the Opus renditions, players get the plain stream
+        if sources.is_empty() {
+            return Ok(());
+        }
+
+        let work_dir = std::env::temp_dir()
+            .join("music-platform")
+            .join(format!("{}.hls", job.id));
+        let result = package_sources(app_state, &content_hash, &sources, &work_dir).await;
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+        result?;
+    }
+
+    let file_size: u64 = storage
+        .list(&storage::hls_key(&content_hash, ""))
+        .await?
+        .iter()
+        .map(|object| object.size)
+        .sum();
+
+    app_state
+        .db_client
+        .save_rendition(
+            track_id,
+            NewRendition {
+                quality: "hls",
+                codec: "hls",
+                mime_type: "application/vnd.apple.mpegurl",
+                bitrate: None,
+                storage_key: &master_key,
+                file_size: file_size as i64,
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn package_sources(
+    app_state: &AppState,
+    content_hash: &str,
+    sources: &[(String, String)],
+    work_dir: &Path,
+) -> JobResult {
+    let storage = app_state.storage.as_ref();
+    let mut variants: Vec<(String, HlsVariant)> = Vec::new();
+
+    for (name, key) in sources {
+        let source = work_dir.join(format!("{}.source", name));
+        storage::download_to_file(storage, key, &source).await?;
+
+        let output_dir = work_dir.join(name);
+        let packaged = {
+            let output_dir = output_dir.clone();
+            tokio::task::spawn_blocking(move || hls::package_variant(&source, &output_dir)).await?
+        };
+
+        // A source the remuxer can't handle only costs its own variant
+        let variant = match packaged {
+            Ok(variant) => variant,
+            Err(e) => {
+                eprintln!("Skipping HLS variant {} of {}: {}", name, content_hash, e);
+                continue;
+            }
+        };
+
+        let mut entries = tokio::fs::read_dir(&output_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let key = storage::hls_key(content_hash, &format!("{}/{}", name, file_name));
+            storage.put_file(&key, &entry.path()).await?;
+        }
+
+        variants.push((name.clone(), variant));
+    }
+
+    if variants.is_empty() {
+        return Err("No source could be packaged for HLS".into());
+    }
+
+    // The master playlist goes last, once it exists every variant is in place
+    storage
+        .put(
+            &storage::hls_key(content_hash, hls::MASTER_PLAYLIST),
+            Bytes::from(hls::master_playlist(&variants)),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    database::{
+        jobs::JobExt, libraries::LibraryExt, track::TrackExt, upload::UploadExt,
+        versions::VersionExt,
+    },
+    dtos::LibraryFileDto,
+    handler::upload::{sanitize_filename, store_track_audio},
+    jobs::{
+        album::{read_header, AUDIO_EXTENSIONS},
+        JobResult, IMPORT_LIBRARY,
+    },
+    models::{Job, Library},
+    utils::validate,
+    AppState,
+};
+
+// What became of a file. Files that can't be made a track are skipped until
+// they change, the track is there if the audio was kept as a failed one.
+enum Imported {
+    Track(uuid::Uuid),
+    Failed(Option<uuid::Uuid>, String),
+}
+
+// An audio file found on disk
+struct ScannedFile {
+    // Relative to the library's root, always with `/` separators
+    path: String,
+    full_path: PathBuf,
+    size: i64,
+    modified_at: NaiveDateTime,
+}
+
+// Queues a scan of the library unless one is queued or running already
+pub async fn queue_scan(app_state: &AppState, library_id: uuid::Uuid) -> Result<bool, sqlx::Error> {
+    if !app_state.db_client.queue_library_scan(library_id).await? {
+        return Ok(false);
+    }
+
+    app_state
+        .db_client
+        .enqueue_job(IMPORT_LIBRARY, None, Some(&library_id.to_string()))
+        .await?;
+
+    Ok(true)
+}
+
+pub async fn import_library(app_state: &AppState, job: &Job) -> JobResult {
+    let library_id: uuid::Uuid = job
+        .payload
+        .as_deref()
+        .ok_or("Library import job without a library")?
+        .parse()?;
+
+    // Deleted since the job was queued, nothing to do
+    let Some(library) = app_state.db_client.get_library(library_id).await? else {
+        return Ok(());
+    };
+
+    app_state
+        .db_client
+        .set_scan_status(library_id, "scanning")
+        .await?;
+
+    let workdir = std::env::temp_dir()
+        .join("music-platform")
+        .join(job.id.to_string());
+    tokio::fs::create_dir_all(&workdir).await?;
+    let result = scan(app_state, &library, &workdir).await;
+    let _ = tokio::fs::remove_dir_all(&workdir).await;
+
+    if let Err(e) = result {
+        app_state
+            .db_client
+            .set_scan_status(library_id, "failed")
+            .await?;
+        return Err(e);
+    }
+
+    app_state.db_client.finish_library_scan(library_id).await?;
+
+    Ok(())
+}
+
+async fn scan(app_state: &AppState, library: &Library, workdir: &Path) -> JobResult {
+    let scanned = {
+        let root = PathBuf::from(&library.root_path);
+        tokio::task::spawn_blocking(move || scan_directory(&root)).await??
+    };
+
+    let mut known: HashMap<String, LibraryFileDto> = app_state
+        .db_client
+        .get_library_files(library.id)
+        .await?
+        .into_iter()
+        .map(|file| (file.path.clone(), file))
+        .collect();
+
+    let (mut added, mut changed, mut failed) = (0, 0, 0);
+    for (index, file) in scanned.iter().enumerate() {
+        let existing = known.remove(&file.path);
+
+        // Untouched since the last scan, failures included
+        if existing.as_ref().is_some_and(|existing| {
+            existing.file_size == file.size && existing.modified_at == file.modified_at
+        }) {
+            continue;
+        }
+
+        let extension = file
+            .path
+            .rsplit_once('.')
+            .map_or("", |(_, extension)| extension);
+        let scratch = workdir.join(format!("{}.{}", index, extension));
+        let existing_track = existing.and_then(|existing| existing.track_id);
+
+        let imported = import_file(app_state, library, file, existing_track, &scratch).await;
+        let _ = tokio::fs::remove_file(&scratch).await;
+        let (track_id, error) = match imported? {
+            Imported::Track(track_id) => (Some(track_id), None),
+            Imported::Failed(track_id, message) => (track_id, Some(message)),
+        };
+
+        app_state
+            .db_client
+            .save_library_file(
+                library.id,
+                &file.path,
+                file.size,
+                file.modified_at,
+                track_id,
+                error.as_deref(),
+            )
+            .await?;
+
+        match (existing_track, &error) {
+            (_, Some(_)) => failed += 1,
+            (Some(_), None) => changed += 1,
+            (None, None) => added += 1,
+        }
+    }
+
+    // What's left wasn't found on disk anymore
+    let gone: Vec<String> = known.into_keys().collect();
+    let removed = if gone.is_empty() {
+        0
+    } else {
+        app_state
+            .db_client
+            .remove_library_files(library.id, &gone)
+            .await?
+    };
+
+    println!(
+        "Scanned {}: {} added, {} changed, {} removed, {} failed",
+        library.root_path, added, changed, removed, failed
+    );
+
+    Ok(())
+}
+
+// Makes a track of the file the same way an upload of it would, or gives an
+// existing one the file's new audio. Admin imports aren't held to the owner's
+// quotas. Storage and database errors fail the scan, so it's retried.
+async fn import_file(
+    app_state: &AppState,
+    library: &Library,
+    file: &ScannedFile,
+    existing_track: Option<uuid::Uuid>,
+    scratch: &Path,
+) -> Result<Imported, Box<dyn std::error::Error + Send + Sync>> {
+    // Files can sit on slow network shares, the copy is local before it's probed
+    let copied = {
+        let (source, scratch) = (file.full_path.clone(), scratch.to_path_buf());
+        tokio::task::spawn_blocking(move || copy_hashed(&source, &scratch)).await?
+    };
+    let content_hash = match copied {
+        Ok(content_hash) => content_hash,
+        Err(e) => return Ok(Imported::Failed(existing_track, e.to_string())),
+    };
+
+    let header = read_header(scratch).await?;
+    if let Err(e) = validate::check_audio_header(&app_state.env, &header) {
+        return Ok(Imported::Failed(existing_track, e.message));
+    }
+
+    // The track may have been deleted in the app since the last scan
+    let track = match existing_track {
+        Some(track_id) => app_state.db_client.get_track(track_id).await?,
+        None => None,
+    };
+
+    let track_id = match track {
+        // Touched but the same audio, nothing to redo
+        Some(track) if track.content_hash.as_deref() == Some(content_hash.as_str()) => {
+            return Ok(Imported::Track(track.id));
+        }
+        Some(track) => {
+            app_state.db_client.clear_library_track(track.id).await?;
+            app_state.db_client.reset_track_audio(track.id).await?;
+            track.id
+        }
+        None => {
+            let file_name = file.path.rsplit('/').next().unwrap_or(&file.path);
+            app_state
+                .db_client
+                .upload_file(library.user_id, &sanitize_filename(file_name))
+                .await?
+        }
+    };
+
+    if let Err(e) = store_track_audio(app_state, track_id, scratch, &content_hash).await {
+        // Audio that can't be used is kept as a failed track, like uploads are
+        if e.kind() == io::ErrorKind::InvalidData {
+            return Ok(Imported::Failed(Some(track_id), e.to_string()));
+        }
+
+        // New tracks are made again by the retry
+        app_state
+            .db_client
+            .delete_incomplete_track(track_id)
+            .await?;
+        return Err(e.into());
+    }
+
+    let file_name = file.path.rsplit('/').next().unwrap_or(&file.path);
+    let title = file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _)| stem);
+    app_state
+        .db_client
+        .fill_track_title(track_id, title)
+        .await?;
+
+    Ok(Imported::Track(track_id))
+}
+
+// Audio files below `root` in path order. Hidden files and directories are
+// left out, symlinked directories aren't followed so loops can't happen.
+fn scan_directory(root: &Path) -> io::Result<Vec<ScannedFile>> {
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        for entry in std::fs::read_dir(&directory)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let full_path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                directories.push(full_path);
+                continue;
+            }
+
+            let extension = name
+                .rsplit_once('.')
+                .map(|(_, extension)| extension.to_ascii_lowercase())
+                .unwrap_or_default();
+            if !AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+                continue;
+            }
+
+            // Symlinked files count as the file they point to
+            let metadata = match std::fs::metadata(&full_path) {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => continue,
+            };
+
+            let relative = full_path.strip_prefix(root).unwrap_or(&full_path);
+            let path = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            // Postgres keeps microseconds
+            let modified_at = DateTime::<Utc>::from(metadata.modified()?)
+                .naive_utc()
+                .trunc_subsecs(6);
+
+            files.push(ScannedFile {
+                path,
+                full_path,
+                size: metadata.len() as i64,
+                modified_at,
+            });
+        }
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+// Copies `source` to `output` and returns its SHA-256
+fn copy_hashed(source: &Path, output: &Path) -> io::Result<String> {
+    let mut source = File::open(source)?;
+    let mut file = File::create(output)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 256 * 1024];
+
+    loop {
+        let read = source.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..read]);
+        file.write_all(&buffer[..read])?;
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use crate::{
+    audio::loudness::{measure_loudness, Loudness},
+    database::track::TrackExt,
+    jobs::JobResult,
+    models::{Job, Track},
+    storage, AppState,
+};
+
+pub async fn analyze_loudness(app_state: &AppState, job: &Job) -> JobResult {
+    let track_id = job.track_id.ok_or("Loudness job without a track")?;
+
+    // Deleted or re-uploaded since the job was queued, nothing to do
+    let Some(track) = app_state.db_client.get_track(track_id).await? else {
+        return Ok(());
+    };
+    if track.upload_status.as_deref() != Some("complete") {
+        return Ok(());
+    }
+
+    let content_hash = track
+        .content_hash
+        .clone()
+        .ok_or("Track audio is not content addressed")?;
+
+    let source = std::env::temp_dir()
+        .join("music-platform")
+        .join(format!("{}.source", job.id));
+    let result = analyze(app_state, &track, &content_hash, &source).await;
+    let _ = tokio::fs::remove_file(&source).await;
+
+    result
+}
+
+async fn analyze(app_state: &AppState, track: &Track, content_hash: &str, source: &Path) -> JobResult {
+    storage::download_to_file(
+        app_state.storage.as_ref(),
+        &storage::audio_key(content_hash),
+        source,
+    )
+    .await?;
+
+    let loudness = {
+        let input = source.to_path_buf();
+        tokio::task::spawn_blocking(move || measure_loudness(&input)).await??
+    };
+
+    app_state.db_client.save_loudness(track.id, &loudness).await?;
+
+    if app_state.env.normalized_rendition {
+        encode_normalized(app_state, track, content_hash, source, &loudness).await?;
+    }
+
+    Ok(())
+}
+
+// Opus rendition already played back at the reference loudness, for players
+// that can't apply ReplayGain themselves
+#[cfg(feature = "opus")]
+async fn encode_normalized(
+    app_state: &AppState,
+    track: &Track,
+    content_hash: &str,
+    source: &Path,
+    loudness: &Loudness,
+) -> JobResult {
+    use crate::{
+        audio::loudness::replay_gain,
+        jobs::transcode::{store_opus_rendition, OpusRendition},
+    };
+
+    // Quiet tracks are only raised as far as 1 dB below full scale
+    const MAX_TRUE_PEAK: f64 = -1.0;
+
+    // Silence stays as it is
+    let Some(integrated) = loudness.integrated else {
+        return Ok(());
+    };
+    let headroom = loudness
+        .true_peak
+        .map_or(f64::INFINITY, |true_peak| MAX_TRUE_PEAK - true_peak);
+    let gain = replay_gain(integrated).min(headroom);
+
+    let rendition = OpusRendition {
+        quality: "normalized",
+        name: "opus_160_normalized.opus",
+        bitrate: 160_000,
+        gain,
+        clip: None,
+    };
+    store_opus_rendition(app_state, track, content_hash, source, rendition).await
+}
+
+// Needs the Opus encoder, see the `opus` feature. The measurements are saved
+// already, the job fails so the missing rendition shows up in `jobs`.
+#[cfg(not(feature = "opus"))]
+async fn encode_normalized(
+    _app_state: &AppState,
+    _track: &Track,
+    _content_hash: &str,
+    _source: &Path,
+    _loudness: &Loudness,
+) -> JobResult {
+    Err(crate::jobs::OPUS_MISSING.into())
+}
@@ -0,0 +1,92 @@
+pub mod album;
+pub mod cleanup;
+pub mod fingerprint;
+pub mod hls;
+pub mod library;
+pub mod loudness;
+pub mod preview;
+pub mod transcode;
+pub mod waveform;
+
+use std::{sync::Arc, time::Duration};
+
+use crate::{database::jobs::JobExt, models::Job, AppState};
+
+pub type JobResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+// Values of `jobs.kind`
+pub const TRANSCODE: &str = "transcode";
+pub const PACKAGE_HLS: &str = "package_hls";
+pub const ANALYZE_LOUDNESS: &str = "analyze_loudness";
+pub const GENERATE_WAVEFORM: &str = "generate_waveform";
+pub const FINGERPRINT: &str = "fingerprint";
+pub const GENERATE_PREVIEW: &str = "generate_preview";
+pub const IMPORT_ALBUM: &str = "import_album";
+pub const IMPORT_LIBRARY: &str = "import_library";
+
+// Why jobs that need the Opus encoder aren't run in builds without it
+pub const OPUS_MISSING: &str = "Opus encoding needs a build with the `opus` feature";
+
+const MAX_ATTEMPTS: i32 = 3;
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// Running jobs that haven't moved for this long belong to a worker that is gone
+const STALE_AFTER: Duration = Duration::from_secs(60 * 60);
+
+pub async fn spawn_workers(app_state: Arc<AppState>) {
+    match app_state.db_client.requeue_stale_jobs(STALE_AFTER).await {
+        Ok(0) => {}
+        Ok(count) => println!("Requeued {} stale jobs", count),
+        Err(e) => eprintln!("Failed to requeue stale jobs: {}", e),
+    }
+
+    for _ in 0..app_state.env.job_workers {
+        tokio::spawn(worker(app_state.clone()));
+    }
+}
+
+async fn worker(app_state: Arc<AppState>) {
+    loop {
+        match app_state.db_client.claim_job().await {
+            Ok(Some(job)) => run_job(&app_state, job).await,
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(e) => {
+                eprintln!("Failed to claim a job: {}", e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn run_job(app_state: &Arc<AppState>, job: Job) {
+    let result = match job.kind.as_str() {
+        TRANSCODE => transcode::transcode(app_state, &job).await,
+        PACKAGE_HLS => hls::package_hls(app_state, &job).await,
+        ANALYZE_LOUDNESS => loudness::analyze_loudness(app_state, &job).await,
+        GENERATE_WAVEFORM => waveform::generate(app_state, &job).await,
+        FINGERPRINT => fingerprint::fingerprint(app_state, &job).await,
+        GENERATE_PREVIEW => preview::generate_preview(app_state, &job).await,
+        IMPORT_ALBUM => album::import_album(app_state, &job).await,
+        IMPORT_LIBRARY => library::import_library(app_state, &job).await,
+        kind => Err(format!("Unknown job kind {}", kind).into()),
+    };
+
+    let saved = match result {
+        Ok(()) => app_state.db_client.complete_job(job.id).await,
+        Err(e) => {
+            eprintln!("Job {} ({}) failed: {}", job.id, job.kind, e);
+
+            // Backs off 30s, 60s, ... before giving up
+            let retry_in = (job.attempts < MAX_ATTEMPTS)
+                .then(|| Duration::from_secs(30 << (job.attempts - 1).max(0)));
+
+            app_state
+                .db_client
+                .fail_job(job.id, &e.to_string(), retry_in)
+                .await
+        }
+    };
+
+    if let Err(e) = saved {
+        eprintln!("Failed to update job {}: {}", job.id, e);
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use crate::{
+    database::track::TrackExt,
+    jobs::JobResult,
+    models::{Job, Track},
+    AppState,
+};
+
+pub async fn generate_preview(app_state: &AppState, job: &Job) -> JobResult {
+    let track_id = job.track_id.ok_or("Preview job without a track")?;
+
+    // Deleted or re-uploaded since the job was queued, nothing to do
+    let Some(track) = app_state.db_client.get_track(track_id).await? else {
+        return Ok(());
+    };
+    if track.upload_status.as_deref() != Some("complete") {
+        return Ok(());
+    }
+
+    let content_hash = track
+        .content_hash
+        .clone()
+        .ok_or("Track audio is not content addressed")?;
+
+    let source = std::env::temp_dir()
+        .join("music-platform")
+        .join(format!("{}.source", job.id));
+    let result = encode_preview(app_state, &track, &content_hash, &source).await;
+    let _ = tokio::fs::remove_file(&source).await;
+
+    result
+}
+
+// Opus clip of the track at 96 kbit/s, stored as the `preview` rendition
+#[cfg(feature = "opus")]
+async fn encode_preview(
+    app_state: &AppState,
+    track: &Track,
+    content_hash: &str,
+    source: &Path,
+) -> JobResult {
+    use crate::{
+        audio::{opus::Clip, preview::find_preview_start},
+        jobs::transcode::{store_opus_rendition, OpusRendition},
+        storage,
+    };
+
+    let duration = app_state.env.preview_duration;
+    let track_seconds = (track.duration.microseconds / 1_000_000) as u32;
+
+    let start = match track.preview_start {
+        // Late starts still get a full preview when the track is long enough
+        Some(start) => (start.max(0) as u32).min(track_seconds.saturating_sub(duration)),
+        None => {
+            storage::download_to_file(
+                app_state.storage.as_ref(),
+                &storage::audio_key(content_hash),
+                source,
+            )
+            .await?;
+
+            let input = source.to_path_buf();
+            tokio::task::spawn_blocking(move || find_preview_start(&input, duration)).await??
+        }
+    };
+
+    // Named after the part of the audio it holds, tracks with the same audio
+    // and start share it
+    let rendition = OpusRendition {
+        quality: "preview",
+        name: &format!("preview_{}_{}.opus", start, duration),
+        bitrate: 96_000,
+        gain: 0.0,
+        clip: Some(Clip {
+            start: start as f64,
+            duration: duration as f64,
+        }),
+    };
+    store_opus_rendition(app_state, track, content_hash, source, rendition).await
+}
+
+// Needs the Opus encoder, see the `opus` feature. Builds without it don't
+// queue the job, one left over from another build fails saying why.
+#[cfg(not(feature = "opus"))]
+async fn encode_preview(
+    _app_state: &AppState,
+    _track: &Track,
+    _content_hash: &str,
+    _source: &Path,
+) -> JobResult {
+    Err(crate::jobs::OPUS_MISSING.into())
+}
@@ -0,0 +1,198 @@
+use crate::{
+    database::{
+        jobs::JobExt,
+        renditions::{NewRendition, RenditionExt},
+        track::TrackExt,
+    },
+    jobs::{JobResult, PACKAGE_HLS},
+    models::{Job, Track},
+    storage, AppState,
+};
+
+// Codecs whose original file can be streamed as the `lossless` rendition
+const LOSSLESS_CODECS: [&str; 3] = ["flac", "alac", "pcm"];
+
+pub async fn transcode(app_state: &AppState, job: &Job) -> JobResult {
+    let track_id = job.track_id.ok_or("Transcode job without a track")?;
+
+    // Deleted or re-uploaded since the job was queued, nothing to do
+    let Some(track) = app_state.db_client.get_track(track_id).await? else {
+        return Ok(());
+    };
+    if track.upload_status.as_deref() != Some("complete") {
+        return Ok(());
+    }
+
+    let content_hash = track
+        .content_hash
+        .clone()
+        .ok_or("Track audio is not content addressed")?;
+    let audio_key = storage::audio_key(&content_hash);
+    let original = app_state
+        .storage
+        .stat(&audio_key)
+        .await?
+        .ok_or("Track audio is missing from storage")?;
+
+    let codec = track.codec.as_deref().unwrap_or_default();
+    if LOSSLESS_CODECS.contains(&codec) {
+        app_state
+            .db_client
+            .save_rendition(
+                track_id,
+                NewRendition {
+                    quality: "lossless",
+                    codec,
+                    mime_type: track.mime_type.as_deref().unwrap_or("audio/flac"),
+                    bitrate: None,
+                    storage_key: &audio_key,
+                    file_size: original.size as i64,
+                },
+            )
+            .await?;
+    }
+
+    let encoded = encode_renditions(app_state, job, &track, &content_hash).await;
+
+    // HLS remuxes the renditions, so it waits until they're all stored
+    if encoded.is_ok() {
+        app_state
+            .db_client
+            .enqueue_job(PACKAGE_HLS, Some(track_id), None)
+            .await?;
+    }
+
+    encoded
+}
+
+#[cfg(feature = "opus")]
+async fn encode_renditions(
+    app_state: &AppState,
+    job: &Job,
+    track: &Track,
+    content_hash: &str,
+) -> JobResult {
+    // The original is only downloaded when a rendition has to be encoded
+    let source = std::env::temp_dir()
+        .join("music-platform")
+        .join(format!("{}.source", job.id));
+    let result = encode_opus_renditions(app_state, track, content_hash, &source).await;
+    let _ = tokio::fs::remove_file(&source).await;
+
+    result
+}
+
+#[cfg(feature = "opus")]
+async fn encode_opus_renditions(
+    app_state: &AppState,
+    track: &Track,
+    content_hash: &str,
+    source: &std::path::Path,
+) -> JobResult {
+    // (quality, bits per second)
+    const OPUS_RENDITIONS: [(&str, i32); 2] = [("high", 160_000), ("low", 96_000)];
+
+    for (quality, bitrate) in OPUS_RENDITIONS {
+        let rendition = OpusRendition {
+            quality,
+            name: &format!("opus_{}.opus", bitrate / 1000),
+            bitrate,
+            gain: 0.0,
+            clip: None,
+        };
+        store_opus_rendition(app_state, track, content_hash, source, rendition).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "opus")]
+pub struct OpusRendition<'a> {
+    pub quality: &'a str,
+    // File name under `uploads/renditions/{content_hash}/`
+    pub name: &'a str,
+    // Bits per second
+    pub bitrate: i32,
+    // dB applied before encoding
+    pub gain: f64,
+    // Only this part of the track, for previews
+    pub clip: Option<crate::audio::opus::Clip>,
+}
+
+// Encodes the original and records it as one of the track's renditions. The
+// original is downloaded to `source` the first time an encode is needed.
+#[cfg(feature = "opus")]
+pub async fn store_opus_rendition(
+    app_state: &AppState,
+    track: &Track,
+    content_hash: &str,
+    source: &std::path::Path,
+    rendition: OpusRendition<'_>,
+) -> JobResult {
+    use crate::audio::opus::encode_opus;
+
+    let OpusRendition {
+        quality,
+        name,
+        bitrate,
+        gain,
+        clip,
+    } = rendition;
+    let storage = app_state.storage.as_ref();
+    let key = storage::rendition_key(content_hash, name);
+
+    // Another track with the same audio may have produced it already
+    if storage.stat(&key).await?.is_none() {
+        if !tokio::fs::try_exists(source).await? {
+            storage::download_to_file(storage, &storage::audio_key(content_hash), source).await?;
+        }
+
+        let output = source.with_extension(format!("{}.opus", quality));
+        let input = source.to_path_buf();
+        let encoded = {
+            let output = output.clone();
+            tokio::task::spawn_blocking(move || encode_opus(&input, &output, bitrate, gain, clip))
+                .await?
+        };
+        if let Err(e) = encoded {
+            let _ = tokio::fs::remove_file(&output).await;
+            return Err(e);
+        }
+
+        storage.put_file(&key, &output).await?;
+    }
+
+    let object = storage
+        .stat(&key)
+        .await?
+        .ok_or("Rendition is missing from storage")?;
+
+    app_state
+        .db_client
+        .save_rendition(
+            track.id,
+            NewRendition {
+                quality,
+                codec: "opus",
+                mime_type: "audio/ogg; codecs=opus",
+                bitrate: Some(bitrate),
+                storage_key: &key,
+                file_size: object.size as i64,
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+// Lossy renditions need libopus, see the `opus` feature. Builds without it
+// don't queue the job, one left over from another build fails saying why.
+#[cfg(not(feature = "opus"))]
+async fn encode_renditions(
+    _app_state: &AppState,
+    _job: &Job,
+    _track: &Track,
+    _content_hash: &str,
+) -> JobResult {
+    Err(crate::jobs::OPUS_MISSING.into())
+}
@@ -0,0 +1,58 @@
+use bytes::Bytes;
+
+use crate::{
+    audio::waveform::{generate_waveform, WaveformFormat},
+    database::track::TrackExt,
+    jobs::JobResult,
+    models::Job,
+    storage, AppState,
+};
+
+const FORMATS: [WaveformFormat; 2] = [WaveformFormat::Json, WaveformFormat::Binary];
+
+pub async fn generate(app_state: &AppState, job: &Job) -> JobResult {
+    let track_id = job.track_id.ok_or("Waveform job without a track")?;
+
+    // Deleted or re-uploaded since the job was queued, nothing to do
+    let Some(track) = app_state.db_client.get_track(track_id).await? else {
+        return Ok(());
+    };
+    if track.upload_status.as_deref() != Some("complete") {
+        return Ok(());
+    }
+
+    let content_hash = track
+        .content_hash
+        .ok_or("Track audio is not content addressed")?;
+    let storage = app_state.storage.as_ref();
+
+    // Another track with the same audio may have produced them already
+    let mut missing = false;
+    for format in FORMATS {
+        let key = storage::waveform_key(&content_hash, format.extension());
+        missing |= storage.stat(&key).await?.is_none();
+    }
+    if !missing {
+        return Ok(());
+    }
+
+    let source = std::env::temp_dir()
+        .join("music-platform")
+        .join(format!("{}.source", job.id));
+    let waveform = async {
+        storage::download_to_file(storage, &storage::audio_key(&content_hash), &source).await?;
+
+        let input = source.clone();
+        tokio::task::spawn_blocking(move || generate_waveform(&input)).await?
+    }
+    .await;
+    let _ = tokio::fs::remove_file(&source).await;
+    let waveform = waveform?;
+
+    for format in FORMATS {
+        let key = storage::waveform_key(&content_hash, format.extension());
+        storage.put(&key, Bytes::from(waveform.encode(format))).await?;
+    }
+
+    Ok(())
+}
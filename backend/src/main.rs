@@ -1,19 +1,28 @@
+mod audio;
 mod auth;
+mod cli;
 mod config;
 mod database;
 mod db;
 mod dtos;
 mod error;
 mod handler;
+mod jobs;
 mod models;
 mod routes;
+mod storage;
 mod utils;
 
 use std::{net::SocketAddr, path::PathBuf, sync::Arc};
 
-use axum::http::{
-    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
-    HeaderValue, Method,
+use axum::{
+    extract::Request,
+    http::{
+        header::{ACCEPT, ACCESS_CONTROL_REQUEST_METHOD, AUTHORIZATION, CONTENT_TYPE, LOCATION},
+        HeaderName, HeaderValue, Method,
+    },
+    middleware::{self, Next},
+    response::IntoResponse,
 };
 use axum_server::tls_rustls::RustlsConfig;
 use config::Config;
@@ -21,6 +30,8 @@ use db::DBClient;
 use dotenv::dotenv;
 use routes::create_router;
 use sqlx::postgres::PgPoolOptions;
+use storage::{local::LocalStorage, s3::S3Storage, Storage};
+use tower::ServiceExt;
 use tower_http::cors::CorsLayer;
 use tracing_subscriber::filter::LevelFilter;
 
@@ -28,16 +39,56 @@ use tracing_subscriber::filter::LevelFilter;
 pub struct AppState {
     pub env: Config,
     pub db_client: DBClient,
+    pub storage: Arc<dyn Storage>,
+}
+
+// Database pool and storage backend, shared by the server and the CLI
+pub async fn connect(config: &Config) -> AppState {
+    let pool = match PgPoolOptions::new()
+        .max_connections(10)
+        .connect(&config.database_url)
+        .await
+    {
+        Ok(pool) => {
+            println!("✅Connection to the database is successful!");
+            pool
+        }
+        Err(err) => {
+            println!("🔥 Failed to connect to the database: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let storage: Arc<dyn Storage> = match config.storage_backend.as_str() {
+        "s3" => Arc::new(S3Storage::new(config).await),
+        _ => Arc::new(LocalStorage::new(&config.storage_dir)),
+    };
+
+    AppState {
+        env: config.clone(),
+        db_client: DBClient::new(pool),
+        storage,
+    }
 }
 
 #[tokio::main]
 async fn main() {
+    dotenv().ok();
+
+    // Maintenance commands run instead of the server, without its request logs
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(e) = cli::run(&args).await {
+            eprintln!("🔥 {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     tracing_subscriber::fmt()
         .with_max_level(LevelFilter::DEBUG)
         .init();
 
-    dotenv().ok();
-
     let config_http = RustlsConfig::from_pem_file(
         PathBuf::from(env!("CARGO_MANIFEST_DIR"))
             .join("certs")
@@ -51,34 +102,60 @@ async fn main() {
 
     let config = Config::init();
 
-    let pool = match PgPoolOptions::new()
-        .max_connections(10)
-        .connect(&config.database_url)
-        .await
-    {
-        Ok(pool) => {
-            println!("✅Connection to the database is successful!");
-            pool
-        }
-        Err(err) => {
-            println!("🔥 Failed to connect to the database: {:?}", err);
-            std::process::exit(1);
-        }
-    };
-
     let cors = CorsLayer::new()
         .allow_origin("https://localhost:3000".parse::<HeaderValue>().unwrap())
-        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE])
+        .allow_headers([
+            AUTHORIZATION,
+            ACCEPT,
+            CONTENT_TYPE,
+            HeaderName::from_static("tus-resumable"),
+            HeaderName::from_static("upload-length"),
+            HeaderName::from_static("upload-offset"),
+            HeaderName::from_static("upload-metadata"),
+            HeaderName::from_static("upload-checksum"),
+        ])
+        .expose_headers([
+            LOCATION,
+            HeaderName::from_static("tus-resumable"),
+            HeaderName::from_static("tus-version"),
+            HeaderName::from_static("tus-extension"),
+            HeaderName::from_static("tus-checksum-algorithm"),
+            HeaderName::from_static("tus-max-size"),
+            HeaderName::from_static("upload-length"),
+            HeaderName::from_static("upload-offset"),
+        ])
         .allow_credentials(true)
-        .allow_methods([Method::GET, Method::POST, Method::PUT]);
-
-    let db_client = DBClient::new(pool);
-    let app_state = AppState {
-        env: config.clone(),
-        db_client,
-    };
-
-    let app = create_router(Arc::new(app_state.clone())).layer(cors.clone());
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::HEAD,
+            Method::OPTIONS,
+        ]);
+
+    let app_state = Arc::new(connect(&config).await);
+    jobs::spawn_workers(app_state.clone()).await;
+    jobs::cleanup::spawn_upload_gc(app_state.clone());
+
+    let router = create_router(app_state);
+    let app = router
+        .clone()
+        .layer(cors.clone())
+        .layer(middleware::from_fn(move |req: Request, next: Next| {
+            let router = router.clone();
+            async move {
+                // The CORS layer answers every OPTIONS as a preflight, plain
+                // ones (tus discovery) are for the routes
+                if req.method() == Method::OPTIONS
+                    && !req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+                {
+                    return router.oneshot(req).await.into_response();
+                }
+                next.run(req).await
+            }
+        }));
 
     println!(
         "{}",
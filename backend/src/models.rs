@@ -49,8 +49,23 @@ pub struct User {
     pub password_hash: String,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub stream_quality: String,
+    pub role: String,
 }
 
+// Values of `users.role`. Admins can import libraries from the server's disks.
+pub const USER_ROLE: &str = "user";
+pub const ADMIN_ROLE: &str = "admin";
+
+// Allowed values of `users.stream_quality` and the stream `quality` parameter.
+// `auto` is the best lossy rendition there is, `original` the file as uploaded.
+// `normalized` is `high` brought to -18 LUFS, when that rendition is enabled.
+pub const STREAM_QUALITIES: [&str; 6] =
+    ["auto", "original", "lossless", "high", "low", "normalized"];
+
+// Allowed values of `tracks.visibility`
+pub const TRACK_VISIBILITIES: [&str; 3] = ["public", "unlisted", "private"];
+
 // Track Model
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Track {
@@ -62,10 +77,54 @@ pub struct Track {
     pub file_name: Option<String>,
     pub upload_status: Option<String>,
     pub thumbnail_name: Option<String>,
+    pub container: Option<String>,
+    pub codec: Option<String>,
+    pub mime_type: Option<String>,
+    pub visibility: String,
+    pub content_hash: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<i32>,
+    pub disc_number: Option<i32>,
+    pub release_year: Option<i32>,
+    pub genre: Option<String>,
+    pub isrc: Option<String>,
+    pub lyrics: Option<String>,
+    pub failure_reason: Option<String>,
+    pub duplicate_of: Option<Uuid>,
+    pub preview_start: Option<i32>,
+    pub description: Option<String>,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
 }
 
+// Descriptive fields of a track, read from the file's tags or sent by the
+// upload form. `None` leaves whatever the track already has.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TrackMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<i32>,
+    pub disc_number: Option<i32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub isrc: Option<String>,
+    pub lyrics: Option<String>,
+}
+
+// What the owner edits on a track, written as a whole. `None` clears the field.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TrackDetails {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub description: Option<String>,
+    pub thumbnail_name: Option<String>,
+}
+
 // AudioFile Model
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct AudioFile {
@@ -78,6 +137,58 @@ pub struct AudioFile {
     pub upload_status: Option<String>,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub file_hash: Option<String>,
+    pub upload_length: Option<i64>,
+}
+
+// TrackRendition Model
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct TrackRendition {
+    pub id: Uuid,
+    pub track_id: Uuid,
+    pub quality: String,
+    pub codec: String,
+    pub mime_type: String,
+    pub bitrate: Option<i32>,
+    pub storage_key: String,
+    pub file_size: i64,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+// Album Model
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Album {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub title: String,
+    pub artist: Option<String>,
+    pub release_year: Option<i32>,
+    pub thumbnail_name: Option<String>,
+    pub import_status: String,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+// Library Model
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Library {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub root_path: String,
+    pub scan_status: String,
+    pub scanned_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+// Job Model
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Job {
+    pub id: Uuid,
+    pub kind: String,
+    pub track_id: Option<Uuid>,
+    pub payload: Option<String>,
+    pub attempts: i32,
 }
 
 // Playlist Model
@@ -1,21 +1,25 @@
 use std::sync::Arc;
 
 use axum::{extract::DefaultBodyLimit, middleware, Extension, Router};
-use tower_http::{services::ServeDir, trace::TraceLayer};
+use tower_http::trace::TraceLayer;
 
 use crate::{
-    auth::auth,
+    auth::{admin, auth},
     handler::{
-        auth::auth_handler, favorites::favorites_handler, getfile::get_file_handler,
-        history::history_handler, playlists::playlist_hanlder, upload::upload_handler,
-        users::users_handler,
+        admin::admin_handler, albums::albums_handler, assets::assets_handler, auth::auth_handler, favorites::favorites_handler, getfile::get_file_handler,
+        history::history_handler, playlists::playlist_hanlder,
+        tracks::{track_previews_handler, tracks_handler},
+        tus::tus_handler, upload::upload_handler, users::users_handler,
     },
     AppState,
 };
 
-const MAX_FILE_SIZE: usize = 6 * 1024 * 1024; // 5 MB in bytes
+const MAX_FILE_SIZE: usize = 6 * 1024 * 1024; // 6 MB in bytes
 
 pub fn create_router(app_state: Arc<AppState>) -> Router {
+    // A tus PATCH may carry the whole file, up to the advertised `Tus-Max-Size`
+    let max_upload_size = app_state.env.max_upload_size as usize;
+
     let api_route = Router::new()
         .nest("/auth", auth_handler())
         .nest("/users", users_handler().layer(middleware::from_fn(auth)))
@@ -25,7 +29,20 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
                 .layer(middleware::from_fn(auth))
                 .layer(DefaultBodyLimit::max(MAX_FILE_SIZE)),
         )
+        .nest(
+            "/upload/tus",
+            tus_handler()
+                .layer(middleware::from_fn(auth))
+                .layer(DefaultBodyLimit::max(max_upload_size)),
+        )
         .nest("/get", get_file_handler().layer(middleware::from_fn(auth)))
+        .nest(
+            "/tracks",
+            tracks_handler()
+                .layer(middleware::from_fn(auth))
+                .layer(DefaultBodyLimit::max(MAX_FILE_SIZE))
+                .merge(track_previews_handler()),
+        )
         .nest(
             "/favorite",
             favorites_handler().layer(middleware::from_fn(auth)),
@@ -38,7 +55,14 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
             "/history",
             history_handler().layer(middleware::from_fn(auth)),
         )
-        .nest_service("/assets", ServeDir::new("assets"))
+        .nest("/albums", albums_handler().layer(middleware::from_fn(auth)))
+        .nest(
+            "/admin",
+            admin_handler()
+                .layer(middleware::from_fn(admin))
+                .layer(middleware::from_fn(auth)),
+        )
+        .nest("/assets", assets_handler())
         .layer(TraceLayer::new_for_http())
         .layer(Extension(app_state));
 
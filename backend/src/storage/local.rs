@@ -0,0 +1,169 @@
+use std::{
+    io::{self, SeekFrom},
+    path::{Component, Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+
+use super::{ByteStream, ObjectMeta, Storage};
+
+// Keys map one to one onto paths below `root`, which keeps the historical
+// `uploads/` and `assets/` directories working when `root` is the working dir.
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalStorage { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        let relative = Path::new(key);
+
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid storage key: {}", key),
+            ));
+        }
+
+        Ok(self.root.join(relative))
+    }
+
+    fn meta(&self, key: String, metadata: &std::fs::Metadata) -> ObjectMeta {
+        let last_modified = metadata
+            .modified()
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_default();
+
+        ObjectMeta {
+            key,
+            size: metadata.len(),
+            last_modified,
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, data: Bytes) -> io::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(path, data).await
+    }
+
+    async fn put_file(&self, key: &str, source: &Path) -> io::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        // A rename is free on the same filesystem, fall back to copying
+        if fs::rename(source, &path).await.is_err() {
+            fs::copy(source, &path).await?;
+            fs::remove_file(source).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_range(&self, key: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream> {
+        let mut file = File::open(self.path(key)?).await?;
+
+        match range {
+            Some((start, end)) => {
+                file.seek(SeekFrom::Start(start)).await?;
+                Ok(ReaderStream::new(file.take(end - start + 1)).boxed())
+            }
+            None => Ok(ReaderStream::new(file).boxed()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        let path = self.path(key)?;
+        match fs::remove_file(&path).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+
+        // Keys are flat, directories are not. Drop the parents once they're
+        // empty so finished uploads don't leave `uploads/temp/{id}` behind, nor
+        // deleted audio `uploads/hls/{hash}/{variant}`.
+        let mut parent = path.parent();
+        while let Some(dir) = parent.filter(|dir| *dir != self.root) {
+            if fs::remove_dir(dir).await.is_err() {
+                break;
+            }
+            parent = dir.parent();
+        }
+
+        Ok(())
+    }
+
+    async fn stat(&self, key: &str) -> io::Result<Option<ObjectMeta>> {
+        match fs::metadata(self.path(key)?).await {
+            Ok(metadata) if metadata.is_file() => Ok(Some(self.meta(key.to_string(), &metadata))),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> io::Result<Vec<ObjectMeta>> {
+        // Walk from the deepest directory the prefix names, then filter by key
+        let base = match prefix.rfind('/') {
+            Some(index) => &prefix[..index],
+            None => "",
+        };
+        let start = if base.is_empty() {
+            self.root.clone()
+        } else {
+            self.path(base)?
+        };
+
+        let mut objects = Vec::new();
+        let mut pending = vec![(start, base.to_string())];
+
+        while let Some((dir, dir_key)) = pending.pop() {
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let key = if dir_key.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", dir_key, name)
+                };
+
+                let metadata = entry.metadata().await?;
+                if metadata.is_dir() {
+                    pending.push((entry.path(), key));
+                } else if key.starts_with(prefix) {
+                    objects.push(self.meta(key, &metadata));
+                }
+            }
+        }
+
+        Ok(objects)
+    }
+}
@@ -0,0 +1,192 @@
+pub mod local;
+pub mod s3;
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::{stream::BoxStream, StreamExt};
+
+use crate::models::Track;
+
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+#[derive(Debug, Clone)]
+pub struct ObjectMeta {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+// Everything the backend persists outside the database (audio, chunks,
+// artwork) goes through this trait so replicas can share object storage.
+#[async_trait]
+pub trait Storage: std::fmt::Debug + Send + Sync {
+    async fn put(&self, key: &str, data: Bytes) -> io::Result<()>;
+
+    // Stores a local file under `key`. The source file is consumed.
+    async fn put_file(&self, key: &str, path: &Path) -> io::Result<()>;
+
+    // Streams the object, or only the inclusive byte range when one is given.
+    async fn get_range(&self, key: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream>;
+
+    // Deleting a key that doesn't exist is not an error.
+    async fn delete(&self, key: &str) -> io::Result<()>;
+
+    async fn stat(&self, key: &str) -> io::Result<Option<ObjectMeta>>;
+
+    async fn list(&self, prefix: &str) -> io::Result<Vec<ObjectMeta>>;
+}
+
+pub async fn read_range(
+    storage: &dyn Storage,
+    key: &str,
+    range: Option<(u64, u64)>,
+) -> io::Result<Vec<u8>> {
+    let mut stream = storage.get_range(key, range).await?;
+    let mut data = Vec::new();
+
+    while let Some(chunk) = stream.next().await {
+        data.extend_from_slice(&chunk?);
+    }
+
+    Ok(data)
+}
+
+// Copies an object into a local file, for work that needs a seekable input
+pub async fn download_to_file(storage: &dyn Storage, key: &str, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    use tokio::io::AsyncWriteExt;
+
+    let mut stream = storage.get_range(key, None).await?;
+    let mut file = tokio::fs::File::create(path).await?;
+
+    while let Some(chunk) = stream.next().await {
+        file.write_all(&chunk?).await?;
+    }
+
+    file.flush().await
+}
+
+// Returns the bytes freed
+pub async fn delete_prefix(storage: &dyn Storage, prefix: &str) -> io::Result<u64> {
+    let mut freed = 0;
+    for object in storage.list(prefix).await? {
+        storage.delete(&object.key).await?;
+        freed += object.size;
+    }
+
+    Ok(freed)
+}
+
+// Chunks and the scratch file of an upload that won't complete, returns the
+// bytes freed
+pub async fn delete_upload_files(storage: &dyn Storage, track_id: uuid::Uuid) -> io::Result<u64> {
+    let mut freed = delete_prefix(storage, &temp_prefix(track_id)).await?;
+
+    let scratch = scratch_path(track_id);
+    if let Ok(metadata) = tokio::fs::metadata(&scratch).await {
+        tokio::fs::remove_file(&scratch).await?;
+        freed += metadata.len();
+    }
+
+    Ok(freed)
+}
+
+// An original and everything derived from it: renditions, HLS and waveforms.
+// Returns the bytes freed.
+pub async fn delete_audio_files(storage: &dyn Storage, content_hash: &str) -> io::Result<u64> {
+    let mut freed = 0;
+    for key in [
+        audio_key(content_hash),
+        waveform_key(content_hash, "json"),
+        waveform_key(content_hash, "dat"),
+    ] {
+        if let Some(object) = storage.stat(&key).await? {
+            storage.delete(&key).await?;
+            freed += object.size;
+        }
+    }
+
+    freed += delete_prefix(storage, &format!("uploads/renditions/{}/", content_hash)).await?;
+    freed += delete_prefix(storage, &format!("uploads/hls/{}/", content_hash)).await?;
+
+    Ok(freed)
+}
+
+pub const TEMP_PREFIX: &str = "uploads/temp/";
+
+// Chunks of an in-flight upload live under a prefix named after the track,
+// so two users uploading `song.mp3` at once never share a directory.
+pub fn temp_prefix(track_id: uuid::Uuid) -> String {
+    format!("{}{}/", TEMP_PREFIX, track_id)
+}
+
+pub fn chunk_key(track_id: uuid::Uuid, chunk_number: i32) -> String {
+    format!("{}chunk_{}", temp_prefix(track_id), chunk_number)
+}
+
+// Album archives wait here until the import job has unpacked them
+pub fn import_key(upload_id: uuid::Uuid) -> String {
+    format!("uploads/imports/{}.zip", upload_id)
+}
+
+// Local scratch file a track is assembled and probed in before it is stored
+pub fn scratch_path(track_id: uuid::Uuid) -> PathBuf {
+    std::env::temp_dir()
+        .join("music-platform")
+        .join(format!("{}.part", track_id))
+}
+
+// Assembled audio is stored by SHA-256 of its content, sharded on the first two
+// bytes of the hash (`uploads/audio/ab/cd/abcd...`) to keep directories small.
+pub fn audio_key(content_hash: &str) -> String {
+    format!(
+        "uploads/audio/{}/{}/{}",
+        &content_hash[0..2],
+        &content_hash[2..4],
+        content_hash
+    )
+}
+
+// Renditions derive from the audio alone, so they're shared by every track with
+// the same content: `uploads/renditions/{content_hash}/opus_96.opus`
+#[cfg(feature = "opus")]
+pub fn rendition_key(content_hash: &str, name: &str) -> String {
+    format!("uploads/renditions/{}/{}", content_hash, name)
+}
+
+// HLS playlists and segments are laid out as `uploads/hls/{content_hash}/master.m3u8`
+// with each variant in a directory of its own next to it
+pub fn hls_key(content_hash: &str, name: &str) -> String {
+    format!("uploads/hls/{}/{}", content_hash, name)
+}
+
+// Peak data for the player's scrubber: `uploads/waveforms/{content_hash}.json`
+pub fn waveform_key(content_hash: &str, extension: &str) -> String {
+    format!("uploads/waveforms/{}.{}", content_hash, extension)
+}
+
+// Tracks uploaded before content addressing still sit at `uploads/{file_name}`
+pub fn track_audio_key(track: &Track) -> Option<String> {
+    match (&track.content_hash, &track.file_name) {
+        (Some(content_hash), _) => Some(audio_key(content_hash)),
+        (None, Some(file_name)) => Some(format!("uploads/{}", file_name)),
+        (None, None) => None,
+    }
+}
+
+pub fn thumbnail_key(thumbnail_name: &str) -> String {
+    format!("assets/images/{}", thumbnail_name)
+}
+
+pub fn playlist_cover_key(thumbnail_name: &str) -> String {
+    format!("assets/playlist/{}", thumbnail_name)
+}
@@ -0,0 +1,181 @@
+use std::{io, path::Path};
+
+use async_trait::async_trait;
+use aws_sdk_s3::{
+    config::{Builder, Region},
+    error::{ProvideErrorMetadata, SdkError},
+    primitives::{ByteStream as S3ByteStream, DateTime as S3DateTime},
+    Client,
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use tokio_util::io::ReaderStream;
+
+use super::{ByteStream, ObjectMeta, Storage};
+use crate::config::Config;
+
+// Works against AWS S3 and S3-compatible servers such as MinIO. Credentials
+// come from the usual AWS environment variables / profiles.
+#[derive(Debug, Clone)]
+pub struct S3Storage {
+    client: Client,
+    bucket: String,
+}
+
+impl S3Storage {
+    pub async fn new(config: &Config) -> Self {
+        let shared_config = aws_config::load_from_env().await;
+        let mut builder = Builder::from(&shared_config);
+
+        if let Some(region) = &config.s3_region {
+            builder = builder.region(Region::new(region.clone()));
+        }
+
+        // MinIO and most self-hosted servers don't do virtual-host buckets
+        if let Some(endpoint) = &config.s3_endpoint {
+            builder = builder.endpoint_url(endpoint).force_path_style(true);
+        }
+
+        S3Storage {
+            client: Client::from_conf(builder.build()),
+            bucket: config
+                .s3_bucket
+                .clone()
+                .expect("S3_BUCKET must be set when STORAGE_BACKEND=s3"),
+        }
+    }
+}
+
+fn s3_error<E>(err: SdkError<E>) -> io::Error
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+{
+    let not_found = match &err {
+        SdkError::ServiceError(service_err) => {
+            matches!(service_err.err().code(), Some("NoSuchKey" | "NotFound"))
+                || service_err.raw().status().as_u16() == 404
+        }
+        _ => false,
+    };
+
+    if not_found {
+        io::Error::new(io::ErrorKind::NotFound, err)
+    } else {
+        io::Error::other(err)
+    }
+}
+
+fn to_chrono(date: Option<&S3DateTime>) -> DateTime<Utc> {
+    date.and_then(|date| DateTime::from_timestamp(date.secs(), date.subsec_nanos()))
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn put(&self, key: &str, data: Bytes) -> io::Result<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(S3ByteStream::from(data))
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(())
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> io::Result<()> {
+        let body = S3ByteStream::from_path(path)
+            .await
+            .map_err(io::Error::other)?;
+
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(body)
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        tokio::fs::remove_file(path).await
+    }
+
+    async fn get_range(&self, key: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .set_range(range.map(|(start, end)| format!("bytes={}-{}", start, end)))
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(ReaderStream::new(output.body.into_async_read()).boxed())
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(s3_error)?;
+
+        Ok(())
+    }
+
+    async fn stat(&self, key: &str) -> io::Result<Option<ObjectMeta>> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(Some(ObjectMeta {
+                key: key.to_string(),
+                size: output.content_length().unwrap_or_default() as u64,
+                last_modified: to_chrono(output.last_modified()),
+            })),
+            Err(err) => match s3_error(err) {
+                err if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                err => Err(err),
+            },
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> io::Result<Vec<ObjectMeta>> {
+        let mut objects = Vec::new();
+        let mut pages = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(prefix)
+            .into_paginator()
+            .send();
+
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(s3_error)?;
+
+            for object in page.contents() {
+                let Some(key) = object.key() else {
+                    continue;
+                };
+
+                objects.push(ObjectMeta {
+                    key: key.to_string(),
+                    size: object.size().unwrap_or_default() as u64,
+                    last_modified: to_chrono(object.last_modified()),
+                });
+            }
+        }
+
+        Ok(objects)
+    }
+}
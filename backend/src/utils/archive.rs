@@ -0,0 +1,234 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use sha2::{Digest, Sha256};
+use zip::ZipArchive;
+
+// Local file header signature, every non-empty ZIP starts with one
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+pub fn is_zip(header: &[u8]) -> bool {
+    header.starts_with(ZIP_MAGIC)
+}
+
+pub fn is_zip_file(path: &Path) -> io::Result<bool> {
+    let mut header = [0; ZIP_MAGIC.len()];
+    let read = File::open(path)?.read(&mut header)?;
+    Ok(is_zip(&header[..read]))
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub index: usize,
+    // Path inside the archive, always with `/` separators
+    pub path: String,
+    // Uncompressed, as the archive declares it
+    pub size: u64,
+}
+
+impl ArchiveEntry {
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    pub fn extension(&self) -> String {
+        self.file_name()
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_ascii_lowercase())
+            .unwrap_or_default()
+    }
+}
+
+// Files of the archive in path order. Directories, hidden files and the
+// `__MACOSX/` resource forks Finder adds are left out.
+pub fn list_entries(path: &Path) -> io::Result<Vec<ArchiveEntry>> {
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(io::Error::other)?;
+    let mut entries = Vec::new();
+
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(io::Error::other)?;
+        let path = entry.name().replace('\\', "/");
+
+        let hidden = path
+            .split('/')
+            .any(|part| part.starts_with('.') || part == "__MACOSX");
+        if entry.is_dir() || hidden {
+            continue;
+        }
+
+        entries.push(ArchiveEntry {
+            index,
+            path,
+            size: entry.size(),
+        });
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+// Unpacks an entry to `output` and returns its SHA-256. Entries that turn out
+// larger than `max_size`, or of another size than their header claimed, are
+// refused, so limits checked against the declared size hold.
+pub fn extract_entry(
+    archive: &Path,
+    index: usize,
+    output: &Path,
+    max_size: u64,
+) -> io::Result<String> {
+    let mut archive = ZipArchive::new(File::open(archive)?).map_err(io::Error::other)?;
+    let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+    let declared_size = entry.size();
+
+    let mut file = File::create(output)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut written: u64 = 0;
+
+    loop {
+        let read = entry.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        written += read as u64;
+        if written > declared_size {
+            return Err(size_mismatch());
+        }
+        if written > max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "File is larger than the upload limit",
+            ));
+        }
+
+        hasher.update(&buffer[..read]);
+        file.write_all(&buffer[..read])?;
+    }
+
+    if written != declared_size {
+        return Err(size_mismatch());
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn size_mismatch() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "File does not match the size the archive declares",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    use super::*;
+
+    // Scratch file of the test, gone when it's dropped
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join("music-platform-tests");
+            std::fs::create_dir_all(&dir).unwrap();
+            TempPath(dir.join(format!("{}-{}", std::process::id(), name)))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, data) in files {
+            if name.ends_with('/') {
+                zip.add_directory(*name, options).unwrap();
+            } else {
+                zip.start_file(*name, options).unwrap();
+                zip.write_all(data).unwrap();
+            }
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn sniffs_zip_files() {
+        let archive = TempPath::new("sniff.zip");
+        write_zip(&archive.0, &[("a.flac", b"audio")]);
+
+        assert!(is_zip_file(&archive.0).unwrap());
+        assert!(!is_zip(b"fLaC"));
+        assert!(!is_zip(b"PK"));
+    }
+
+    #[test]
+    fn lists_files_in_path_order() {
+        let archive = TempPath::new("list.zip");
+        write_zip(
+            &archive.0,
+            &[
+                ("Album/02 Second.MP3", b"two"),
+                ("Album/", b""),
+                ("Album/.DS_Store", b"finder"),
+                ("__MACOSX/Album/._01 First.flac", b"fork"),
+                ("Album/01 First.flac", b"first"),
+            ],
+        );
+
+        let entries = list_entries(&archive.0).unwrap();
+        let paths: Vec<&str> = entries.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["Album/01 First.flac", "Album/02 Second.MP3"]);
+
+        assert_eq!(entries[0].size, 5);
+        assert_eq!(entries[1].file_name(), "02 Second.MP3");
+        assert_eq!(entries[1].extension(), "mp3");
+    }
+
+    #[test]
+    fn extracts_and_hashes_entries() {
+        let archive = TempPath::new("extract.zip");
+        let output = TempPath::new("extract.flac");
+        write_zip(&archive.0, &[("a.flac", b"audio")]);
+        let index = list_entries(&archive.0).unwrap()[0].index;
+
+        let hash = extract_entry(&archive.0, index, &output.0, 5).unwrap();
+        assert_eq!(hash, hex::encode(Sha256::digest(b"audio")));
+        assert_eq!(std::fs::read(&output.0).unwrap(), b"audio");
+
+        let error = extract_entry(&archive.0, index, &output.0, 4).unwrap_err();
+        assert_eq!(error.to_string(), "File is larger than the upload limit");
+    }
+
+    #[test]
+    fn refuses_entries_larger_than_declared() {
+        let archive = TempPath::new("lying.zip");
+        let output = TempPath::new("lying.flac");
+        write_zip(&archive.0, &[("a.flac", b"audio")]);
+
+        // Claim a larger uncompressed size in the local and central headers
+        let mut data = std::fs::read(&archive.0).unwrap();
+        let central = data
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        data[22..26].copy_from_slice(&9u32.to_le_bytes());
+        data[central + 24..central + 28].copy_from_slice(&9u32.to_le_bytes());
+        std::fs::write(&archive.0, data).unwrap();
+
+        let entry = &list_entries(&archive.0).unwrap()[0];
+        assert_eq!(entry.size, 9);
+        let error = extract_entry(&archive.0, entry.index, &output.0, 100).unwrap_err();
+        assert_eq!(error.to_string(), size_mismatch().to_string());
+    }
+}
@@ -0,0 +1,99 @@
+use axum::http::{header, HeaderMap};
+use chrono::{DateTime, Utc};
+
+use crate::storage::ObjectMeta;
+
+// Audio is served under the track's id, which keeps its id when the audio is
+// replaced or rolled back. Clients revalidate, the ETags name the content so
+// unchanged audio is a cheap 304. They sit behind auth, hence `private`.
+pub const AUDIO_CACHE_CONTROL: &str = "private, no-cache";
+
+// Thumbnails, playlist covers and previews can be replaced in place, so clients
+// must revalidate them (answered with ETag / Last-Modified / 304).
+pub const ASSET_CACHE_CONTROL: &str = "public, no-cache";
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+#[derive(Debug, Clone)]
+pub struct Validators {
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+}
+
+impl Validators {
+    // Strong validator built from the stored object's size and modification time.
+    pub fn from_object(object: &ObjectMeta) -> Self {
+        let modified = object.last_modified;
+
+        Validators {
+            etag: format!(
+                "\"{:x}-{:x}{:08x}\"",
+                object.size,
+                modified.timestamp(),
+                modified.timestamp_subsec_nanos()
+            ),
+            // HTTP dates have second precision
+            last_modified: DateTime::from_timestamp(modified.timestamp(), 0).unwrap_or_default(),
+        }
+    }
+
+    pub fn with_etag(mut self, tag: &str) -> Self {
+        self.etag = format!("\"{}\"", tag);
+        self
+    }
+
+    pub fn last_modified_header(&self) -> String {
+        self.last_modified.format(HTTP_DATE_FORMAT).to_string()
+    }
+
+    // Evaluates `If-None-Match` / `If-Modified-Since` the way RFC 9110 orders
+    // them: when `If-None-Match` is present `If-Modified-Since` is ignored.
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+            let Ok(if_none_match) = if_none_match.to_str() else {
+                return false;
+            };
+
+            return if_none_match.trim() == "*"
+                || if_none_match
+                    .split(',')
+                    .any(|tag| weak_eq(tag.trim(), &self.etag));
+        }
+
+        headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_http_date)
+            .is_some_and(|since| self.last_modified <= since)
+    }
+
+    // A Range request carrying `If-Range` only gets a partial response when
+    // the validator still matches, otherwise the whole file is sent.
+    pub fn range_applies(&self, headers: &HeaderMap) -> bool {
+        let Some(if_range) = headers.get(header::IF_RANGE) else {
+            return true;
+        };
+
+        let Ok(if_range) = if_range.to_str() else {
+            return false;
+        };
+        let if_range = if_range.trim();
+
+        // Weak tags never match here, If-Range needs a strong comparison
+        if if_range.starts_with('"') || if_range.starts_with("W/") {
+            return if_range == self.etag;
+        }
+
+        parse_http_date(if_range).is_some_and(|date| date == self.last_modified)
+    }
+}
+
+fn weak_eq(tag: &str, etag: &str) -> bool {
+    tag.trim_start_matches("W/") == etag.trim_start_matches("W/")
+}
+
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
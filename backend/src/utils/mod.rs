@@ -1,2 +1,7 @@
+pub mod archive;
+pub mod cache;
 pub mod password;
-pub mod token;
\ No newline at end of file
+pub mod range;
+pub mod stream;
+pub mod token;
+pub mod validate;
\ No newline at end of file
@@ -0,0 +1,159 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    // No usable Range header, serve the whole file
+    Full,
+    // Inclusive byte range within the file
+    Partial { start: u64, end: u64 },
+    // Syntactically valid range that does not overlap the file
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    pub fn len(&self, file_size: u64) -> u64 {
+        match self {
+            ByteRange::Full => file_size,
+            ByteRange::Partial { start, end } => end - start + 1,
+            ByteRange::Unsatisfiable => 0,
+        }
+    }
+}
+
+// Parses a `Range` header value against the size of the file being served.
+//
+// Only single `bytes` ranges are supported. Anything we cannot parse (other
+// units, multiple ranges, garbage) is ignored and the whole file is served,
+// which is what RFC 9110 asks servers to do with ranges they don't understand.
+pub fn parse_range(header: Option<&str>, file_size: u64) -> ByteRange {
+    let Some(value) = header else {
+        return ByteRange::Full;
+    };
+
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = (start.trim(), end.trim());
+
+    // Suffix range: `bytes=-500` asks for the last 500 bytes
+    if start.is_empty() {
+        let Ok(suffix) = end.parse::<u64>() else {
+            return ByteRange::Full;
+        };
+
+        if suffix == 0 || file_size == 0 {
+            return ByteRange::Unsatisfiable;
+        }
+
+        return ByteRange::Partial {
+            start: file_size.saturating_sub(suffix),
+            end: file_size - 1,
+        };
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+
+    let end = if end.is_empty() {
+        None
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) => Some(end),
+            Err(_) => return ByteRange::Full,
+        }
+    };
+
+    if let Some(end) = end {
+        if end < start {
+            return ByteRange::Full;
+        }
+    }
+
+    if start >= file_size {
+        return ByteRange::Unsatisfiable;
+    }
+
+    let last = file_size - 1;
+
+    ByteRange::Partial {
+        start,
+        end: end.map_or(last, |end| end.min(last)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_or_foreign_headers_serve_the_whole_file() {
+        assert_eq!(parse_range(None, 1000), ByteRange::Full);
+        assert_eq!(parse_range(Some("items=0-10"), 1000), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=0-10,20-30"), 1000), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=abc-10"), 1000), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=10-5"), 1000), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=10"), 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn bounded_and_open_ranges() {
+        assert_eq!(
+            parse_range(Some("bytes=0-99"), 1000),
+            ByteRange::Partial { start: 0, end: 99 }
+        );
+        assert_eq!(
+            parse_range(Some("bytes=500-"), 1000),
+            ByteRange::Partial {
+                start: 500,
+                end: 999
+            }
+        );
+        // The end is clamped to the file rather than refused
+        assert_eq!(
+            parse_range(Some("bytes=900-5000"), 1000),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(parse_range(Some(" bytes= 1 - 2 "), 1000).len(1000), 2);
+    }
+
+    #[test]
+    fn suffix_ranges() {
+        assert_eq!(
+            parse_range(Some("bytes=-100"), 1000),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-5000"), 1000),
+            ByteRange::Partial { start: 0, end: 999 }
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-0"), 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_range(Some("bytes=-10"), 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn ranges_past_the_end_are_unsatisfiable() {
+        assert_eq!(
+            parse_range(Some("bytes=1000-"), 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_range(Some("bytes=0-"), 0), ByteRange::Unsatisfiable);
+        assert_eq!(ByteRange::Unsatisfiable.len(1000), 0);
+    }
+}
@@ -0,0 +1,130 @@
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Response, StatusCode},
+};
+
+use crate::{
+    audio::format::{mime_type, Container},
+    error::HttpError,
+    storage::{self, Storage},
+    utils::{
+        cache::{Validators, AUDIO_CACHE_CONTROL},
+        range::{parse_range, ByteRange},
+    },
+};
+
+// Streams a stored object, honouring conditional and Range requests.
+// Only the requested bytes are ever read, whatever the size of the object.
+pub async fn serve_object(
+    storage: &dyn Storage,
+    key: &str,
+    content_type: &str,
+    cache_control: &'static str,
+    content_hash: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Response<Body>, HttpError> {
+    let object = storage
+        .stat(key)
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or(HttpError::not_found("File Not Found"))?;
+    let file_size = object.size;
+
+    // Content-addressed files make the hash the natural strong validator
+    let validators = match content_hash {
+        Some(content_hash) => Validators::from_object(&object).with_etag(content_hash),
+        None => Validators::from_object(&object),
+    };
+
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::ACCEPT_RANGES, "bytes") // Allow range requests
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::ETAG, &validators.etag)
+        .header(header::LAST_MODIFIED, validators.last_modified_header());
+
+    if validators.is_not_modified(headers) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .map_err(|e| HttpError::server_error(e.to_string()));
+    }
+
+    let range = if validators.range_applies(headers) {
+        let range_header = headers
+            .get(header::RANGE)
+            .and_then(|value| value.to_str().ok());
+
+        parse_range(range_header, file_size)
+    } else {
+        ByteRange::Full
+    };
+
+    let response = match range {
+        ByteRange::Unsatisfiable => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", file_size))
+            .header(header::CONTENT_LENGTH, 0)
+            .body(Body::empty()),
+        ByteRange::Full => {
+            let stream = storage
+                .get_range(key, None)
+                .await
+                .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+            builder
+                .header(header::CONTENT_DISPOSITION, "inline")
+                .header(header::CONTENT_LENGTH, file_size)
+                .body(Body::from_stream(stream))
+        }
+        ByteRange::Partial { start, end } => {
+            // Only fetch the requested slice, never the rest of the object
+            let stream = storage
+                .get_range(key, Some((start, end)))
+                .await
+                .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+            builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", start, end, file_size),
+                )
+                .header(header::CONTENT_LENGTH, range.len(file_size))
+                .body(Body::from_stream(stream))
+        }
+    };
+
+    response.map_err(|e| HttpError::server_error(e.to_string()))
+}
+
+pub async fn stream_audio(
+    storage: &dyn Storage,
+    key: &str,
+    content_type: Option<String>,
+    content_hash: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Response<Body>, HttpError> {
+    // Tracks assembled before the format was recorded get sniffed on the fly
+    let content_type = match content_type {
+        Some(content_type) => content_type,
+        None => {
+            let header = storage::read_range(storage, key, Some((0, 63)))
+                .await
+                .unwrap_or_default();
+
+            Container::sniff(&header)
+                .map_or("audio/mpeg".to_string(), |container| mime_type(container, None))
+        }
+    };
+
+    serve_object(
+        storage,
+        key,
+        &content_type,
+        AUDIO_CACHE_CONTROL,
+        content_hash,
+        headers,
+    )
+    .await
+}
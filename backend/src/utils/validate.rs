@@ -0,0 +1,179 @@
+use std::io::Cursor;
+
+use axum::http::StatusCode;
+use image::{ImageFormat, ImageReader, Limits};
+
+use crate::{
+    audio::{format::Container, probe::AudioInfo},
+    config::Config,
+    error::HttpError,
+};
+
+fn unsupported(message: impl Into<String>) -> HttpError {
+    HttpError::new(message, StatusCode::UNSUPPORTED_MEDIA_TYPE)
+}
+
+fn too_large(message: impl Into<String>) -> HttpError {
+    HttpError::new(message, StatusCode::PAYLOAD_TOO_LARGE)
+}
+
+pub fn format_size(bytes: i64) -> String {
+    if bytes >= 1024 * 1024 * 1024 {
+        format!("{} GB", bytes / (1024 * 1024 * 1024))
+    } else if bytes >= 1024 * 1024 {
+        format!("{} MB", bytes / (1024 * 1024))
+    } else {
+        format!("{} KB", bytes / 1024)
+    }
+}
+
+fn format_duration(seconds: i64) -> String {
+    if seconds >= 60 {
+        format!("{} minute", seconds / 60)
+    } else {
+        format!("{} second", seconds)
+    }
+}
+
+// Looks at the magic bytes of the first chunk, before anything is stored
+pub fn check_audio_header(config: &Config, header: &[u8]) -> Result<Container, HttpError> {
+    let container = Container::sniff(header)
+        .ok_or(unsupported("File is not a recognised audio format"))?;
+
+    if !config
+        .allowed_containers
+        .iter()
+        .any(|allowed| allowed == container.as_str())
+    {
+        return Err(unsupported(format!(
+            "{} files are not accepted, allowed formats are {}",
+            container.as_str(),
+            config.allowed_containers.join(", ")
+        )));
+    }
+
+    Ok(container)
+}
+
+// Uploads are a single audio file or a ZIP archive of an album
+pub fn check_upload_header(config: &Config, header: &[u8]) -> Result<(), HttpError> {
+    if super::archive::is_zip(header) {
+        return Ok(());
+    }
+
+    check_audio_header(config, header).map(|_| ())
+}
+
+pub fn check_upload_size(config: &Config, size: i64) -> Result<(), HttpError> {
+    if size > config.max_upload_size {
+        return Err(too_large(format!(
+            "File is larger than the {} upload limit",
+            format_size(config.max_upload_size)
+        )));
+    }
+
+    Ok(())
+}
+
+// Codec and duration are only known once the whole file is probed. The error is
+// kept as the track's failure reason.
+pub fn check_audio(config: &Config, audio_info: &AudioInfo) -> Result<(), String> {
+    if !config
+        .allowed_containers
+        .iter()
+        .any(|allowed| allowed == audio_info.container.as_str())
+    {
+        return Err(format!(
+            "{} files are not accepted, allowed formats are {}",
+            audio_info.container.as_str(),
+            config.allowed_containers.join(", ")
+        ));
+    }
+
+    if !config.allowed_codecs.contains(&audio_info.codec) {
+        return Err(format!(
+            "{} audio is not accepted, allowed codecs are {}",
+            audio_info.codec,
+            config.allowed_codecs.join(", ")
+        ));
+    }
+
+    if audio_info.duration.num_seconds() > config.max_track_duration {
+        return Err(format!(
+            "Track is longer than the {} limit",
+            format_duration(config.max_track_duration)
+        ));
+    }
+
+    Ok(())
+}
+
+fn image_type(format: ImageFormat) -> Option<&'static str> {
+    match format {
+        ImageFormat::Jpeg => Some("jpeg"),
+        ImageFormat::Png => Some("png"),
+        ImageFormat::WebP => Some("webp"),
+        ImageFormat::Gif => Some("gif"),
+        _ => None,
+    }
+}
+
+// Thumbnails and covers are served straight from storage, so they have to be
+// real images. Returns the file extension matching the detected type.
+pub fn check_image(config: &Config, data: &[u8]) -> Result<&'static str, HttpError> {
+    if data.len() > config.max_image_size {
+        return Err(too_large(format!(
+            "Image is larger than the {} limit",
+            format_size(config.max_image_size as i64)
+        )));
+    }
+
+    let reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|_| HttpError::bad_request("Image could not be read"))?;
+
+    let image_type = reader
+        .format()
+        .and_then(image_type)
+        .filter(|image_type| {
+            config
+                .allowed_image_types
+                .iter()
+                .any(|allowed| allowed == image_type)
+        })
+        .ok_or(unsupported(format!(
+            "Image must be one of {}",
+            config.allowed_image_types.join(", ")
+        )))?;
+
+    // Only the header is read for the dimensions, so an oversized image is
+    // turned down before it's decoded
+    let (width, height) = reader
+        .into_dimensions()
+        .map_err(|_| HttpError::bad_request("Image could not be read"))?;
+    let max_dimension = config.max_image_dimension;
+
+    if width > max_dimension || height > max_dimension {
+        return Err(HttpError::bad_request(format!(
+            "Image must be at most {}x{} pixels",
+            max_dimension, max_dimension
+        )));
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(max_dimension);
+    limits.max_image_height = Some(max_dimension);
+
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|_| HttpError::bad_request("Image could not be read"))?;
+    reader.limits(limits);
+    reader
+        .decode()
+        .map_err(|_| HttpError::bad_request("Image is corrupt or truncated"))?;
+
+    Ok(match image_type {
+        "jpeg" => "jpg",
+        image_type => image_type,
+    })
+}
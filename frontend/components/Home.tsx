@@ -15,7 +15,6 @@ export interface TrackProps {
   duration_minutes: number;
   duration_seconds: number;
   duration_played: number;
-  file_name: string;
   thumbnail_name: string;
   is_favorite: boolean;
   played_at: string;
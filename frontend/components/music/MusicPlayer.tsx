@@ -98,7 +98,7 @@ const MusicPlayer = () => {
 
     if (audioElement) {
         // Set the audio source
-        audioElement.src = `${apiUrl}/get/play/${currentSong.file_name}`;
+        audioElement.src = `${apiUrl}/tracks/${currentSong.id}/stream`;
 
         // Set the current time based on the duration played
         if (currentSong.duration_played) {
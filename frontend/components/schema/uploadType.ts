@@ -12,9 +12,10 @@ export const fileSchema = z.object({
         
 })
 
+// Left empty, title and artist are taken from the file's tags
 export const trackSchema = z.object({
-    title: z.string().min(1, { message: "Title is required" }),
-    artist: z.string().min(1, { message: "Artist is required" }),
+    title: z.string(),
+    artist: z.string(),
 });
 
 export const ALLOWED_IMAGE_TYPES = [
@@ -18,7 +18,7 @@ import { Music } from "lucide-react";
 import { Input } from "../ui/input";
 import { Button } from "../ui/button";
 import { Logout } from "@/action/authHandler";
-import { CHUNK_SIZE } from "./uploadFile";
+import { CHUNK_RETRIES, CHUNK_SIZE, sha256Hex } from "./uploadFile";
 import { useRouter } from "next/navigation";
 
 interface incompleteTrackInfoProps {
@@ -60,17 +60,33 @@ export const ResumeUploadFile: React.FC<ResumeUploadFile> = ({
     setIsLoading(true);
     const file = values.file;
     const totalChunks = Math.ceil(file.size / CHUNK_SIZE);
-    let currentChunk = parseInt(track.current_chunk) + 1;
     const track_id = track.track_id;
     if (totalChunks != parseInt(track.total_chunks)) {
         setIsLoading(false);
         return;
     }
-    console.log("inSubmit")
-    console.log(currentChunk, "currentChunk")
-    console.log(track.current_chunk, "track.current_chuck")
 
-    while (currentChunk < totalChunks) {
+    // Ask the server which chunks it still needs, they may be anywhere in the file
+    const statusResult = await fetch(`${API_BASE_URL}/upload/${track_id}/status`, {
+      credentials: "include",
+    });
+    if (statusResult.status === 401) {
+      Logout();
+      return;
+    }
+    if (!statusResult.ok) {
+      console.error(`Error reading upload status: ${statusResult.status}`);
+      setIsLoading(false);
+      return;
+    }
+    const { missing_chunks }: { missing_chunks: number[] } = await statusResult.json();
+
+    let attempts = 0;
+    const fileHash = await sha256Hex(file);
+    let index = 0;
+
+    while (index < missing_chunks.length) {
+      const currentChunk = missing_chunks[index];
       const start = currentChunk * CHUNK_SIZE;
       const end = Math.min(start + CHUNK_SIZE, file.size);
       const chuck = file.slice(start, end);
@@ -78,6 +94,8 @@ export const ResumeUploadFile: React.FC<ResumeUploadFile> = ({
       formData.append("fileName", file.name);
       formData.append("chunkNumber", currentChunk.toString());
       formData.append("totalChunks", totalChunks.toString());
+      formData.append("chunkHash", await sha256Hex(chuck));
+      formData.append("fileHash", fileHash);
       formData.append("chunk", chuck);
       formData.append("trackId", track_id);
 
@@ -93,6 +111,11 @@ export const ResumeUploadFile: React.FC<ResumeUploadFile> = ({
           return; // Stop further processing after logout
         }
 
+        if (result.status === 422 && attempts < CHUNK_RETRIES) {
+          attempts++;
+          continue; // Send the same chunk again
+        }
+
         if (!result.ok) {
           const errorText = await result.text(); // Read response body for details
           console.error(`Error uploading chunk: ${result.status} ${errorText}`);
@@ -100,7 +123,8 @@ export const ResumeUploadFile: React.FC<ResumeUploadFile> = ({
           break; // Break on error
         }
 
-        currentChunk++;
+        attempts = 0;
+        index++;
       } catch (error) {
         if (error instanceof Error) {
           if (error.message.includes("401")) {
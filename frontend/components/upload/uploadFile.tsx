@@ -23,6 +23,14 @@ interface UploadFileProps {
 }
 
 export const CHUNK_SIZE = 5 * 1024 * 1024; // 5MB chunk size
+export const CHUNK_RETRIES = 3; // the server answers 422 when a chunk arrives corrupted
+
+export const sha256Hex = async (data: Blob): Promise<string> => {
+  const digest = await crypto.subtle.digest("SHA-256", await data.arrayBuffer());
+  return Array.from(new Uint8Array(digest))
+    .map((byte) => byte.toString(16).padStart(2, "0"))
+    .join("");
+};
 
 export const UploadFile: React.FC<UploadFileProps> = ({
   nextStep,
@@ -45,6 +53,8 @@ export const UploadFile: React.FC<UploadFileProps> = ({
     let currentChunk = 0;
     setUploadProgress(0);
     let track_id = null;
+    let attempts = 0;
+    const fileHash = await sha256Hex(file);
 
     while (currentChunk < totalChunks) {
       const start = currentChunk * CHUNK_SIZE;
@@ -54,6 +64,8 @@ export const UploadFile: React.FC<UploadFileProps> = ({
       formData.append("fileName", file.name);
       formData.append("chunkNumber", currentChunk.toString());
       formData.append("totalChunks", totalChunks.toString());
+      formData.append("chunkHash", await sha256Hex(chuck));
+      formData.append("fileHash", fileHash);
       formData.append("chunk", chuck);
       if (track_id != null) {
         formData.append("trackId", track_id);
@@ -71,12 +83,18 @@ export const UploadFile: React.FC<UploadFileProps> = ({
           return; // Stop further processing after logout
         }
 
+        if (result.status === 422 && attempts < CHUNK_RETRIES) {
+          attempts++;
+          continue; // Send the same chunk again
+        }
+
         if (!result.ok) {
           const errorText = await result.text(); // Read response body for details
           console.error(`Error uploading chunk: ${result.status} ${errorText}`);
           break; // Break on error
         }
 
+        attempts = 0;
         const response = await result.json();
         if (currentChunk === 0) {
           // Fix typo from 1 to 0
@@ -45,7 +45,7 @@ export const UploadTrack: React.FC<UploadTrackProps> = ({
                             <Input 
                                 {...field}
                                 type="text"
-                                placeholder="Enter song title"
+                                placeholder="Leave empty to use the title from the file"
                             />
                         </FormControl>
                         <FormMessage />
@@ -62,7 +62,7 @@ export const UploadTrack: React.FC<UploadTrackProps> = ({
                             <Input 
                                 {...field}
                                 type="text"
-                                placeholder="Enter song artist"
+                                placeholder="Leave empty to use the artist from the file"
                             />
                         </FormControl>
                         <FormMessage />